@@ -0,0 +1,598 @@
+//! Random walks on arbitrary undirected graphs.
+//!
+//! [Football](crate::Football) hardcodes the 20-node graph of Andy's hexagons, with exactly three
+//! neighbours per node. [GraphWalk] lifts both restrictions, so that any graph can be explored with
+//! the same [RandomWalk] machinery.
+
+use crate::{solve_linear_system, RandomWalk};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::collections::HashMap;
+
+/// The most iterations the power method behind
+/// [stationary_distribution](GraphWalk::stationary_distribution) will make.
+const MAX_POWER_ITERATIONS: usize = 100_000;
+
+/// A random walk on an undirected graph, moving at each step to one of the current node's
+/// neighbours, chosen uniformly at random. Nodes may have any number of neighbours.
+pub struct GraphWalk {
+    curr: u32,
+    adjacency: HashMap<u32, Vec<u32>>,
+    /// Relative weights of moving along particular edges, in the direction `(from, to)`. Edges
+    /// not listed here have weight 1. While this is empty, every move is uniform.
+    weights: HashMap<(u32, u32), f64>,
+}
+
+impl GraphWalk {
+    /// Create a graph walk from a list of undirected edges. Each edge `(a, b)` lets the walk move
+    /// from `a` to `b` and from `b` to `a`; repeated edges are ignored.
+    pub fn from_edges(edges: &[(u32, u32)]) -> Self {
+        let mut adjacency: HashMap<u32, Vec<u32>> = HashMap::new();
+        for (a, b) in edges {
+            let a_neighbours = adjacency.entry(*a).or_default();
+            if !a_neighbours.contains(b) {
+                a_neighbours.push(*b);
+            }
+
+            let b_neighbours = adjacency.entry(*b).or_default();
+            if !b_neighbours.contains(a) {
+                b_neighbours.push(*a);
+            }
+        }
+
+        for neighbours in adjacency.values_mut() {
+            neighbours.sort();
+        }
+
+        Self {
+            curr: edges.first().map_or(0, |(a, _)| *a),
+            adjacency,
+            weights: HashMap::new(),
+        }
+    }
+
+    /// Create a graph walk from an adjacency matrix, where nodes are numbered from 0 and
+    /// `matrix[a][b]` is `true` if there is an edge between `a` and `b`. As edges are undirected,
+    /// only one of `matrix[a][b]` and `matrix[b][a]` needs to be set.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the matrix is not square.
+    pub fn from_adjacency_matrix(matrix: &[Vec<bool>]) -> Self {
+        assert!(
+            matrix.iter().all(|row| row.len() == matrix.len()),
+            "adjacency matrix should be square"
+        );
+
+        let edges: Vec<(u32, u32)> = matrix
+            .iter()
+            .enumerate()
+            .flat_map(|(a, row)| {
+                row.iter()
+                    .enumerate()
+                    .filter(|(_, edge)| **edge)
+                    .map(move |(b, _)| (a as u32, b as u32))
+            })
+            .collect();
+
+        Self::from_edges(&edges)
+    }
+
+    /// Parse a graph walk from an undirected graph in the
+    /// [DOT language](https://graphviz.org/doc/info/lang.html), such as [to_dot](Self::to_dot)
+    /// produces. Only a simple subset is supported: a `graph { ... }` block, optionally named,
+    /// whose statements are separated by semicolons or new lines and are either edges such as
+    /// `1 -- 2` (or chains of them, `1 -- 2 -- 3`) or lone node IDs. Node IDs must be `u32`s.
+    /// Attributes aren't supported. Nodes without any edges are ignored, as a walk could never
+    /// leave them.
+    pub fn from_dot(s: &str) -> Result<Self, ParseError> {
+        let s = s.trim();
+        let header_end = s.find('{').ok_or(ParseError::MissingHeader)?;
+        let header: Vec<&str> = s[..header_end].split_whitespace().collect();
+        if !matches!(header.as_slice(), ["graph"] | ["graph", _]) {
+            return Err(ParseError::MissingHeader);
+        }
+        let body = s[header_end + 1..]
+            .strip_suffix('}')
+            .ok_or(ParseError::MissingClosingBrace)?;
+
+        let mut edges = Vec::new();
+        for statement in body.split([';', '\n']).map(str::trim) {
+            if statement.is_empty() {
+                continue;
+            }
+            if statement.contains(['[', '=', '{', '}']) || statement.contains("->") {
+                return Err(ParseError::UnsupportedStatement(statement.to_string()));
+            }
+
+            let nodes = statement
+                .split("--")
+                .map(|id| {
+                    id.trim()
+                        .parse::<u32>()
+                        .map_err(|_| ParseError::InvalidNode(id.trim().to_string()))
+                })
+                .collect::<Result<Vec<u32>, _>>()?;
+            edges.extend(nodes.windows(2).map(|pair| (pair[0], pair[1])));
+        }
+
+        Ok(Self::from_edges(&edges))
+    }
+
+    /// Describe the graph in the [DOT language](https://graphviz.org/doc/info/lang.html), for
+    /// rendering with Graphviz or reading back with [from_dot](Self::from_dot). Each edge is
+    /// listed once. Weights are not included.
+    pub fn to_dot(&self) -> String {
+        let mut nodes: Vec<&u32> = self.adjacency.keys().collect();
+        nodes.sort();
+
+        let mut dot = String::from("graph {\n");
+        for node in nodes {
+            for n in self.adjacency[node].iter().filter(|n| *n >= node) {
+                dot.push_str(&format!("    {} -- {};\n", node, n));
+            }
+        }
+        dot.push_str("}\n");
+
+        dot
+    }
+
+    /// Set the relative weight of moving from `from` to `to`, biasing the walk. The weight of the
+    /// reverse move is unaffected. All moves start with weight 1.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no edge between `from` and `to`.
+    pub fn set_weight(&mut self, from: u32, to: u32, weight: f64) {
+        assert!(
+            self.adjacency
+                .get(&from)
+                .is_some_and(|possibles| possibles.contains(&to)),
+            "can only weight an existing edge"
+        );
+        self.weights.insert((from, to), weight);
+    }
+
+    /// The long-run fraction of time the walk spends at each node.
+    ///
+    /// If every node has the same number of neighbours and no weights have been set, this is
+    /// uniform. Otherwise it is found by power iteration on the transition matrix. The iteration
+    /// uses the "lazy" walk, which stays put half the time, so that it converges even on
+    /// bipartite graphs where the walk itself alternates between the two halves forever; the lazy
+    /// walk has the same stationary distribution. If the graph is disconnected the stationary
+    /// distribution isn't unique, and the one returned depends on the sizes of the components.
+    pub fn stationary_distribution(&self) -> HashMap<u32, f64> {
+        let (nodes, transitions) = self.transition_matrix();
+        stationary(&transitions)
+            .into_iter()
+            .zip(nodes)
+            .map(|(p, node)| (node, p))
+            .collect()
+    }
+
+    /// The spectral gap of the walk, $1 - |\lambda_2|$, where $|\lambda_2|$ is the
+    /// second-largest magnitude of any eigenvalue of the transition matrix. The largest is always
+    /// 1, for the stationary distribution. The closer the gap is to 1, the faster the walk forgets
+    /// where it started and approaches the stationary distribution; a gap of 0 means it never
+    /// does, e.g. on a bipartite graph.
+    ///
+    /// The magnitude $|\lambda_2|$ is the rate at which power iteration shrinks a vector with no
+    /// component along the stationary distribution, so it is estimated rather than computed
+    /// exactly. It should be accurate to many decimal places unless the next eigenvalue has a
+    /// magnitude very close to $|\lambda_2|$.
+    pub fn spectral_gap(&self) -> f64 {
+        let (_, transitions) = self.transition_matrix();
+        let n = transitions.len();
+        let pi = stationary(&transitions);
+
+        // Start from a random vector (with a fixed seed, for repeatability), as one with any
+        // structure could miss the eigenvectors of $\lambda_2$ on a symmetric graph. Summing to
+        // zero makes it orthogonal to the right eigenvector of all ones, and this is preserved by
+        // the iteration, so the stationary distribution never reappears except through rounding.
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut x: Vec<f64> = (0..n).map(|_| rng.gen_range(-1.0..1.0)).collect();
+
+        // The eigenvalues of largest magnitude may be a complex pair, or both signs, so that the
+        // norm doesn't shrink by exactly the same factor every step. Average over the final
+        // `window` steps, once the smaller eigenvalues have died away.
+        let (iterations, window) = (10_000, 1_000);
+        let mut log_shrinkage = Vec::with_capacity(window);
+        for i in 0..iterations {
+            let sum: f64 = x.iter().sum();
+            for (xi, p) in x.iter_mut().zip(&pi) {
+                *xi -= sum * p;
+            }
+
+            let norm = x.iter().map(|xi| xi * xi).sum::<f64>().sqrt();
+            if norm < 1e-300 {
+                // Every other eigenvalue is zero.
+                return 1.0;
+            }
+            x.iter_mut().for_each(|xi| *xi /= norm);
+            if i >= iterations - window {
+                log_shrinkage.push(norm.ln());
+            }
+
+            x = step(&x, &transitions);
+        }
+
+        let lambda = (log_shrinkage.iter().sum::<f64>() / log_shrinkage.len() as f64).exp();
+        1.0 - lambda.min(1.0)
+    }
+
+    /// Calculate exactly the probability that a walk starting at `home` returns there within `k`
+    /// steps, taking any weights into account.
+    ///
+    /// The probability of being at each node is pushed through the transition matrix one step at
+    /// a time, with `home` absorbing: whatever arrives there is added to the total and removed, as
+    /// those walks have ended. This takes `k` matrix-vector products, so is exact up to rounding
+    /// rather than a Monte Carlo estimate.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `home` is not a node of the graph.
+    pub fn return_probability_within(&self, home: u32, k: u32) -> f64 {
+        let (nodes, transitions) = self.transition_matrix();
+        let h = nodes
+            .binary_search(&home)
+            .expect("home should be a node of the graph");
+
+        let mut x = vec![0.0; nodes.len()];
+        x[h] = 1.0;
+
+        let mut returned = 0.0;
+        for _ in 0..k {
+            x = step(&x, &transitions);
+            returned += x[h];
+            x[h] = 0.0;
+        }
+
+        returned
+    }
+
+    /// Calculate exactly the expected number of steps for a walk starting at `home` to first
+    /// return there, taking any weights into account.
+    ///
+    /// As with [Football::expected_return_time](crate::Football::expected_return_time), the
+    /// expected hitting times $h_k$ of `home` from every other node $k$ satisfy
+    /// $h_k = 1 + \sum_j P_{kj} h_j$, with $h_{\text{home}} = 0$. Solving this linear system, the
+    /// expected return time is $1 + \sum_j P_{\text{home}, j} h_j$.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `home` is not a node of the graph, or if some node can't reach `home`, as then
+    /// the system has no solution.
+    pub fn expected_return_time(&self, home: u32) -> f64 {
+        let (nodes, transitions) = self.transition_matrix();
+        let h = nodes
+            .binary_search(&home)
+            .expect("home should be a node of the graph");
+
+        // The unknowns are the hitting times of every node other than `home`, so drop its row and
+        // column from $I - P$.
+        let others: Vec<usize> = (0..nodes.len()).filter(|i| *i != h).collect();
+        let mut a: Vec<Vec<f64>> = others
+            .iter()
+            .map(|i| {
+                others
+                    .iter()
+                    .map(|j| if i == j { 1.0 } else { 0.0 } - transitions[*i][*j])
+                    .collect()
+            })
+            .collect();
+        let mut b = vec![1.0; others.len()];
+        solve_linear_system(&mut a, &mut b);
+
+        1.0 + others
+            .iter()
+            .zip(&b)
+            .map(|(j, h_j)| transitions[h][*j] * h_j)
+            .sum::<f64>()
+    }
+
+    /// The nodes in ascending order, along with the matrix of probabilities of moving from the
+    /// `i`th node to the `j`th, taking any weights into account.
+    fn transition_matrix(&self) -> (Vec<u32>, Vec<Vec<f64>>) {
+        let mut nodes: Vec<u32> = self.adjacency.keys().copied().collect();
+        nodes.sort();
+        let index: HashMap<u32, usize> = nodes.iter().enumerate().map(|(i, n)| (*n, i)).collect();
+
+        let mut transitions = vec![vec![0.0; nodes.len()]; nodes.len()];
+        for (i, node) in nodes.iter().enumerate() {
+            let neighbours = &self.adjacency[node];
+            let weights: Vec<f64> = neighbours
+                .iter()
+                .map(|n| *self.weights.get(&(*node, *n)).unwrap_or(&1.0))
+                .collect();
+            let total: f64 = weights.iter().sum();
+
+            for (n, w) in neighbours.iter().zip(weights) {
+                transitions[i][index[n]] += w / total;
+            }
+        }
+
+        (nodes, transitions)
+    }
+}
+
+/// Multiply the row vector `x` by the matrix `m`.
+fn step(x: &[f64], m: &[Vec<f64>]) -> Vec<f64> {
+    let mut next = vec![0.0; x.len()];
+    for (xi, row) in x.iter().zip(m) {
+        for (next_j, m_ij) in next.iter_mut().zip(row) {
+            *next_j += xi * m_ij;
+        }
+    }
+    next
+}
+
+/// The stationary distribution of the transition matrix `m`. See
+/// [GraphWalk::stationary_distribution].
+fn stationary(m: &[Vec<f64>]) -> Vec<f64> {
+    let n = m.len();
+    let uniform = vec![1.0 / n as f64; n];
+
+    // A uniform distribution is stationary exactly when the columns sum to one as well as the rows,
+    // as they do for an unweighted walk on a regular graph.
+    let col_sums = step(&vec![1.0; n], m);
+    if col_sums.iter().all(|s| (s - 1.0).abs() < 1e-12) {
+        return uniform;
+    }
+
+    let mut pi = uniform;
+    for _ in 0..MAX_POWER_ITERATIONS {
+        let moved = step(&pi, m);
+        let next: Vec<f64> = pi.iter().zip(&moved).map(|(p, q)| (p + q) / 2.0).collect();
+        let change: f64 = next.iter().zip(&pi).map(|(a, b)| (a - b).abs()).sum();
+        pi = next;
+
+        if change < 1e-15 {
+            break;
+        }
+    }
+
+    pi
+}
+
+/// An error returned when parsing a [GraphWalk] from the DOT language.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// The input doesn't start with `graph {`, or `graph name {`.
+    MissingHeader,
+    /// The input doesn't end with a closing `}`.
+    MissingClosingBrace,
+    /// A node ID is not a `u32`. Contains the ID.
+    InvalidNode(String),
+    /// A statement uses DOT features beyond plain nodes and undirected edges, such as attributes
+    /// or directed edges. Contains the statement.
+    UnsupportedStatement(String),
+}
+
+impl RandomWalk for GraphWalk {
+    type State = u32;
+
+    fn make_move<R: Rng>(&mut self, rng: &mut R) {
+        if !self.weights.is_empty() {
+            return self.make_weighted_move(rng);
+        }
+
+        let possibles = self.adjacency.get(&self.curr).unwrap();
+        self.curr = possibles[rng.gen_range(0..possibles.len())];
+    }
+
+    fn get_state(&self) -> Self::State {
+        self.curr
+    }
+
+    fn set_state(&mut self, state: Self::State) {
+        self.curr = state;
+    }
+
+    fn move_weights(&self) -> Option<Vec<(Self::State, f64)>> {
+        if self.weights.is_empty() {
+            return None;
+        }
+
+        let possibles = self.adjacency.get(&self.curr).unwrap();
+        Some(
+            possibles
+                .iter()
+                .map(|n| (*n, *self.weights.get(&(self.curr, *n)).unwrap_or(&1.0)))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Expectation, Football};
+
+    #[test]
+    fn from_edges_builds_symmetric_adjacency() {
+        let graph = GraphWalk::from_edges(&[(1, 2), (2, 3), (2, 1), (3, 1), (3, 4)]);
+
+        assert_eq!(graph.adjacency[&1], [2, 3]);
+        assert_eq!(graph.adjacency[&2], [1, 3]);
+        assert_eq!(graph.adjacency[&3], [1, 2, 4]);
+        assert_eq!(graph.adjacency[&4], [3]);
+    }
+
+    #[test]
+    fn cycle_expected_return_time() {
+        // On a cycle the stationary distribution is uniform, so the expected return time to any
+        // node is the number of nodes.
+        let cycle = GraphWalk::from_edges(&[(0, 1), (1, 2), (2, 3), (3, 4), (4, 0)]);
+        let mut exp = Expectation::new(cycle);
+        let mean = exp.calculate(0, 0, 100_000);
+
+        assert!((mean - 5.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn weighted_moves_follow_weights() {
+        // Two nodes, where node 0 can either stay put or cross to node 1, and crossing is three
+        // times as likely as staying.
+        let mut graph = GraphWalk::from_edges(&[(0, 0), (0, 1)]);
+        graph.set_weight(0, 1, 3.0);
+
+        let mut rng = StdRng::seed_from_u64(767);
+        let runs = 100_000;
+        let mut crossed = 0;
+        for _ in 0..runs {
+            graph.set_state(0);
+            graph.make_weighted_move(&mut rng);
+            if graph.get_state() == 1 {
+                crossed += 1;
+            }
+        }
+
+        assert!((crossed as f64 / runs as f64 - 0.75).abs() < 0.01);
+    }
+
+    #[test]
+    fn walk_until_any_stops_at_a_target() {
+        // A path, with the walk starting in the middle and absorbed at either end.
+        let mut path = GraphWalk::from_edges(&[(0, 1), (1, 2), (2, 3), (3, 4)]);
+        let mut rng = rand::thread_rng();
+
+        let mut ends = [0; 2];
+        for _ in 0..1_000 {
+            let (end, steps) = path.walk_until_any(2, &[0, 4], &mut rng);
+
+            assert!(end == 0 || end == 4);
+            assert!(steps >= 2 && steps % 2 == 0);
+            ends[(end / 4) as usize] += 1;
+        }
+
+        assert!(ends[0] > 0 && ends[1] > 0);
+    }
+
+    #[test]
+    fn regular_graph_has_uniform_stationary_distribution() {
+        let cycle = GraphWalk::from_edges(&[(0, 1), (1, 2), (2, 3), (3, 4), (4, 0)]);
+        let pi = cycle.stationary_distribution();
+
+        assert_eq!(pi.len(), 5);
+        assert!(pi.values().all(|p| (p - 0.2).abs() < 1e-12));
+    }
+
+    #[test]
+    fn stationary_distribution_is_proportional_to_degree() {
+        // A star with three leaves. The centre has half the edge ends, so half the probability.
+        let star = GraphWalk::from_edges(&[(0, 1), (0, 2), (0, 3)]);
+        let pi = star.stationary_distribution();
+
+        assert!((pi[&0] - 0.5).abs() < 1e-9);
+        assert!((1..4).all(|n| (pi[&n] - 1.0 / 6.0).abs() < 1e-9));
+    }
+
+    #[test]
+    fn adjacency_matrix_matches_edges() {
+        let matrix = vec![
+            vec![false, true, false],
+            vec![false, false, true],
+            vec![true, false, false],
+        ];
+        let from_matrix = GraphWalk::from_adjacency_matrix(&matrix);
+        let from_edges = GraphWalk::from_edges(&[(0, 1), (1, 2), (2, 0)]);
+
+        assert_eq!(from_matrix.adjacency, from_edges.adjacency);
+    }
+
+    #[test]
+    fn spectral_gaps_of_known_graphs() {
+        // The eigenvalues of a walk on a cycle of n nodes are $\cos(2 \pi k / n)$.
+        let cycle = GraphWalk::from_edges(&[(0, 1), (1, 2), (2, 3), (3, 4), (4, 0)]);
+        let expected = 1.0 - (4.0 * std::f64::consts::PI / 5.0).cos().abs();
+        assert!((cycle.spectral_gap() - expected).abs() < 1e-6);
+
+        // On the complete graph $K_4$, every other eigenvalue is $-1/3$.
+        let complete = GraphWalk::from_edges(&[(0, 1), (0, 2), (0, 3), (1, 2), (1, 3), (2, 3)]);
+        assert!((complete.spectral_gap() - 2.0 / 3.0).abs() < 1e-6);
+
+        // A bipartite graph never mixes.
+        let square = GraphWalk::from_edges(&[(0, 1), (1, 2), (2, 3), (3, 0)]);
+        assert!(square.spectral_gap().abs() < 1e-6);
+    }
+
+    #[test]
+    fn dot_round_trip() {
+        let graph = GraphWalk::from_edges(&[(0, 1), (1, 2), (2, 0), (2, 3), (3, 3)]);
+        let dot = graph.to_dot();
+        assert_eq!(
+            dot,
+            "graph {\n    0 -- 1;\n    0 -- 2;\n    1 -- 2;\n    2 -- 3;\n    3 -- 3;\n}\n"
+        );
+
+        let parsed = GraphWalk::from_dot(&dot).unwrap();
+        assert_eq!(parsed.adjacency, graph.adjacency);
+        assert_eq!(parsed.to_dot(), dot);
+    }
+
+    #[test]
+    fn from_dot_accepts_chains_and_rejects_the_rest() {
+        let chain = GraphWalk::from_dot("graph path { 0 -- 1 -- 2; 2 -- 3 }").unwrap();
+        let path = GraphWalk::from_edges(&[(0, 1), (1, 2), (2, 3)]);
+        assert_eq!(chain.adjacency, path.adjacency);
+
+        assert_eq!(
+            GraphWalk::from_dot("digraph { 0 -> 1 }").err(),
+            Some(ParseError::MissingHeader)
+        );
+        assert_eq!(
+            GraphWalk::from_dot("graph { 0 -- 1").err(),
+            Some(ParseError::MissingClosingBrace)
+        );
+        assert_eq!(
+            GraphWalk::from_dot("graph { 0 -- a }").err(),
+            Some(ParseError::InvalidNode("a".to_string()))
+        );
+        assert_eq!(
+            GraphWalk::from_dot("graph { 0 [color=red] }").err(),
+            Some(ParseError::UnsupportedStatement(
+                "0 [color=red]".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn exact_return_statistics_on_the_football() {
+        let football = Football::new();
+        let edges: Vec<(u32, u32)> = (1..=20)
+            .flat_map(|node| {
+                let neighbours = football.neighbours(node).unwrap();
+                neighbours.map(|n| (node as u32, n as u32))
+            })
+            .collect();
+        let graph = GraphWalk::from_edges(&edges);
+
+        assert!((graph.expected_return_time(1) - 20.0).abs() < 1e-9);
+        assert_eq!(graph.return_probability_within(1, 0), 0.0);
+
+        // Andy can't get back home in fewer than two steps, and the first five return times
+        // agree with the football's own solver.
+        assert_eq!(graph.return_probability_within(1, 1), 0.0);
+        let dist = football.return_time_distribution(5);
+        for k in 1..=5 {
+            let expected: f64 = dist[..k as usize].iter().sum();
+            assert!((graph.return_probability_within(1, k) - expected).abs() < 1e-12);
+        }
+
+        // He's all but certain to get home eventually.
+        assert!(1.0 - graph.return_probability_within(1, 2_000) < 1e-9);
+    }
+
+    #[test]
+    fn weighted_expected_return_time() {
+        // On a path 0 -- 1 -- 2, the middle node is visited half the time, so it takes two steps
+        // on average to return there. Biasing the walk doesn't change that, as every move from
+        // the middle ends in a step straight back.
+        let mut path = GraphWalk::from_edges(&[(0, 1), (1, 2)]);
+        assert!((path.expected_return_time(1) - 2.0).abs() < 1e-12);
+        assert!((path.expected_return_time(0) - 4.0).abs() < 1e-12);
+
+        path.set_weight(1, 2, 3.0);
+        assert!((path.expected_return_time(1) - 2.0).abs() < 1e-12);
+        assert!((path.expected_return_time(0) - 8.0).abs() < 1e-12);
+    }
+}
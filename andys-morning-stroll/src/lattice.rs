@@ -0,0 +1,151 @@
+//! Random walks on the regular lattices of the plane, for comparison with Andy's
+//! [KitchenFloor](crate::KitchenFloor).
+//!
+//! By [Pólya's recurrence theorem](https://en.wikipedia.org/wiki/Random_walk#Lattice_random_walk),
+//! a simple random walk on any of these two-dimensional lattices returns home with probability 1,
+//! but the expected time to do so is infinite. So walks can be extremely long, and it is best to
+//! use [walk_until_limit](crate::RandomWalk::walk_until_limit) rather than
+//! [walk](crate::RandomWalk::walk) with them.
+
+use crate::{Coord, TilingWalk};
+
+/// The square lattice, where each site has four neighbours, one along each axis direction.
+pub struct SquareLattice {
+    coords: Coord,
+}
+
+impl Default for SquareLattice {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SquareLattice {
+    /// Create a new square lattice, with the walker at the origin.
+    pub fn new() -> Self {
+        Self { coords: (0, 0) }
+    }
+
+    /// The four neighbours of a site, in the order east, north, west, south.
+    pub fn coord_neighbours(coord: Coord) -> [Coord; 4] {
+        let (x, y) = coord;
+        [(x + 1, y), (x, y + 1), (x - 1, y), (x, y - 1)]
+    }
+}
+
+impl TilingWalk for SquareLattice {
+    fn neighbours(coord: Coord) -> Vec<Coord> {
+        Self::coord_neighbours(coord).to_vec()
+    }
+
+    fn branching_factor() -> usize {
+        4
+    }
+
+    fn position(&self) -> Coord {
+        self.coords
+    }
+
+    fn set_position(&mut self, coord: Coord) {
+        self.coords = coord;
+    }
+
+    fn neighbour(&self, idx: usize) -> Coord {
+        Self::coord_neighbours(self.coords)[idx]
+    }
+}
+
+/// The triangular lattice, where each site has six neighbours.
+///
+/// Sites are addressed with axial coordinates: the `x` axis runs east, and the `y` axis runs
+/// north-east, at 60° to it. The six neighbours of `(x, y)` are then found by adding one of
+/// `(1, 0)`, `(0, 1)`, `(-1, 1)`, `(-1, 0)`, `(0, -1)` or `(1, -1)`.
+pub struct TriangularLattice {
+    coords: Coord,
+}
+
+impl Default for TriangularLattice {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TriangularLattice {
+    /// Create a new triangular lattice, with the walker at the origin.
+    pub fn new() -> Self {
+        Self { coords: (0, 0) }
+    }
+
+    /// The six neighbours of a site, anticlockwise starting from the east.
+    pub fn coord_neighbours(coord: Coord) -> [Coord; 6] {
+        let (x, y) = coord;
+        [
+            (x + 1, y),
+            (x, y + 1),
+            (x - 1, y + 1),
+            (x - 1, y),
+            (x, y - 1),
+            (x + 1, y - 1),
+        ]
+    }
+}
+
+impl TilingWalk for TriangularLattice {
+    fn neighbours(coord: Coord) -> Vec<Coord> {
+        Self::coord_neighbours(coord).to_vec()
+    }
+
+    fn branching_factor() -> usize {
+        6
+    }
+
+    fn position(&self) -> Coord {
+        self.coords
+    }
+
+    fn set_position(&mut self, coord: Coord) {
+        self.coords = coord;
+    }
+
+    fn neighbour(&self, idx: usize) -> Coord {
+        Self::coord_neighbours(self.coords)[idx]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RandomWalk;
+
+    #[test]
+    fn square_lattice_neighbours() {
+        assert_eq!(
+            SquareLattice::coord_neighbours((0, 0)),
+            [(1, 0), (0, 1), (-1, 0), (0, -1)]
+        );
+        assert_eq!(
+            SquareLattice::coord_neighbours((3, -2)),
+            [(4, -2), (3, -1), (2, -2), (3, -3)]
+        );
+    }
+
+    #[test]
+    fn square_lattice_moves_to_neighbours() {
+        let mut lattice = SquareLattice::new();
+        let mut rng = rand::thread_rng();
+
+        for _ in 0..1_000 {
+            let before = lattice.get_state();
+            lattice.make_move(&mut rng);
+            assert!(SquareLattice::coord_neighbours(before).contains(&lattice.get_state()));
+        }
+    }
+
+    #[test]
+    fn triangular_lattice_neighbours() {
+        assert_eq!(
+            TriangularLattice::coord_neighbours((0, 0)),
+            [(1, 0), (0, 1), (-1, 1), (-1, 0), (0, -1), (1, -1)]
+        );
+    }
+}
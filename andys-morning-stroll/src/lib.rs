@@ -1,4 +1,3 @@
-#![feature(associated_type_defaults)]
 #![allow(dead_code)]
 #![deny(missing_docs)]
 //! Algorithms used to solve [Andy's Morning Stroll](https://www.janestreet.com/puzzles/current-puzzle/):
@@ -78,11 +77,18 @@
 //!
 //! $$p = 0.4480326 \text{ (7 s.f.)}$$
 
+pub mod graph;
+pub mod lattice;
+
 use rand::{
-    distributions::{Distribution, Uniform},
-    Rng,
+    distributions::{Distribution, Uniform, WeightedIndex},
+    rngs::StdRng,
+    Rng, SeedableRng,
+};
+use std::{
+    collections::HashMap,
+    io::{self, Write},
 };
-use std::collections::HashMap;
 
 /// Implement random walks on a state machine.
 pub trait RandomWalk {
@@ -98,6 +104,32 @@ pub trait RandomWalk {
     /// Set the state of the internal state machine.
     fn set_state(&mut self, state: Self::State);
 
+    /// The states reachable in one move from the current state, each paired with the relative
+    /// weight of moving there. The default of `None` means every move is equally likely, which is
+    /// how [make_move](RandomWalk::make_move) behaves.
+    fn move_weights(&self) -> Option<Vec<(Self::State, f64)>> {
+        None
+    }
+
+    /// Make a random move, choosing among the moves listed by
+    /// [move_weights](RandomWalk::move_weights) with probability proportional to their weights.
+    /// Falls back to [make_move](RandomWalk::make_move) if there are no weights.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the weights are empty, negative, or all zero.
+    fn make_weighted_move<R: Rng>(&mut self, rng: &mut R) {
+        match self.move_weights() {
+            None => self.make_move(rng),
+            Some(moves) => {
+                let dist = WeightedIndex::new(moves.iter().map(|(_, w)| *w))
+                    .expect("move weights should be valid");
+                let next = moves[dist.sample(rng)].0.clone();
+                self.set_state(next);
+            }
+        }
+    }
+
     /// Perform a random walk, starting at `src`, and making random moves until the `tgt` state is
     /// reached. This does not terminate at zero steps if `src` and `tgt` are the same, a move is
     /// always made first before continuing until `tgt`.
@@ -105,21 +137,92 @@ pub trait RandomWalk {
     /// Returns the number of steps it took. This method could block forever if the state
     /// diverges somehow and never arrives at `tgt`. See also `walk_until_limit`.
     fn walk<R: Rng>(&mut self, src: Self::State, tgt: Self::State, rng: &mut R) -> u32 {
+        self.walk_until_any(src, &[tgt], rng).1
+    }
+
+    /// Same as [walk](RandomWalk::walk), except that a walk starting on `tgt` has already arrived,
+    /// so takes zero steps and makes no moves. This is the hitting time of `tgt`, as used when
+    /// analysing absorbing chains, rather than the return time the puzzle asks about: Andy's
+    /// stroll starts at home, so must always make at least one move before it can end there.
+    ///
+    /// When `src` and `tgt` differ, this is exactly [walk](RandomWalk::walk), and could likewise
+    /// block forever.
+    fn walk_allow_zero<R: Rng>(&mut self, src: Self::State, tgt: Self::State, rng: &mut R) -> u32 {
+        if src == tgt {
+            self.set_state(src);
+            return 0;
+        }
+
+        self.walk(src, tgt, rng)
+    }
+
+    /// Same as [walk](RandomWalk::walk), but stops as soon as any one of the states in `tgts` is
+    /// reached. Returns the state the walk ended on, along with the number of steps it took.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tgts` is empty.
+    fn walk_until_any<R: Rng>(
+        &mut self,
+        src: Self::State,
+        tgts: &[Self::State],
+        rng: &mut R,
+    ) -> (Self::State, u32) {
+        assert!(!tgts.is_empty(), "tgts should be non-empty");
         self.set_state(src);
 
         self.make_move(rng);
         let mut cnt = 1u32;
 
-        while self.get_state() != tgt {
+        while !tgts.contains(&self.get_state()) {
             self.make_move(rng);
             cnt += 1;
         }
-        cnt
+        (self.get_state(), cnt)
+    }
+
+    /// Same as [walk](RandomWalk::walk), but returns every state visited rather than just the
+    /// number of steps. The path starts with `src` and ends with the first arrival at `tgt`, so it
+    /// contains one more state than the number of steps taken.
+    ///
+    /// Like [walk](RandomWalk::walk), this could block forever (and grow without bound) if the
+    /// walk never arrives at `tgt`.
+    fn walk_recording_path<R: Rng>(
+        &mut self,
+        src: Self::State,
+        tgt: Self::State,
+        rng: &mut R,
+    ) -> Vec<Self::State> {
+        self.set_state(src.clone());
+        let mut path = vec![src];
+
+        loop {
+            self.make_move(rng);
+            let state = self.get_state();
+            let arrived = state == tgt;
+            path.push(state);
+
+            if arrived {
+                return path;
+            }
+        }
+    }
+
+    /// Step the walk lazily from wherever it currently is, yielding the state after each move. The
+    /// iterator never ends, so use e.g. `take_while(|state| *state != home)` to stop a walk when it
+    /// arrives home. Unlike [walk_recording_path](RandomWalk::walk_recording_path), the starting
+    /// state is not yielded.
+    fn iter<'a, R: Rng>(&'a mut self, rng: &'a mut R) -> WalkIter<'a, Self, R>
+    where
+        Self: Sized,
+    {
+        WalkIter { walker: self, rng }
     }
 
     /// Same as `walk_until`, but also takes a `limit` parameter, specifying the maximum length of
-    /// the walk we should allow before bailing out. Returns `Ok(num_steps)` if `tgt` is reached at or
-    /// before the limit, and `Err(limit)` otherwise.
+    /// the walk we should allow before bailing out. Returns
+    /// [Reached(num_steps)](WalkOutcome::Reached) if `tgt` is reached at or before the limit, and
+    /// [LimitHit(limit)](WalkOutcome::LimitHit) otherwise.
     ///
     /// # Panics
     ///
@@ -130,7 +233,7 @@ pub trait RandomWalk {
         tgt: Self::State,
         rng: &mut R,
         limit: u32,
-    ) -> Result<u32, u32> {
+    ) -> WalkOutcome {
         if limit == 0 {
             panic!("limit should be > 0");
         }
@@ -144,18 +247,142 @@ pub trait RandomWalk {
             cnt += 1;
         }
 
-        if cnt == limit {
-            if self.get_state() == tgt {
-                Ok(limit)
-            } else {
-                Err(limit)
-            }
+        // The loop stops after `limit` moves at the latest, and the state after the final move is
+        // still checked here, so arriving on exactly step `limit` counts as reaching `tgt`.
+        if self.get_state() == tgt {
+            WalkOutcome::Reached(cnt)
         } else {
-            Ok(cnt)
+            WalkOutcome::LimitHit(limit)
+        }
+    }
+
+    /// Same as [walk](RandomWalk::walk), but gives up after `cap` steps, returning `None` if `tgt`
+    /// hasn't been reached by then. This is a middle ground between `walk`, which could block
+    /// forever, and [walk_until_limit](RandomWalk::walk_until_limit).
+    ///
+    /// Walks which are cut off are dropped rather than counted, so `cap` should be chosen large
+    /// enough that hitting it is vanishingly rare, or any statistics gathered from the walks will
+    /// be biased towards shorter walks.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `cap` is zero.
+    fn walk_or_none<R: Rng>(
+        &mut self,
+        src: Self::State,
+        tgt: Self::State,
+        rng: &mut R,
+        cap: u32,
+    ) -> Option<u32> {
+        self.walk_until_limit(src, tgt, rng, cap).reached()
+    }
+
+    /// Estimate the expected number of steps for a walk starting at `home` to first return there,
+    /// by averaging the lengths of `runs` walks. Use an [Expectation] instead to see more than the
+    /// mean, such as the distribution of walk lengths.
+    ///
+    /// Like [walk](RandomWalk::walk), this could block forever if some walk never returns home.
+    fn expected_return_time_monte_carlo<R: Rng>(
+        &mut self,
+        home: Self::State,
+        runs: u32,
+        rng: &mut R,
+    ) -> f64 {
+        let total: u64 = (0..runs)
+            .map(|_| self.walk(home.clone(), home.clone(), rng) as u64)
+            .sum();
+
+        total as f64 / runs as f64
+    }
+}
+
+/// How a walk with a limit on its length, from
+/// [walk_until_limit](RandomWalk::walk_until_limit), ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WalkOutcome {
+    /// The target was reached, after the contained number of steps.
+    Reached(u32),
+    /// The walk was cut off at the contained limit before reaching the target.
+    LimitHit(u32),
+}
+
+impl WalkOutcome {
+    /// The number of steps walked, however the walk ended.
+    pub fn steps(self) -> u32 {
+        match self {
+            WalkOutcome::Reached(steps) | WalkOutcome::LimitHit(steps) => steps,
+        }
+    }
+
+    /// The number of steps taken to reach the target, or `None` if the limit was hit first.
+    pub fn reached(self) -> Option<u32> {
+        match self {
+            WalkOutcome::Reached(steps) => Some(steps),
+            WalkOutcome::LimitHit(_) => None,
         }
     }
 }
 
+/// An endless iterator over the states of a [RandomWalk], created by [RandomWalk::iter].
+pub struct WalkIter<'a, T: RandomWalk, R: Rng> {
+    walker: &'a mut T,
+    rng: &'a mut R,
+}
+
+impl<T: RandomWalk, R: Rng> Iterator for WalkIter<'_, T, R> {
+    type Item = T::State;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.walker.make_move(self.rng);
+        Some(self.walker.get_state())
+    }
+}
+
+/// A random walk on a tiling of the plane, such as the [KitchenFloor], where each tile is
+/// addressed by a [Coord] and the walker moves to one of the adjacent tiles, chosen uniformly at
+/// random, at each step.
+///
+/// Every `TilingWalk` is a [RandomWalk] whose state is the walker's coordinate, so implementors
+/// need only describe the geometry of the tiling.
+pub trait TilingWalk {
+    /// The tiles adjacent to the tile at `coord`, which the walker can move to from there. There
+    /// should be [branching_factor](TilingWalk::branching_factor) of them.
+    fn neighbours(coord: Coord) -> Vec<Coord>;
+
+    /// The number of neighbours of every tile, i.e. the number of choices at each step.
+    fn branching_factor() -> usize;
+
+    /// The coordinate of the tile the walker is on.
+    fn position(&self) -> Coord;
+
+    /// Move the walker to the tile at `coord`.
+    fn set_position(&mut self, coord: Coord);
+
+    /// The neighbour of the walker's tile at position `idx` of
+    /// [neighbours](TilingWalk::neighbours). This is called on every move, so implementors may
+    /// want to override it to avoid allocating.
+    fn neighbour(&self, idx: usize) -> Coord {
+        Self::neighbours(self.position())[idx]
+    }
+}
+
+impl<T: TilingWalk> RandomWalk for T {
+    type State = Coord;
+
+    fn make_move<R: Rng>(&mut self, rng: &mut R) {
+        let random_idx = rng.gen_range(0..T::branching_factor());
+        self.set_position(self.neighbour(random_idx));
+    }
+
+    fn get_state(&self) -> Self::State {
+        self.position()
+    }
+
+    fn set_state(&mut self, state: Self::State) {
+        self.set_position(state);
+    }
+}
+
 /// A representation of the football Andy the Ant lives on.
 ///
 /// A football is a [truncated icosahedron](https://en.wikipedia.org/wiki/Truncated_icosahedron).
@@ -167,17 +394,26 @@ pub trait RandomWalk {
 /// and define the available transitions manually.
 pub struct Football {
     curr: i32,
+    /// The hexagon Andy calls home, where his walks start and end.
+    home: i32,
     transitions: HashMap<i32, [i32; 3]>,
     dist: Uniform<usize>,
+    /// Relative weights of moving along particular edges, in the direction `(from, to)`. Edges
+    /// not listed here have weight 1. While this is empty, every move is uniform.
+    weights: HashMap<(i32, i32), f64>,
 }
 
 impl RandomWalk for Football {
     type State = i32;
 
     fn make_move<R: Rng>(&mut self, rng: &mut R) {
-        let possibles = self.transitions.get(&self.curr).unwrap();
+        if !self.weights.is_empty() {
+            return self.make_weighted_move(rng);
+        }
+
+        let possibles = self.current_neighbours();
         let random_idx = self.dist.sample(rng);
-        self.curr = *possibles.get(random_idx).unwrap();
+        self.curr = possibles[random_idx];
     }
 
     fn get_state(&self) -> Self::State {
@@ -187,14 +423,33 @@ impl RandomWalk for Football {
     fn set_state(&mut self, state: Self::State) {
         self.curr = state;
     }
+
+    fn move_weights(&self) -> Option<Vec<(Self::State, f64)>> {
+        if self.weights.is_empty() {
+            return None;
+        }
+
+        let possibles = self.current_neighbours();
+        Some(
+            possibles
+                .iter()
+                .map(|n| (*n, *self.weights.get(&(self.curr, *n)).unwrap_or(&1.0)))
+                .collect(),
+        )
+    }
+}
+
+impl Default for Football {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Football {
     /// Create a football.
     pub fn new() -> Self {
         // Generated by randomly labelling the hexagons on the stereographic projection and manually
-        // hardcoding the transition matrix. It would be interesting to think about ways to
-        // programmatically generate things like this, but for now, this is quicker.
+        // hardcoding the transition matrix. See `generated` for a programmatic alternative.
         let transitions: HashMap<i32, [i32; 3]> = HashMap::from([
             (1, [2, 6, 5]),
             (2, [1, 7, 3]),
@@ -218,563 +473,3692 @@ impl Football {
             (20, [11, 16, 19]),
         ]);
 
-        Self {
+        Self::from_transitions(transitions).expect("hardcoded football should be valid")
+    }
+
+    /// Create a football from a table mapping each hexagon to its three neighbours.
+    ///
+    /// The table is checked with [validate](Football::validate) first, so that a mistake such as
+    /// a transposed digit is reported rather than silently skewing the walk.
+    pub fn from_transitions(transitions: HashMap<i32, [i32; 3]>) -> Result<Self, GraphError> {
+        Self::validate(&transitions)?;
+
+        let home = *transitions.keys().min().unwrap();
+
+        Ok(Self {
+            curr: home,
+            home,
             transitions,
-            curr: 1,
             dist: Uniform::from(0..3),
-        }
+            weights: HashMap::new(),
+        })
     }
-}
 
-/// An implementation of the infinite hexagonally tiled kitchen floor Andy unwittingly found
-/// himself walking around on this morning.
-///
-/// To implement this, we can use a coordinate system (x, y), where each hexagon, including the
-/// black ones, has a coordinate assigned.
-///
-/// There are two distinct types of white hexagon, which we can characterise by the compass
-/// directions we can move to adjacent white hexagons:
-///  A. (NW, SW, E)
-///  B. (W, NE, SE)
-///
-///  We can map the co-ordinates of any given white hexagon to ascertain its type by:
-///  ```
-///  hex_type: bool = (y % 3) == (3 - x) % 3
-///  ```
-///
-///  The available moves in each case are:
-///  * `hex_type == 0`: `(x, y) -> [(x+1, y+1), (x, y-1), (x-1, y)]`
-///  * `hex_type == 1`: `(x, y) -> [(x, y+1), (x-1, y-1), (x+1, y)]`
-pub struct KitchenFloor {
-    coords: (i32, i32),
-}
+    /// Create a football where Andy's home is `node`, rather than node 1. By the symmetry of the
+    /// football, every choice of home should give walks with the same statistics.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `node` is not a node of the graph.
+    pub fn with_home(node: i32) -> Self {
+        let mut football = Self::new();
+        assert!(
+            football.transitions.contains_key(&node),
+            "home should be a node of the graph"
+        );
+        football.home = node;
+        football.curr = node;
 
-impl KitchenFloor {
-    /// Create a new kitchen floor.
-    fn new() -> Self {
-        Self { coords: (0, 0) }
+        football
     }
 
-    fn coord_hex_type(coord: (i32, i32)) -> bool {
-        let x = coord.0;
-        let y = coord.1;
-        y.rem_euclid(3) == (3 - x).rem_euclid(3)
+    /// The hexagon Andy calls home.
+    pub fn home(&self) -> i32 {
+        self.home
     }
 
-    fn coord_neighbours(coord: (i32, i32)) -> [(i32, i32); 3] {
-        if Self::coord_hex_type(coord) {
-            [
-                (coord.0 + 1, coord.1 + 1),
-                (coord.0, coord.1 - 1),
-                (coord.0 - 1, coord.1),
-            ]
-        } else {
-            [
-                (coord.0, coord.1 + 1),
-                (coord.0 + 1, coord.1),
-                (coord.0 - 1, coord.1 - 1),
-            ]
+    /// Move Andy to `node`, as [set_state](RandomWalk::set_state) does, but only if it is a node of
+    /// the graph. Otherwise Andy stays where he is and [GraphError::UnknownNode] is returned,
+    /// rather than the next move panicking.
+    pub fn try_set_state(&mut self, node: i32) -> Result<(), GraphError> {
+        if !self.transitions.contains_key(&node) {
+            return Err(GraphError::UnknownNode(node));
         }
-    }
 
-    /// For simplicity, we use a boolean to encode the two types of hexagon we could be on.
-    fn hex_type(&self) -> bool {
-        Self::coord_hex_type(self.coords)
+        self.curr = node;
+        Ok(())
     }
-    fn neighbours(&self) -> [(i32, i32); 3] {
-        Self::coord_neighbours(self.coords)
+
+    /// Same as [walk](RandomWalk::walk), but first checks that `src` and `tgt` are both nodes of
+    /// the graph, returning [GraphError::UnknownNode] if not. An unknown `src` would otherwise
+    /// panic on the first move, and an unknown `tgt` would never be reached.
+    pub fn try_walk<R: Rng>(&mut self, src: i32, tgt: i32, rng: &mut R) -> Result<u32, GraphError> {
+        if !self.transitions.contains_key(&tgt) {
+            return Err(GraphError::UnknownNode(tgt));
+        }
+        self.try_set_state(src)?;
+
+        Ok(self.walk(src, tgt, rng))
     }
 
-    fn move_from_idx(&mut self, idx: usize) {
-        if self.hex_type() {
-            match idx {
-                0 => {
-                    self.coords.0 += 1;
-                    self.coords.1 += 1
-                }
-                1 => self.coords.1 -= 1,
-                2 => self.coords.0 -= 1,
-                _ => unreachable!(),
-            };
-        } else {
-            match idx {
-                0 => self.coords.1 += 1,
-                1 => {
-                    self.coords.0 -= 1;
-                    self.coords.1 -= 1;
-                }
-                2 => self.coords.0 += 1,
-                _ => unreachable!(),
-            }
+    /// The neighbours of the hexagon Andy is on.
+    ///
+    /// # Panics
+    ///
+    /// Panics if Andy has been put on a node which isn't in the graph with
+    /// [set_state](RandomWalk::set_state). Use [try_set_state](Football::try_set_state) to avoid
+    /// this.
+    fn current_neighbours(&self) -> [i32; 3] {
+        match self.transitions.get(&self.curr) {
+            Some(neighbours) => *neighbours,
+            None => panic!("Andy is on {}, which is not a node of the graph", self.curr),
         }
     }
-}
 
-impl RandomWalk for KitchenFloor {
-    type State = (i32, i32);
+    /// The three hexagons sharing an edge with `node`, in the order Andy chooses between them when
+    /// moving, or `None` if `node` is not a node of the graph.
+    pub fn neighbours(&self, node: i32) -> Option<[i32; 3]> {
+        self.transitions.get(&node).copied()
+    }
 
-    fn make_move<R: Rng>(&mut self, _rng: &mut R) {
-        let random_idx = fastrand::usize(..3);
-        self.move_from_idx(random_idx);
+    /// Set the relative weight of moving from `from` to `to`, biasing the walk. The weight of the
+    /// reverse move is unaffected. All moves start with weight 1.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `from` and `to` are not neighbours.
+    pub fn set_weight(&mut self, from: i32, to: i32, weight: f64) {
+        assert!(
+            self.transitions
+                .get(&from)
+                .is_some_and(|possibles| possibles.contains(&to)),
+            "can only weight an existing edge"
+        );
+        self.weights.insert((from, to), weight);
     }
 
-    fn get_state(&self) -> Self::State {
-        self.coords
+    /// The probability of moving from `node` to each of its neighbours, given the weights set by
+    /// [set_weight](Football::set_weight).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the weights out of `node` are negative or all zero.
+    fn move_probabilities(&self, node: i32) -> [(i32, f64); 3] {
+        let weights =
+            self.transitions[&node].map(|n| (n, *self.weights.get(&(node, n)).unwrap_or(&1.0)));
+        let total: f64 = weights.iter().map(|(_, w)| w).sum();
+        assert!(
+            total > 0.0 && weights.iter().all(|(_, w)| *w >= 0.0),
+            "move weights should be valid"
+        );
+
+        weights.map(|(n, w)| (n, w / total))
     }
 
-    fn set_state(&mut self, state: Self::State) {
-        self.coords = state;
+    /// Check that a transition table describes a valid football: there must be exactly 20 nodes,
+    /// each listing three distinct neighbours other than itself, and adjacency must be mutual.
+    fn validate(transitions: &HashMap<i32, [i32; 3]>) -> Result<(), GraphError> {
+        if transitions.len() != 20 {
+            return Err(GraphError::WrongNodeCount(transitions.len()));
+        }
+
+        // Check the nodes in order, so the first problem reported is always the same one.
+        let mut nodes: Vec<&i32> = transitions.keys().collect();
+        nodes.sort();
+
+        for node in nodes {
+            let neighbours = &transitions[node];
+            for (i, n) in neighbours.iter().enumerate() {
+                if n == node {
+                    return Err(GraphError::SelfLoop(*node));
+                }
+                if neighbours[..i].contains(n) {
+                    return Err(GraphError::RepeatedNeighbour(*node, *n));
+                }
+                match transitions.get(n) {
+                    None => return Err(GraphError::UnknownNode(*n)),
+                    Some(back) if !back.contains(node) => {
+                        return Err(GraphError::NotMutual(*node, *n))
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+
+        Ok(())
     }
-}
 
-/// A struct to calculate the expected length of a random walk, for any type `T: RandomWalk`. We
-/// will use this to calculate the expected values of walks on our [Football](crate::Football) and
-/// [KitchenFloor](crate::KitchenFloor) types.
-pub struct Expectation<T: RandomWalk> {
-    /// The model of our random walk.
-    walker: T,
+    /// Create a football, generating the transitions from the geometry of the truncated
+    /// icosahedron rather than the hand-labelled table used by [new](Football::new).
+    ///
+    /// Truncating each of the 12 vertices of an icosahedron leaves a pentagon in its place, and
+    /// shrinks each of its 20 triangular faces to a hexagon. So Andy's hexagons are exactly the
+    /// faces of an icosahedron, and two hexagons are adjacent precisely when their faces share an
+    /// edge. We take the vertices of the icosahedron to be the cyclic permutations of
+    /// $(0, \pm 1, \pm \varphi)$, whose edges all have length 2, find the faces as the triangles
+    /// of mutually adjacent vertices, and then join any two faces sharing a pair of vertices. The
+    /// faces are labelled from 1 to 20 in lexicographic order of their vertices.
+    ///
+    /// The result is isomorphic to, but not labelled identically to, the graph from
+    /// [new](Football::new).
+    pub fn generated() -> Self {
+        let phi = (1.0 + 5_f64.sqrt()) / 2.0;
 
-    /// A map from walk lengths to the frequency of occurences of walks of that length.
-    pub freq_map: HashMap<u32, u32>,
+        let mut vertices: Vec<[f64; 3]> = Vec::with_capacity(12);
+        for a in [-1.0, 1.0] {
+            for b in [-phi, phi] {
+                vertices.push([0.0, a, b]);
+                vertices.push([a, b, 0.0]);
+                vertices.push([b, 0.0, a]);
+            }
+        }
 
-    /// Count of the number of runs executed so far.
-    pub cnt: u32,
-}
+        let adjacent = |i: usize, j: usize| {
+            let d2: f64 = (0..3)
+                .map(|k| (vertices[i][k] - vertices[j][k]).powi(2))
+                .sum();
+            (d2 - 4.0).abs() < 1e-9
+        };
 
-impl<T: RandomWalk> Expectation<T> {
-    /// Create a new expectation calculator.
-    pub fn new(walker: T) -> Self {
-        Self {
-            walker,
-            freq_map: HashMap::new(),
-            cnt: 0,
+        let mut faces: Vec<[usize; 3]> = Vec::with_capacity(20);
+        for i in 0..12 {
+            for j in i + 1..12 {
+                for k in j + 1..12 {
+                    if adjacent(i, j) && adjacent(j, k) && adjacent(i, k) {
+                        faces.push([i, j, k]);
+                    }
+                }
+            }
         }
+
+        let transitions: HashMap<i32, [i32; 3]> = faces
+            .iter()
+            .enumerate()
+            .map(|(f, face)| {
+                let neighbours: Vec<i32> = faces
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, other)| other.iter().filter(|v| face.contains(v)).count() == 2)
+                    .map(|(g, _)| g as i32 + 1)
+                    .collect();
+                let neighbours: [i32; 3] = neighbours
+                    .try_into()
+                    .expect("every face of an icosahedron has three neighbours");
+
+                (f as i32 + 1, neighbours)
+            })
+            .collect();
+
+        Self::from_transitions(transitions).expect("generated football should be valid")
     }
 
-    /// Run the expectation computation. Internally, this calls `walker.walk` which does not take a
-    /// limit cut-off for walk lengths. Therefore, this function could take a long time if walks
-    /// can be extremely long or even diverege to infinity.
-    pub fn calculate(&mut self, src: T::State, tgt: T::State, runs: u32) -> f32 {
-        let mut rng = rand::thread_rng();
-        while self.cnt < runs {
-            let steps = self.walker.walk(src.clone(), tgt.clone(), &mut rng);
-            *self.freq_map.entry(steps).or_insert(0) += 1;
-            self.cnt += 1;
+    /// Describe the graph in the [DOT language](https://graphviz.org/doc/info/lang.html), for
+    /// rendering with Graphviz. Each edge is listed once, and the [home](Football::home) node is
+    /// filled in.
+    pub fn to_dot(&self) -> String {
+        let mut nodes: Vec<&i32> = self.transitions.keys().collect();
+        nodes.sort();
+
+        let mut dot = String::from("graph football {\n");
+        dot.push_str(&format!(
+            "    {} [style=filled, fillcolor=gold];\n",
+            self.home
+        ));
+        for node in nodes {
+            let mut neighbours = self.transitions[node];
+            neighbours.sort();
+            for n in neighbours.iter().filter(|n| *n > node) {
+                dot.push_str(&format!("    {} -- {};\n", node, n));
+            }
         }
+        dot.push_str("}\n");
 
-        self.finish()
+        dot
     }
 
-    /// Same as [calculate](Expectation::calculate) but takes a `limit` argument which is passed to
-    /// [RandomWalk::walk_until_limit](RandomWalk::walk_until_limit) in order to ensure the function
-    /// terminates, ideally in a reasonable time.
-    pub fn calculate_with_limit(
-        &mut self,
-        src: T::State,
-        tgt: T::State,
-        runs: u32,
-        limit: u32,
-    ) -> f32 {
-        let mut rng = rand::thread_rng();
-        while self.cnt < runs {
-            let steps =
-                match self
-                    .walker
-                    .walk_until_limit(src.clone(), tgt.clone(), &mut rng, limit)
-                {
-                    Ok(t) => t,
-                    Err(t) => t,
-                };
-            *self.freq_map.entry(steps).or_insert(0) += 1;
-            self.cnt += 1;
+    /// Calculate exactly the expected number of steps for a walk starting at `home` to first
+    /// return there.
+    ///
+    /// Let $h_k$ be the expected number of steps to reach `home` from node $k$, so that
+    /// $h_{\text{home}} = 0$. Every other node satisfies $h_k = 1 + \sum_j p_{kj} h_j$ over its
+    /// three neighbours $j$, where $p_{kj}$ is the probability of moving from $k$ to $j$, which gives
+    /// a linear system in the unknown $h_k$. Having solved it, the expected return time is one step
+    /// plus the average of $h_j$ over the neighbours of `home`, weighted in the same way. The
+    /// probabilities are all $\frac{1}{3}$ unless set otherwise with
+    /// [set_weight](Football::set_weight).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `home` is not a node of the graph, or if any move weights are invalid.
+    pub fn expected_return_time(&self, home: i32) -> f64 {
+        assert!(
+            self.transitions.contains_key(&home),
+            "home should be a node of the graph"
+        );
+
+        // The unknowns are the hitting times of every node other than `home`.
+        let mut nodes: Vec<i32> = self
+            .transitions
+            .keys()
+            .copied()
+            .filter(|node| *node != home)
+            .collect();
+        nodes.sort();
+        let index: HashMap<i32, usize> = nodes.iter().enumerate().map(|(i, n)| (*n, i)).collect();
+
+        let mut a = vec![vec![0.0; nodes.len()]; nodes.len()];
+        let mut b = vec![1.0; nodes.len()];
+        for (i, node) in nodes.iter().enumerate() {
+            a[i][i] += 1.0;
+            for (n, p) in self.move_probabilities(*node) {
+                if let Some(j) = index.get(&n) {
+                    a[i][*j] -= p;
+                }
+            }
         }
+        solve_linear_system(&mut a, &mut b);
 
-        self.finish()
+        1.0 + self
+            .move_probabilities(home)
+            .iter()
+            .map(|(n, p)| p * b[index[n]])
+            .sum::<f64>()
     }
 
-    fn finish(&self) -> f32 {
-        self.freq_map
-            .iter()
-            .fold(0, |acc, (walk_length, frequency)| {
-                acc + walk_length * frequency
-            }) as f32
-            / self.cnt as f32
+    /// Calculate exactly the distribution of the number of steps a walk starting at
+    /// [home](Football::home) takes to first return there. Element `k - 1` of the result is the
+    /// probability of first returning on step `k`, for `k = 1..=max_steps`.
+    ///
+    /// Like [GraphPathCounter], this propagates a frontier forward one step at a time, although
+    /// here it tracks the probability of the walk being at each node rather than a count of paths.
+    /// Any probability arriving at `home` is recorded and then removed, as those walks have ended.
+    /// The probabilities sum to less than 1 by the chance of the walk lasting more than
+    /// `max_steps`, which shrinks geometrically as `max_steps` grows. Each move follows the
+    /// weights set by [set_weight](Football::set_weight).
+    ///
+    /// # Panics
+    ///
+    /// Panics if any move weights are invalid.
+    pub fn return_time_distribution(&self, max_steps: u32) -> Vec<f64> {
+        let mut dist = Vec::with_capacity(max_steps as usize);
+        let mut probs: HashMap<i32, f64> = HashMap::from([(self.home, 1.0)]);
+
+        for _ in 0..max_steps {
+            let mut next: HashMap<i32, f64> = HashMap::with_capacity(self.transitions.len());
+            for (node, p) in &probs {
+                for (n, q) in self.move_probabilities(*node) {
+                    *next.entry(n).or_insert(0.0) += p * q;
+                }
+            }
+
+            dist.push(next.remove(&self.home).unwrap_or(0.0));
+            probs = next;
+        }
+
+        dist
     }
-}
 
-/// Run this to get the answer to the first part of the question.
-///
-/// Interestingly, switching to [calculate_with_limit](Expectation::calculate_with_limit) and running
-/// this with a walk-length limit even as high as 80 or 90 seems to
-/// give the wrong picture. The expected value appears to vary significantly even as we
-/// continue increasing the walk length limit to high values, so very long walks are
-/// contributing to the result (it is a fat-tailed distribution). This is corroborated when we print
-/// out the frequency map and see that we are getting plenty of walks which are several hundred steps
-/// long.
-///
-/// Using the [calculate](Expectation::calculate) method which imposes no limitation on walk-lengths, but takes longer
-/// to run, we seem to immediately be converging on an expected walk-length of 20 (which is of
-/// course the total number of nodes in the graph). This is an interesting result, and one we later
-/// proved rigorously (see [crate-level documentation](./index.html#first-part)).
-pub fn expected_walk_length_on_football() {
-    let football = Football::new();
-    let mut exp = Expectation::new(football);
-    let runs = 100_000_000;
-    let exp_walk_length = exp.calculate(1, 1, runs);
-    println!("E(length of walk to return home): {}", exp_walk_length);
-    println!("cnt: {}", exp.cnt);
-    for (k, v) in &exp.freq_map {
-        println!("{}: {}", k, v);
+    /// Partition the nodes into their orbits under the symmetries of the football which leave
+    /// `home` where it is. Nodes in the same orbit are indistinguishable to a walk starting at
+    /// `home`, so they share the same expected time to reach it; these are the equivalence classes
+    /// used to solve the first part by hand.
+    ///
+    /// Every such symmetry (graph automorphism) is found by a backtracking search, so the result
+    /// doesn't rely on the graph being labelled in any particular way. Each orbit is sorted, and
+    /// the orbits are ordered by their distance from `home`, with `[home]` first.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `home` is not a node of the graph.
+    pub fn orbit_partition(&self, home: i32) -> Vec<Vec<i32>> {
+        assert!(
+            self.transitions.contains_key(&home),
+            "home should be a node of the graph"
+        );
+
+        // Visit the nodes breadth first from `home`, so each one after `home` is adjacent to one
+        // visited before it, and its image under a symmetry is constrained to few choices.
+        let mut order = vec![home];
+        let mut dist = HashMap::from([(home, 0)]);
+        let mut i = 0;
+        while i < order.len() {
+            let node = order[i];
+            for n in &self.transitions[&node] {
+                if !dist.contains_key(n) {
+                    dist.insert(*n, dist[&node] + 1);
+                    order.push(*n);
+                }
+            }
+            i += 1;
+        }
+
+        let mut symmetries = Vec::new();
+        let mut mapping = HashMap::from([(home, home)]);
+        self.extend_symmetry(&order[1..], &mut mapping, &mut symmetries);
+
+        let mut orbits: Vec<Vec<i32>> = Vec::new();
+        for node in &order {
+            if orbits.iter().any(|orbit| orbit.contains(node)) {
+                continue;
+            }
+            let mut orbit: Vec<i32> = symmetries.iter().map(|s| s[node]).collect();
+            orbit.sort();
+            orbit.dedup();
+            orbits.push(orbit);
+        }
+        orbits.sort_by_key(|orbit| (dist[&orbit[0]], orbit[0]));
+
+        orbits
+    }
+
+    /// Try every way of extending the partial symmetry `mapping` to the nodes in `remaining`,
+    /// pushing each complete symmetry found onto `found`. A node may only be mapped to another if
+    /// that preserves adjacency with every node mapped so far.
+    fn extend_symmetry(
+        &self,
+        remaining: &[i32],
+        mapping: &mut HashMap<i32, i32>,
+        found: &mut Vec<HashMap<i32, i32>>,
+    ) {
+        let Some((node, rest)) = remaining.split_first() else {
+            found.push(mapping.clone());
+            return;
+        };
+
+        let adjacent = |a: &i32, b: &i32| self.transitions[a].contains(b);
+        let candidates: Vec<i32> = self
+            .transitions
+            .keys()
+            .copied()
+            .filter(|image| !mapping.values().any(|v| v == image))
+            .filter(|image| {
+                mapping
+                    .iter()
+                    .all(|(m, m_image)| adjacent(node, m) == adjacent(image, m_image))
+            })
+            .collect();
+
+        for image in candidates {
+            mapping.insert(*node, image);
+            self.extend_symmetry(rest, mapping, found);
+            mapping.remove(node);
+        }
     }
 }
 
-/// Find the probability that the random walk on the kitchen floor is strictly more than 20 steps.
-///
-/// This is the first attempt to solve the second part of the question. We discovered that 20 steps
-/// is the expected length of a walk on the football, so we want want to know what proportion of
-/// walks on the kitchen floor last longer than 20 steps.
+/// An error describing why a transition table does not form a valid [Football].
+#[derive(Debug, PartialEq, Eq)]
+pub enum GraphError {
+    /// The table does not have exactly 20 nodes. Contains the number it does have.
+    WrongNodeCount(usize),
+    /// A node lists itself as a neighbour.
+    SelfLoop(i32),
+    /// A node lists the same neighbour more than once. Contains the node and the neighbour.
+    RepeatedNeighbour(i32, i32),
+    /// A node which has no entry in the table, such as a neighbour listed by another node, or a
+    /// state Andy is asked to [stand on](Football::try_set_state).
+    UnknownNode(i32),
+    /// A node lists a neighbour which does not list it back. Contains the node and the neighbour.
+    NotMutual(i32, i32),
+}
+
+/// Solve the square linear system $Ax = b$ by Gaussian elimination with partial pivoting. The
+/// solution is left in `b`, and `a` is overwritten.
 ///
-/// We will perform a large number of runs, counting how many reach 21 steps without
-/// terminating.
+/// # Panics
 ///
-/// With increasing runs, we seem to be converging towards about 0.448.
-pub fn prob_of_longer_walk_in_the_kitchen() -> (u64, u64) {
-    let mut kitchen_floor = KitchenFloor::new();
-    let mut rng = rand::thread_rng();
-    let runs: u64 = 10_000_000;
-    let progress_unit = runs / 20;
-    let mut progress_cnt = 0;
-    let mut progress = 0;
-    let mut cnt: u64 = 0;
-    let mut longer_walk_cnt: u64 = 0;
-    while cnt < runs {
-        match kitchen_floor.walk_until_limit((0, 0), (0, 0), &mut rng, 20) {
-            Ok(_) => {
-                // We terminated on or before the 20th step. So this does not contribute to our count
-                // of longer walks.
+/// Panics if the matrix is singular.
+fn solve_linear_system(a: &mut [Vec<f64>], b: &mut [f64]) {
+    let n = b.len();
+
+    for col in 0..n {
+        // Swap the row with the largest entry in this column into the pivot position.
+        let pivot = (col..n)
+            .max_by(|i, j| a[*i][col].abs().total_cmp(&a[*j][col].abs()))
+            .unwrap();
+        assert!(a[pivot][col].abs() > 1e-12, "matrix should be non-singular");
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+
+        for row in col + 1..n {
+            let factor = a[row][col] / a[col][col];
+            if factor == 0.0 {
+                continue;
             }
-            Err(_) => {
-                // We had not terminated by the 20th step, so this does contribute to our count of
-                // longer walks.
-                longer_walk_cnt += 1;
+            let (upper, lower) = a.split_at_mut(row);
+            for (x, y) in lower[0][col..].iter_mut().zip(&upper[col][col..]) {
+                *x -= factor * y;
             }
-        };
-        cnt += 1;
-        progress_cnt += 1;
-        if progress_cnt == progress_unit {
-            progress += 1;
-            println!(
-                "{:2}% complete, {} runs, current prob: {}",
-                progress as f32 * 5 as f32 as f32,
-                cnt,
-                longer_walk_cnt as f64 / cnt as f64
-            );
-            progress_cnt = 0;
+            b[row] -= factor * b[col];
         }
     }
-    println!("runs longer than 20: {}", longer_walk_cnt);
-    println!("total runs: {}", runs);
-    println!(
-        "probability of a longer than 20 walk: {}",
-        longer_walk_cnt as f64 / runs as f64
-    );
-    (longer_walk_cnt, runs)
+
+    // Back substitution.
+    for row in (0..n).rev() {
+        let sum: f64 = (row + 1..n).map(|k| a[row][k] * b[k]).sum();
+        b[row] = (b[row] - sum) / a[row][row];
+    }
 }
 
-/// Multithreaded version of [prob_of_longer_walk_in_the_kitchen](prob_of_longer_walk_in_the_kitchen).
+/// A compass direction from a hexagon on the [KitchenFloor] to one of its six neighbours, black or
+/// white.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum HexDir {
+    /// East, to `(x - 1, y)`.
+    E,
+    /// North-east, to `(x, y + 1)`.
+    NE,
+    /// North-west, to `(x + 1, y + 1)`.
+    NW,
+    /// West, to `(x + 1, y)`.
+    W,
+    /// South-west, to `(x, y - 1)`.
+    SW,
+    /// South-east, to `(x - 1, y - 1)`.
+    SE,
+}
+
+impl HexDir {
+    /// The change in coordinates from a hexagon to its neighbour in this direction.
+    pub fn delta(self) -> Coord {
+        use HexDir::*;
+        match self {
+            E => (-1, 0),
+            NE => (0, 1),
+            NW => (1, 1),
+            W => (1, 0),
+            SW => (0, -1),
+            SE => (-1, -1),
+        }
+    }
+}
+
+impl std::fmt::Display for HexDir {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// The moves Andy can make from each type of white hexagon on a [KitchenFloor], as offsets from his
+/// coordinate, in the order he chooses between them. The default is the kitchen floor's own
+/// geometry, as documented there, but other move sets can be plugged in to experiment with
+/// alternative conventions or local geometries. Hexagons are still split into types A and B by
+/// [coord_hex_type](KitchenFloor::coord_hex_type).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MoveSet {
+    type_a: [Coord; 3],
+    type_b: [Coord; 3],
+}
+
+impl Default for MoveSet {
+    fn default() -> Self {
+        Self::new([(1, 1), (0, -1), (-1, 0)], [(0, 1), (1, 0), (-1, -1)])
+    }
+}
+
+impl MoveSet {
+    /// Create a move set from the offsets of the moves from type A and type B hexagons.
+    pub fn new(type_a: [Coord; 3], type_b: [Coord; 3]) -> Self {
+        Self { type_a, type_b }
+    }
+
+    /// The offsets of the moves from the hexagon at `coord`, in order.
+    pub fn deltas(&self, coord: Coord) -> [Coord; 3] {
+        if KitchenFloor::coord_hex_type(coord) {
+            self.type_a
+        } else {
+            self.type_b
+        }
+    }
+
+    /// The hexagons Andy can move to from the hexagon at `coord`, in order.
+    pub fn neighbours(&self, coord: Coord) -> [Coord; 3] {
+        self.deltas(coord)
+            .map(|(dx, dy)| (coord.0 + dx, coord.1 + dy))
+    }
+}
+
+/// An implementation of the infinite hexagonally tiled kitchen floor Andy unwittingly found
+/// himself walking around on this morning.
 ///
-/// I've got a computer with lots of cpus, and running a monte carlo with indpendent trials is
-/// silly to do single-threaded. So we can split this across a bunch of threads to do more
-/// trials.
+/// To implement this, we can use a coordinate system (x, y), where each hexagon, including the
+/// black ones, has a coordinate assigned.
 ///
-/// Running this with about 1 billion iterations per threads over 8 threads, we get to about an
-/// estimate of our probability that the random walk is longer than 20 steps of: ~0.448
-pub fn multithreaded() {
-    let cpus = 8;
-    let idx = 0..cpus;
-    let mut join_handles: Vec<std::thread::JoinHandle<(u64, u64)>> = Vec::with_capacity(cpus);
-    for _ in idx {
-        join_handles.push(std::thread::spawn(|| prob_of_longer_walk_in_the_kitchen()));
-    }
-    let mut results: Vec<(u64, u64)> = Vec::with_capacity(cpus);
-    join_handles
+/// There are two distinct types of white hexagon, which we can characterise by the compass
+/// directions we can move to adjacent white hexagons:
+///  A. (NW, SW, E)
+///  B. (W, NE, SE)
+///
+///  We can map the co-ordinates of any given white hexagon to ascertain its type by:
+///  ```text
+///  hex_type: bool = (y % 3) == (3 - x) % 3
+///  ```
+///
+///  The available moves in each case are:
+///  * `hex_type == 0`: `(x, y) -> [(x+1, y+1), (x, y-1), (x-1, y)]`
+///  * `hex_type == 1`: `(x, y) -> [(x, y+1), (x-1, y-1), (x+1, y)]`
+///
+///  Andy's current state displays as his coordinate, the type of that hexagon, and the three
+///  hexagons he can move to next, in order: `(0, 0) A -> [(1, 1), (0, -1), (-1, 0)]`.
+#[derive(Debug)]
+pub struct KitchenFloor {
+    coords: (i32, i32),
+
+    /// The hexagon Andy calls home, where his walks start and end.
+    home: Coord,
+
+    /// The moves Andy can make from each hexagon.
+    moves: MoveSet,
+}
+
+impl KitchenFloor {
+    /// Create a new kitchen floor.
+    fn new() -> Self {
+        Self::with_home((0, 0))
+    }
+
+    /// Create a kitchen floor where Andy's home is the hexagon at `coord`, rather than the origin.
+    /// The tiling looks the same from every white hexagon, so walks from any home should have the
+    /// same statistics. Note that the [type](KitchenFloor::coord_hex_type) of `coord` fixes the
+    /// directions of Andy's first move, and so the order in which the moves are numbered; only
+    /// homes of the same type give identical walks from identically seeded generators.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `coord` is one of the [black](KitchenFloor::is_black) hexagons.
+    pub fn with_home(coord: Coord) -> Self {
+        assert!(!Self::is_black(coord), "home should be a white hexagon");
+        debug_assert!(Self::neighbours_have_opposite_type(coord));
+
+        Self {
+            coords: coord,
+            home: coord,
+            moves: MoveSet::default(),
+        }
+    }
+
+    /// Create a kitchen floor, with home at the origin, where Andy moves according to `moves`
+    /// rather than the usual geometry.
+    ///
+    /// Only walks made through [RandomWalk] use the move set. Functions which work from
+    /// coordinates alone, such as [coord_neighbours](KitchenFloor::coord_neighbours) and the
+    /// path counting of [GraphPathCounter], always use the default.
+    pub fn with_move_set(moves: MoveSet) -> Self {
+        Self {
+            moves,
+            ..Self::new()
+        }
+    }
+
+    /// The hexagon Andy calls home.
+    pub fn home(&self) -> Coord {
+        self.home
+    }
+
+    /// The moves Andy can make from each hexagon.
+    pub fn move_set(&self) -> MoveSet {
+        self.moves
+    }
+
+    /// The type of the white hexagon at `coord`: `true` for type A, whose moves are (NW, SW, E),
+    /// and `false` for type B, whose moves are (W, NE, SE).
+    ///
+    /// Coordinates follow the convention laid out on [KitchenFloor]: `x` increases to the west
+    /// and `y` to the north, and the white hexagons are those with `x + y` congruent to 0 (type A)
+    /// or 2 (type B) modulo 3. The result is meaningless for black hexagons.
+    pub fn coord_hex_type(coord: (i32, i32)) -> bool {
+        let x = coord.0;
+        let y = coord.1;
+        y.rem_euclid(3) == (3 - x).rem_euclid(3)
+    }
+
+    /// The three white hexagons adjacent to the white hexagon at `coord`, in the order of the
+    /// default [MoveSet]. For type A hexagons this is also the order of
+    /// [move_direction](KitchenFloor::move_direction), but for type B hexagons the last two are
+    /// the other way round to it.
+    ///
+    /// ```
+    /// use andys_morning_stroll::KitchenFloor;
+    ///
+    /// // Home is a type A hexagon, so Andy can move NW, SW or E.
+    /// assert!(KitchenFloor::coord_hex_type((0, 0)));
+    /// assert_eq!(KitchenFloor::coord_neighbours((0, 0)), [(1, 1), (0, -1), (-1, 0)]);
+    ///
+    /// // Each of those is type B, and leads straight back home.
+    /// for coord in KitchenFloor::coord_neighbours((0, 0)) {
+    ///     assert!(!KitchenFloor::coord_hex_type(coord));
+    ///     assert!(KitchenFloor::coord_neighbours(coord).contains(&(0, 0)));
+    /// }
+    /// ```
+    pub fn coord_neighbours(coord: (i32, i32)) -> [(i32, i32); 3] {
+        MoveSet::default().neighbours(coord)
+    }
+
+    /// Whether the white hexagon at `coord` is surrounded by white hexagons of the other type, as
+    /// the tiling requires: every move takes Andy from type A to type B or back again. This is a
+    /// check on the consistency of [coord_hex_type](KitchenFloor::coord_hex_type) and
+    /// [coord_neighbours](KitchenFloor::coord_neighbours), and should always hold. Returns
+    /// `false` for black hexagons, which have no type.
+    pub fn neighbours_have_opposite_type(coord: Coord) -> bool {
+        let hex_type = Self::coord_hex_type(coord);
+
+        !Self::is_black(coord)
+            && Self::coord_neighbours(coord)
+                .iter()
+                .all(|n| !Self::is_black(*n) && Self::coord_hex_type(*n) != hex_type)
+    }
+
+    /// Whether the hexagon at `coord` is one of the black hexagons Andy never steps on. These are
+    /// the hexagons with `x + y` congruent to 1 modulo 3.
+    pub fn is_black(coord: (i32, i32)) -> bool {
+        (coord.0 + coord.1).rem_euclid(3) == 1
+    }
+
+    /// The black hexagons sharing an edge with the hexagon at `coord`. Every white hexagon has
+    /// three of these, one between each pair of its white neighbours, while a black hexagon is
+    /// surrounded entirely by white ones.
+    pub fn black_neighbours(coord: (i32, i32)) -> Vec<(i32, i32)> {
+        let (x, y) = coord;
+        [
+            (x + 1, y + 1),
+            (x, y + 1),
+            (x - 1, y),
+            (x - 1, y - 1),
+            (x, y - 1),
+            (x + 1, y),
+        ]
         .into_iter()
-        .for_each(|jh| results.push(jh.join().unwrap()));
+        .filter(|c| Self::is_black(*c))
+        .collect()
+    }
+
+    /// The position of the centre of the hexagon at `coord` in the plane, for plotting. Cartesian
+    /// axes are used, with `x` increasing to the east and `y` to the north, home at the origin,
+    /// and adjacent hexagons a distance of 1 apart.
+    ///
+    /// The hexagons have vertices to the north and south, so that rows of hexagons run east to
+    /// west. Stepping west (`x + 1` in hex coordinates) moves one unit along a row, and stepping
+    /// north east (`y + 1`) moves half a unit east and up to the next row.
+    pub fn coord_to_cartesian(coord: Coord) -> (f64, f64) {
+        let (x, y) = (coord.0 as f64, coord.1 as f64);
+        ((y - 2.0 * x) / 2.0, y * 3_f64.sqrt() / 2.0)
+    }
+
+    /// The direction Andy moves in when he makes move `idx` from a hexagon of type `hex_type`, as
+    /// numbered when enumerating every walk. These are the documented move sets of each type of
+    /// white hexagon, in order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is not 0, 1 or 2.
+    pub fn move_direction(hex_type: bool, idx: usize) -> HexDir {
+        use HexDir::*;
+        let dirs = if hex_type { [NW, SW, E] } else { [NE, SE, W] };
+        dirs[idx]
+    }
+
+    /// The white hexagon next to the white hexagon at `coord` in direction `dir`, or `None` if
+    /// Andy can't move that way because the neighbour is black, or if `coord` is itself black.
+    pub fn neighbour_in_direction(coord: Coord, dir: HexDir) -> Option<Coord> {
+        let (dx, dy) = dir.delta();
+        let neighbour = (coord.0 + dx, coord.1 + dy);
+
+        (!Self::is_black(coord) && !Self::is_black(neighbour)).then_some(neighbour)
+    }
+
+    /// For simplicity, we use a boolean to encode the two types of hexagon we could be on.
+    fn hex_type(&self) -> bool {
+        Self::coord_hex_type(self.coords)
+    }
+
+    /// Draw a walk on the kitchen floor as text, for eyeballing the geometry. The walk is a list
+    /// of coordinates such as [walk_recording_path](RandomWalk::walk_recording_path) produces, and
+    /// its first coordinate is taken to be home.
+    ///
+    /// Each hexagon takes up two characters, and alternate rows are offset by one character to
+    /// form the honeycomb, laid out so that the compass directions match those in the
+    /// documentation above: `x` increases to the west, and `y` to the north. Home is drawn as `H`,
+    /// other visited hexagons as `A` or `B` by their type, unvisited white hexagons as `·`, and
+    /// black hexagons are left blank. A border of one hexagon is drawn around the walk.
+    pub fn render_path(path: &[Coord]) -> String {
+        let Some(home) = path.first() else {
+            return String::new();
+        };
+
+        let (mut min_x, mut max_x, mut min_y, mut max_y) = (home.0, home.0, home.1, home.1);
+        for (x, y) in path {
+            min_x = min_x.min(*x - 1);
+            max_x = max_x.max(*x + 1);
+            min_y = min_y.min(*y - 1);
+            max_y = max_y.max(*y + 1);
+        }
+
+        // The leftmost column is taken by the westernmost hexagon of the southernmost row.
+        let col = |(x, y): Coord| (y - min_y + 2 * (max_x - x)) as usize;
+        let width = col((min_x, max_y)) + 1;
+
+        let mut out = String::new();
+        for y in (min_y..=max_y).rev() {
+            let mut row = vec![' '; width];
+            for x in min_x..=max_x {
+                let coord = (x, y);
+                let white = !Self::is_black(coord);
+
+                row[col(coord)] = if coord == *home {
+                    'H'
+                } else if path.contains(&coord) {
+                    if Self::coord_hex_type(coord) {
+                        'A'
+                    } else {
+                        'B'
+                    }
+                } else if white {
+                    '·'
+                } else {
+                    ' '
+                };
+            }
+
+            out.push_str(row.iter().collect::<String>().trim_end());
+            out.push('\n');
+        }
+
+        out
+    }
+
+    /// Make move `idx`, numbered as in [move_direction](KitchenFloor::move_direction) when
+    /// enumerating every walk. That numbering lists the last two moves from type B hexagons the
+    /// other way round to the move set.
+    fn move_from_idx(&mut self, idx: usize) {
+        let idx = if self.hex_type() { idx } else { [0, 2, 1][idx] };
+        self.coords = self.moves.neighbours(self.coords)[idx];
+    }
+}
+
+impl std::fmt::Display for KitchenFloor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let hex_type = if self.hex_type() { 'A' } else { 'B' };
+        write!(
+            f,
+            "{:?} {} -> {:?}",
+            self.coords,
+            hex_type,
+            self.moves.neighbours(self.coords)
+        )
+    }
+}
+
+impl TilingWalk for KitchenFloor {
+    fn neighbours(coord: Coord) -> Vec<Coord> {
+        Self::coord_neighbours(coord).to_vec()
+    }
+
+    fn branching_factor() -> usize {
+        3
+    }
+
+    fn position(&self) -> Coord {
+        self.coords
+    }
+
+    fn set_position(&mut self, coord: Coord) {
+        self.coords = coord;
+    }
+
+    fn neighbour(&self, idx: usize) -> Coord {
+        self.moves.neighbours(self.coords)[idx]
+    }
+}
+
+/// The most runs [calculate_until_converged](Expectation::calculate_until_converged) will make
+/// before giving up on the mean settling down.
+pub const MAX_CONVERGENCE_RUNS: u32 = 100_000_000;
+
+/// A map from walk lengths to the number of walks recorded with that length, with methods for the
+/// statistics of the distribution it describes.
+///
+/// Optionally, every length from some threshold `T` upwards can be bucketed into a single `>=T`
+/// tail entry, so that the map stays small however many distinct rare lengths there are. The
+/// bucket keeps the number of walks in it and the sum of their lengths, so
+/// [total_count](FrequencyMap::total_count) and [mean](FrequencyMap::mean) are still exact. The
+/// other statistics see every walk in the bucket as being exactly `T` steps long, which biases
+/// them downwards: [percentile](FrequencyMap::percentile) gives `T` for any quantile falling in
+/// the tail, and [entropy](FrequencyMap::entropy) treats the tail as a single outcome, so is an
+/// underestimate. Lengths below `T` are unaffected.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FrequencyMap {
+    /// The number of walks of each length, for the lengths not bucketed into the tail.
+    map: HashMap<u32, u32>,
+    /// The length from which walks are bucketed into the tail, if they are.
+    tail_threshold: Option<u32>,
+    /// The number of walks in the tail bucket.
+    tail_count: u32,
+    /// The total length of the walks in the tail bucket.
+    tail_sum: u64,
+}
+
+impl From<HashMap<u32, u32>> for FrequencyMap {
+    fn from(map: HashMap<u32, u32>) -> Self {
+        Self {
+            map,
+            ..Self::default()
+        }
+    }
+}
+
+impl FrequencyMap {
+    /// Create an empty frequency map.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an empty frequency map which buckets every length of at least `threshold` into a
+    /// single tail entry. See [FrequencyMap] for the bias this introduces.
+    pub fn with_tail_threshold(threshold: u32) -> Self {
+        let mut freq_map = Self::new();
+        freq_map.bucket_tail(threshold);
+        freq_map
+    }
+
+    /// Bucket every length of at least `threshold` into the tail entry from now on, moving any
+    /// such lengths already recorded into it. If there is already a lower threshold, that is
+    /// kept, as lengths in the tail can't be taken back out.
+    pub fn bucket_tail(&mut self, threshold: u32) {
+        let threshold = self.tail_threshold.map_or(threshold, |t| t.min(threshold));
+        self.tail_threshold = Some(threshold);
+
+        let (tail_count, tail_sum) = (&mut self.tail_count, &mut self.tail_sum);
+        self.map.retain(|length, frequency| {
+            if *length < threshold {
+                return true;
+            }
+            *tail_count += *frequency;
+            *tail_sum += *length as u64 * *frequency as u64;
+            false
+        });
+    }
+
+    /// The length from which walks are bucketed into the tail, or `None` if they aren't.
+    pub fn tail_threshold(&self) -> Option<u32> {
+        self.tail_threshold
+    }
+
+    /// The number of walks bucketed into the tail.
+    pub fn tail_count(&self) -> u32 {
+        self.tail_count
+    }
+
+    /// Record `count` more walks of length `length`.
+    pub fn add(&mut self, length: u32, count: u32) {
+        match self.tail_threshold {
+            Some(threshold) if length >= threshold => {
+                self.tail_count += count;
+                self.tail_sum += length as u64 * count as u64;
+            }
+            _ => *self.map.entry(length).or_insert(0) += count,
+        }
+    }
+
+    /// The number of walks recorded with length `length`. Lengths bucketed into the tail aren't
+    /// recorded individually, so give zero.
+    pub fn get(&self, length: u32) -> u32 {
+        self.map.get(&length).copied().unwrap_or(0)
+    }
+
+    /// Whether no walks have been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty() && self.tail_count == 0
+    }
+
+    /// Forget every walk recorded so far. Any tail threshold is kept.
+    pub fn clear(&mut self) {
+        self.map.clear();
+        self.tail_count = 0;
+        self.tail_sum = 0;
+    }
+
+    /// Every `(length, frequency)` pair, in no particular order. The tail bucket is not included.
+    pub fn iter(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        self.map
+            .iter()
+            .map(|(length, frequency)| (*length, *frequency))
+    }
+
+    /// Every `(length, frequency)` pair, shortest first. The tail bucket is not included.
+    pub fn sorted(&self) -> Vec<(u32, u32)> {
+        let mut walk_lengths: Vec<_> = self.iter().collect();
+        walk_lengths.sort_unstable();
+        walk_lengths
+    }
+
+    /// Add the counts recorded in `other` to this map. The maps need not cover the same lengths.
+    /// If `other` has a tail bucket, this map buckets its tail from the same threshold, or its own
+    /// if lower.
+    pub fn merge(&mut self, other: FrequencyMap) {
+        if let Some(threshold) = other.tail_threshold {
+            self.bucket_tail(threshold);
+        }
+
+        for (length, frequency) in other.map {
+            self.add(length, frequency);
+        }
+        self.tail_count += other.tail_count;
+        self.tail_sum += other.tail_sum;
+    }
+
+    /// The total number of walks recorded, including those in the tail bucket.
+    pub fn total_count(&self) -> u64 {
+        self.map
+            .values()
+            .map(|frequency| *frequency as u64)
+            .sum::<u64>()
+            + self.tail_count as u64
+    }
+
+    /// The total length of all the walks recorded, including those in the tail bucket.
+    pub fn total_length(&self) -> u64 {
+        self.iter()
+            .map(|(length, frequency)| length as u64 * frequency as u64)
+            .sum::<u64>()
+            + self.tail_sum
+    }
+
+    /// The mean walk length, or zero if no walks have been recorded. This is exact, even with a
+    /// tail bucket.
+    pub fn mean(&self) -> f64 {
+        let total = self.total_count();
+        if total == 0 {
+            return 0.0;
+        }
+
+        self.total_length() as f64 / total as f64
+    }
+
+    /// The most frequent walk length, or `None` if no walks have been recorded. Ties go to the
+    /// shortest length. If the tail bucket is the most frequent, this is its threshold.
+    pub fn mode(&self) -> Option<u32> {
+        self.buckets()
+            .max_by_key(|(length, frequency)| (*frequency, std::cmp::Reverse(*length)))
+            .map(|(length, _)| length)
+    }
+
+    /// The walk length at the `p`-th quantile, for `p` between 0 and 1: the shortest length such
+    /// that at least a fraction `p` of walks were no longer than it. A quantile falling in the
+    /// tail bucket gives its threshold.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `p` is outside `[0, 1]`, or if no walks have been recorded.
+    pub fn percentile(&self, p: f64) -> u32 {
+        assert!(
+            (0.0..=1.0).contains(&p),
+            "percentile should be between 0 and 1"
+        );
+        assert!(!self.is_empty(), "no walks have been recorded");
+
+        let mut walk_lengths: Vec<_> = self.buckets().collect();
+        walk_lengths.sort_unstable();
+        let target = ((p * self.total_count() as f64).ceil() as u64).max(1);
+        let mut cumulative = 0;
+        for (walk_length, frequency) in &walk_lengths {
+            cumulative += *frequency as u64;
+            if cumulative >= target {
+                return *walk_length;
+            }
+        }
+
+        walk_lengths.last().unwrap().0
+    }
+
+    /// The median walk length. See [percentile](FrequencyMap::percentile).
+    pub fn median(&self) -> u32 {
+        self.percentile(0.5)
+    }
+
+    /// The Shannon entropy of the distribution of walk lengths in bits, $-\sum_i p_i \log_2 p_i$,
+    /// where $p_i$ is the fraction of walks with the $i$-th length. This is zero if no walks, or
+    /// only walks of a single length, have been recorded. The tail bucket counts as one length.
+    pub fn entropy(&self) -> f64 {
+        let total = self.total_count() as f64;
+        self.buckets()
+            .filter(|(_, frequency)| *frequency > 0)
+            .map(|(_, frequency)| {
+                let p = frequency as f64 / total;
+                -p * p.log2()
+            })
+            .sum()
+    }
+
+    /// Every `(length, frequency)` pair, with the tail bucket, if it holds any walks, as a pair
+    /// for its threshold.
+    fn buckets(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        let tail = self
+            .tail_threshold
+            .filter(|_| self.tail_count > 0)
+            .map(|threshold| (threshold, self.tail_count));
+        self.iter().chain(tail)
+    }
+}
+
+/// A struct to calculate the expected length of a random walk, for any type `T: RandomWalk`. We
+/// will use this to calculate the expected values of walks on our [Football](crate::Football) and
+/// [KitchenFloor](crate::KitchenFloor) types.
+pub struct Expectation<T: RandomWalk> {
+    /// The model of our random walk.
+    walker: T,
+
+    /// A map from walk lengths to the frequency of occurences of walks of that length.
+    pub freq_map: FrequencyMap,
+
+    /// Count of the number of runs executed so far.
+    pub cnt: u32,
+
+    /// The running mean of the walk lengths, maintained with Welford's online algorithm.
+    mean: f64,
+
+    /// The running sum of squared deviations from the mean, maintained with Welford's online
+    /// algorithm.
+    m2: f64,
+
+    /// The length of the longest walk seen so far.
+    max_length: u32,
+}
+
+impl<T: RandomWalk> Expectation<T> {
+    /// Create a new expectation calculator.
+    pub fn new(walker: T) -> Self {
+        Self {
+            walker,
+            freq_map: FrequencyMap::new(),
+            cnt: 0,
+            mean: 0.0,
+            m2: 0.0,
+            max_length: 0,
+        }
+    }
+
+    /// Forget every run recorded so far, clearing [freq_map](Expectation::freq_map) and zeroing
+    /// [cnt](Expectation::cnt) and the running moments, so that the calculator can be reused, e.g.
+    /// across a sweep of parameters. The walker is kept. Its internal state is not touched here,
+    /// but every walk starts by calling [set_state](RandomWalk::set_state) with `src`, so it is
+    /// reset on the next run anyway.
+    pub fn reset(&mut self) {
+        self.freq_map.clear();
+        self.cnt = 0;
+        self.mean = 0.0;
+        self.m2 = 0.0;
+        self.max_length = 0;
+    }
+
+    /// Run the expectation computation. Internally, this calls `walker.walk` which does not take a
+    /// limit cut-off for walk lengths. Therefore, this function could take a long time if walks
+    /// can be extremely long or even diverege to infinity.
+    pub fn calculate(&mut self, src: T::State, tgt: T::State, runs: u32) -> f32 {
+        self.calculate_with_rng(src, tgt, runs, &mut rand::thread_rng())
+    }
+
+    /// Same as [calculate](Expectation::calculate), but every move of the walker is drawn from
+    /// `rng` rather than the thread-local generator. Passing a seeded generator makes the whole
+    /// computation, including [freq_map](Expectation::freq_map), reproducible.
+    pub fn calculate_with_rng<R: Rng>(
+        &mut self,
+        src: T::State,
+        tgt: T::State,
+        runs: u32,
+        rng: &mut R,
+    ) -> f32 {
+        self.assert_mode(false);
+        while self.cnt < runs {
+            let steps = self.walker.walk(src.clone(), tgt.clone(), rng);
+            self.freq_map.add(steps, 1);
+            self.record(steps);
+        }
+
+        self.finish()
+    }
+
+    /// Same as [calculate](Expectation::calculate), but does not populate
+    /// [freq_map](Expectation::freq_map). Only the running [mean](Expectation::mean) and
+    /// [variance](Expectation::variance) are updated, so memory use stays constant however many
+    /// distinct walk lengths are seen.
+    ///
+    /// # Panics
+    ///
+    /// Panics if runs have already been recorded in [freq_map](Expectation::freq_map), as it
+    /// would then no longer count every run.
+    pub fn calculate_streaming(&mut self, src: T::State, tgt: T::State, runs: u32) -> f64 {
+        self.assert_mode(true);
+        let mut rng = rand::thread_rng();
+        while self.cnt < runs {
+            let steps = self.walker.walk(src.clone(), tgt.clone(), &mut rng);
+            self.record(steps);
+        }
+
+        self.mean()
+    }
+
+    /// Same as [calculate](Expectation::calculate) but takes a `limit` argument which is passed to
+    /// [RandomWalk::walk_until_limit](RandomWalk::walk_until_limit) in order to ensure the function
+    /// terminates, ideally in a reasonable time.
+    pub fn calculate_with_limit(
+        &mut self,
+        src: T::State,
+        tgt: T::State,
+        runs: u32,
+        limit: u32,
+    ) -> f32 {
+        self.assert_mode(false);
+        let mut rng = rand::thread_rng();
+        while self.cnt < runs {
+            let steps = self
+                .walker
+                .walk_until_limit(src.clone(), tgt.clone(), &mut rng, limit)
+                .steps();
+            self.freq_map.add(steps, 1);
+            self.record(steps);
+        }
+
+        self.finish()
+    }
+
+    /// Keep running batches of `window` walks until the mean walk length changes by less than
+    /// `tol` from one batch to the next, rather than fixing the number of runs up front. Gives up
+    /// once [MAX_CONVERGENCE_RUNS] runs have been made, whether or not the mean has settled.
+    ///
+    /// Returns the mean walk length and the total number of runs it was computed from. Like
+    /// [calculate](Expectation::calculate), this places no limit on the length of each walk.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `window` is zero or `tol` isn't positive, as the mean could then never settle.
+    pub fn calculate_until_converged(
+        &mut self,
+        src: T::State,
+        tgt: T::State,
+        tol: f64,
+        window: u32,
+    ) -> (f32, u32) {
+        assert!(
+            window > 0 && tol > 0.0,
+            "should have a positive window and tolerance"
+        );
+        self.assert_mode(false);
+
+        let mut rng = rand::thread_rng();
+        let mut prev_mean = None;
+        while self.cnt < MAX_CONVERGENCE_RUNS {
+            for _ in 0..window.min(MAX_CONVERGENCE_RUNS - self.cnt) {
+                let steps = self.walker.walk(src.clone(), tgt.clone(), &mut rng);
+                self.freq_map.add(steps, 1);
+                self.record(steps);
+            }
+
+            if prev_mean.is_some_and(|prev: f64| (self.mean - prev).abs() < tol) {
+                break;
+            }
+            prev_mean = Some(self.mean);
+        }
+
+        (self.finish(), self.cnt)
+    }
+
+    /// The mean walk length over all runs so far.
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    /// The length of the longest walk over all runs so far, or zero if there have been none. With
+    /// a limit on the walk length, walks cut off at the limit count as being that long. This is
+    /// useful for choosing a sensible limit for fat-tailed distributions.
+    pub fn max_length(&self) -> u32 {
+        self.max_length
+    }
+
+    /// The (unbiased) sample variance of the walk lengths over all runs so far. This is zero until
+    /// at least two runs have been made.
+    pub fn variance(&self) -> f64 {
+        if self.cnt < 2 {
+            0.0
+        } else {
+            self.m2 / (self.cnt - 1) as f64
+        }
+    }
+
+    /// The sample standard deviation of the walk lengths over all runs so far.
+    pub fn std_dev(&self) -> f64 {
+        self.variance().sqrt()
+    }
+
+    /// The walk length at the `p`-th quantile of the recorded walks, for `p` between 0 and 1: the
+    /// shortest length such that at least a fraction `p` of walks were no longer than it. This is
+    /// read from [freq_map](Expectation::freq_map), so reflects only runs which populate it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `p` is outside `[0, 1]`, or if no walks have been recorded.
+    pub fn percentile(&self, p: f64) -> u32 {
+        self.freq_map.percentile(p)
+    }
+
+    /// The median walk length of the recorded walks. See [percentile](Expectation::percentile).
+    pub fn median(&self) -> u32 {
+        self.percentile(0.5)
+    }
+
+    /// The Shannon entropy, in bits, of the distribution of walk lengths in
+    /// [freq_map](Expectation::freq_map). See [FrequencyMap::entropy]. This gives a single number
+    /// for how spread out the walk lengths are. It is dominated by the common lengths rather than
+    /// the tail: although the kitchen floor's walk lengths have a far fatter tail than the
+    /// football's, a third of its walks are over in two steps, and its entropy is lower.
+    pub fn entropy(&self) -> f64 {
+        self.freq_map.entropy()
+    }
+
+    /// Write [freq_map](Expectation::freq_map) to `w` as CSV, with a `length,frequency` header
+    /// followed by one row per walk length, shortest first. If the map has a tail bucket with
+    /// threshold `T`, it is written last, with length `>=T`.
+    pub fn write_csv<W: Write>(&self, mut w: W) -> io::Result<()> {
+        writeln!(w, "length,frequency")?;
+        for (walk_length, frequency) in self.freq_map.sorted() {
+            writeln!(w, "{},{}", walk_length, frequency)?;
+        }
+        if let Some(threshold) = self.freq_map.tail_threshold() {
+            writeln!(w, ">={},{}", threshold, self.freq_map.tail_count())?;
+        }
+
+        Ok(())
+    }
+
+    /// Fold the runs recorded by `other` into this calculator, e.g. to combine the results of
+    /// several `Expectation`s run on separate threads. The frequency maps need not cover the same
+    /// walk lengths; counts for lengths present in both are added together.
+    pub fn merge(&mut self, other: Expectation<T>) {
+        self.freq_map.merge(other.freq_map);
+
+        // Combine the running moments using the pairwise update of Chan, Golub and LeVeque.
+        let cnt = self.cnt + other.cnt;
+        if cnt > 0 {
+            let delta = other.mean - self.mean;
+            let weight = self.cnt as f64 * other.cnt as f64 / cnt as f64;
+            self.mean += delta * other.cnt as f64 / cnt as f64;
+            self.m2 += other.m2 + delta * delta * weight;
+        }
+        self.cnt = cnt;
+        self.max_length = self.max_length.max(other.max_length);
+    }
+
+    /// Check that the runs so far were all recorded the same way as the next ones will be: either
+    /// every run is in [freq_map](Expectation::freq_map), or, when `streaming`, none are.
+    fn assert_mode(&self, streaming: bool) {
+        if streaming {
+            assert!(
+                self.freq_map.is_empty(),
+                "can't stream runs once the frequency map has been filled"
+            );
+        } else {
+            assert_eq!(
+                self.freq_map.total_count(),
+                self.cnt as u64,
+                "can't fill the frequency map after streaming runs"
+            );
+        }
+    }
+
+    /// Count a run of length `steps`, updating the running moments.
+    fn record(&mut self, steps: u32) {
+        self.cnt += 1;
+        self.max_length = self.max_length.max(steps);
+
+        let x = steps as f64;
+        let delta = x - self.mean;
+        self.mean += delta / self.cnt as f64;
+        self.m2 += delta * (x - self.mean);
+    }
+
+    fn finish(&self) -> f32 {
+        self.freq_map.total_length() as f32 / self.cnt as f32
+    }
+}
+
+/// Run this to get the answer to the first part of the question.
+///
+/// Interestingly, switching to [calculate_with_limit](Expectation::calculate_with_limit) and running
+/// this with a walk-length limit even as high as 80 or 90 seems to
+/// give the wrong picture. The expected value appears to vary significantly even as we
+/// continue increasing the walk length limit to high values, so very long walks are
+/// contributing to the result (it is a fat-tailed distribution). This is corroborated when we print
+/// out the frequency map and see that we are getting plenty of walks which are several hundred steps
+/// long.
+///
+/// Using the [calculate](Expectation::calculate) method which imposes no limitation on walk-lengths, but takes longer
+/// to run, we seem to immediately be converging on an expected walk-length of 20 (which is of
+/// course the total number of nodes in the graph). This is an interesting result, and one we later
+/// proved rigorously (see [crate-level documentation](./index.html#first-part)).
+///
+/// Walks start and end at `home`; by symmetry, the answer is the same whichever hexagon is chosen.
+pub fn expected_walk_length_on_football(home: i32) {
+    let football = Football::with_home(home);
+    let mut exp = Expectation::new(football);
+    let runs = 100_000_000;
+    let exp_walk_length = exp.calculate(home, home, runs);
+    println!("E(length of walk to return home): {}", exp_walk_length);
+    println!("cnt: {}", exp.cnt);
+    exp.write_csv(io::stdout().lock())
+        .expect("should be able to write to stdout");
+}
+
+/// Find the probability that the random walk on the kitchen floor is strictly more than 20 steps.
+///
+/// This is the first attempt to solve the second part of the question. We discovered that 20 steps
+/// is the expected length of a walk on the football, so we want want to know what proportion of
+/// walks on the kitchen floor last longer than 20 steps.
+///
+/// We will perform a large number of runs, counting how many reach 21 steps without
+/// terminating.
+///
+/// With increasing runs, we seem to be converging towards about 0.448.
+///
+/// Returns the number of walks longer than 20 steps, and the total number of runs. When `verbose`
+/// is set, progress is printed every 5% of the way through, followed by a summary.
+pub fn prob_of_longer_walk_in_the_kitchen(runs: u64, verbose: bool) -> (u64, u64) {
+    prob_of_longer_walk_in_the_kitchen_with_rng(runs, verbose, &mut rand::thread_rng())
+}
+
+/// Same as [prob_of_longer_walk_in_the_kitchen], but draws every move from the supplied `rng`, so
+/// that a seeded generator gives reproducible results.
+pub fn prob_of_longer_walk_in_the_kitchen_with_rng<R: Rng>(
+    runs: u64,
+    verbose: bool,
+    rng: &mut R,
+) -> (u64, u64) {
+    prob_of_longer_walk_from(KitchenFloor::new(), runs, verbose, rng)
+}
+
+/// Same as [prob_of_longer_walk_in_the_kitchen_with_rng], but every walk starts and ends at the
+/// [home](KitchenFloor::home) of `kitchen_floor`, e.g. one made with [KitchenFloor::with_home].
+pub fn prob_of_longer_walk_from<R: Rng>(
+    mut kitchen_floor: KitchenFloor,
+    runs: u64,
+    verbose: bool,
+    rng: &mut R,
+) -> (u64, u64) {
+    let home = kitchen_floor.home();
+    let progress_unit = runs / 20;
+    let mut progress_cnt = 0;
+    let mut progress = 0;
+    let mut cnt: u64 = 0;
+    let mut longer_walk_cnt: u64 = 0;
+    while cnt < runs {
+        match kitchen_floor.walk_until_limit(home, home, rng, 20) {
+            WalkOutcome::Reached(_) => {
+                // We terminated on or before the 20th step. So this does not contribute to our count
+                // of longer walks.
+            }
+            WalkOutcome::LimitHit(_) => {
+                // We had not terminated by the 20th step, so this does contribute to our count of
+                // longer walks.
+                longer_walk_cnt += 1;
+            }
+        };
+        cnt += 1;
+        progress_cnt += 1;
+        if progress_cnt == progress_unit {
+            progress += 1;
+            if verbose {
+                println!(
+                    "{:2}% complete, {} runs, current prob: {}",
+                    progress as f32 * 5.0,
+                    cnt,
+                    longer_walk_cnt as f64 / cnt as f64
+                );
+            }
+            progress_cnt = 0;
+        }
+    }
+    if verbose {
+        println!("runs longer than 20: {}", longer_walk_cnt);
+        println!("total runs: {}", runs);
+        println!(
+            "probability of a longer than 20 walk: {}",
+            longer_walk_cnt as f64 / runs as f64
+        );
+        let (lo, hi) = wilson_interval(longer_walk_cnt, runs, 1.96);
+        println!("95% confidence interval: [{}, {}]", lo, hi);
+    }
+    (longer_walk_cnt, runs)
+}
+
+/// Same as [prob_of_longer_walk_in_the_kitchen], but records how the estimate converges rather
+/// than printing it. After every `sample_every` runs, and after the final run, the number of runs
+/// so far and the running estimate of the probability are recorded, so the trajectory can be
+/// plotted without storing anything per run.
+///
+/// # Panics
+///
+/// Panics if `sample_every` is zero.
+pub fn prob_of_longer_walk_with_trace(runs: u64, sample_every: u64) -> Vec<(u64, f64)> {
+    prob_of_longer_walk_with_trace_and_rng(runs, sample_every, &mut rand::thread_rng())
+}
+
+/// Same as [prob_of_longer_walk_with_trace], but draws every move from the supplied `rng`, so that
+/// a seeded generator gives reproducible results.
+///
+/// # Panics
+///
+/// Panics if `sample_every` is zero.
+pub fn prob_of_longer_walk_with_trace_and_rng<R: Rng>(
+    runs: u64,
+    sample_every: u64,
+    rng: &mut R,
+) -> Vec<(u64, f64)> {
+    assert!(sample_every > 0, "sample_every should be > 0");
+
+    let mut kitchen_floor = KitchenFloor::new();
+    let home = kitchen_floor.home();
+    let mut trace = Vec::with_capacity((runs / sample_every + 1) as usize);
+    let mut longer_walk_cnt: u64 = 0;
+    for cnt in 1..=runs {
+        if let WalkOutcome::LimitHit(_) = kitchen_floor.walk_until_limit(home, home, rng, 20) {
+            longer_walk_cnt += 1;
+        }
+
+        if cnt % sample_every == 0 || cnt == runs {
+            trace.push((cnt, longer_walk_cnt as f64 / cnt as f64));
+        }
+    }
+
+    trace
+}
+
+/// Estimate the probability that a walk on the kitchen floor is strictly more than 20 steps, using
+/// [antithetic variates](https://en.wikipedia.org/wiki/Antithetic_variates) to reduce the variance
+/// of the estimate.
+///
+/// Each of the `runs` random walks is paired with a mirrored walk, which makes the other of the
+/// last two choices at every step where the random walk made one of them. Both walks are equally
+/// likely, so the estimate is still unbiased, but their outcomes are negatively correlated (by
+/// about -0.17), so each pair tells us more than two independent walks would.
+///
+/// The mirror is deliberately not a symmetry of the floor. Swapping the first two choices at
+/// every step instead is a reflection of the floor, so the mirrored walk would return home on
+/// exactly the same step as the random one, and nothing would be gained.
+///
+/// Returns the number of walks longer than 20 steps, and the total number of walks, `2 * runs`.
+pub fn prob_of_longer_walk_antithetic(runs: u64) -> (u64, u64) {
+    prob_of_longer_walk_antithetic_with_rng(runs, &mut rand::thread_rng())
+}
+
+/// Same as [prob_of_longer_walk_antithetic], but draws every move from the supplied `rng`, so that
+/// a seeded generator gives reproducible results.
+pub fn prob_of_longer_walk_antithetic_with_rng<R: Rng>(runs: u64, rng: &mut R) -> (u64, u64) {
+    let mut kitchen_floor = KitchenFloor::new();
+    let mut longer_walk_cnt = 0;
+    for _ in 0..runs {
+        let (walk, mirror) = antithetic_pair(&mut kitchen_floor, rng);
+        longer_walk_cnt += walk as u64 + mirror as u64;
+    }
+
+    (longer_walk_cnt, 2 * runs)
+}
+
+/// Take a random walk of up to 20 steps on `kitchen_floor`, along with its mirror as described on
+/// [prob_of_longer_walk_antithetic], and return whether each of them is longer than 20 steps.
+fn antithetic_pair<R: Rng>(kitchen_floor: &mut KitchenFloor, rng: &mut R) -> (bool, bool) {
+    let mut decisions = [0; 20];
+    for dec in decisions.iter_mut() {
+        *dec = rng.gen_range(0..3);
+    }
+    let mirrored = decisions.map(|dec| [0, 2, 1][dec]);
+
+    let mut is_longer = |decisions: [usize; 20]| {
+        let home = kitchen_floor.home();
+        kitchen_floor.set_state(home);
+        !decisions.into_iter().any(|dec| {
+            kitchen_floor.move_from_idx(dec);
+            kitchen_floor.get_state() == home
+        })
+    };
+
+    (is_longer(decisions), is_longer(mirrored))
+}
+
+/// Estimate the probability that a walk on `subject` is strictly longer than the expected return
+/// time of `reference`. This is the structure of the whole puzzle, with the football as the
+/// reference and the kitchen floor as the subject, but any pair of walks can be compared.
+///
+/// Each walk starts and ends at the walker's current state. The expected return time of
+/// `reference` is estimated from `runs` walks, as by
+/// [expected_return_time_monte_carlo](RandomWalk::expected_return_time_monte_carlo), and then
+/// compared against with [prob_longer_than]. When the expected return time is a whole number, as
+/// it is for the football, noise in the estimate puts it on either side of that number about
+/// equally often, which changes the answer sharply. If the expected return time is known exactly,
+/// e.g. from [Football::expected_return_time], pass it to `prob_longer_than` instead.
+///
+/// Like [walk](RandomWalk::walk), this could block forever if some walk on `reference` never
+/// returns home.
+///
+/// # Panics
+///
+/// Panics if `runs` is zero.
+pub fn prob_longer_than_reference<A: RandomWalk, B: RandomWalk>(
+    reference: A,
+    subject: B,
+    runs: u32,
+) -> f64 {
+    prob_longer_than_reference_with_rng(reference, subject, runs, &mut rand::thread_rng())
+}
+
+/// Same as [prob_longer_than_reference], but draws every move from the supplied `rng`, so that a
+/// seeded generator gives reproducible results.
+pub fn prob_longer_than_reference_with_rng<A: RandomWalk, B: RandomWalk, R: Rng>(
+    mut reference: A,
+    subject: B,
+    runs: u32,
+    rng: &mut R,
+) -> f64 {
+    assert!(runs > 0, "should make at least one run");
+
+    let reference_home = reference.get_state();
+    let expected = reference.expected_return_time_monte_carlo(reference_home, runs, rng);
+    prob_longer_than_with_rng(subject, expected, runs, rng)
+}
+
+/// Estimate the probability that a walk on `subject`, starting and ending at its current state, is
+/// strictly longer than `threshold` steps, from `runs` walks. Each walk is cut off as soon as it
+/// exceeds the threshold, so this can't block even if some walks never return.
+///
+/// Walk lengths are whole numbers, so a walk exceeds `threshold` when it hasn't returned home
+/// after $\lfloor \text{threshold} \rfloor$ steps. A threshold within rounding error of a whole
+/// number counts as that number, so that an exactly calculated expected return time of 20 which
+/// comes out as 19.999999999999996 still compares walks against 20 steps.
+///
+/// # Panics
+///
+/// Panics if `runs` is zero.
+pub fn prob_longer_than<B: RandomWalk>(subject: B, threshold: f64, runs: u32) -> f64 {
+    prob_longer_than_with_rng(subject, threshold, runs, &mut rand::thread_rng())
+}
+
+/// Same as [prob_longer_than], but draws every move from the supplied `rng`, so that a seeded
+/// generator gives reproducible results.
+pub fn prob_longer_than_with_rng<B: RandomWalk, R: Rng>(
+    mut subject: B,
+    threshold: f64,
+    runs: u32,
+    rng: &mut R,
+) -> f64 {
+    assert!(runs > 0, "should make at least one run");
+
+    let limit = (threshold + 1e-9).floor();
+    if limit < 1.0 {
+        // Every walk takes at least one step.
+        return 1.0;
+    }
+    let limit = limit.min(u32::MAX as f64) as u32;
+
+    let home = subject.get_state();
+    let longer_walk_cnt = (0..runs)
+        .filter(|_| {
+            subject
+                .walk_until_limit(home.clone(), home.clone(), rng, limit)
+                .reached()
+                .is_none()
+        })
+        .count();
+
+    longer_walk_cnt as f64 / runs as f64
+}
+
+/// The [Wilson score interval](https://en.wikipedia.org/wiki/Binomial_proportion_confidence_interval#Wilson_score_interval)
+/// for a probability estimated from `successes` out of `trials` independent Bernoulli trials.
+///
+/// `z` is the standard normal quantile for the desired confidence level, e.g. `1.96` for a 95%
+/// interval. Unlike the more familiar $\hat{p} \pm z \sqrt{\hat{p}(1 - \hat{p}) / n}$, the Wilson
+/// interval behaves sensibly when $\hat{p}$ is close to 0 or 1, and never strays outside $[0, 1]$.
+///
+/// Returns `(lower, upper)`. With no trials at all, nothing is known and `(0.0, 1.0)` is returned.
+pub fn wilson_interval(successes: u64, trials: u64, z: f64) -> (f64, f64) {
+    if trials == 0 {
+        return (0.0, 1.0);
+    }
+
+    let n = trials as f64;
+    let p = successes as f64 / n;
+    let z2 = z * z;
+
+    let denom = 1.0 + z2 / n;
+    let centre = (p + z2 / (2.0 * n)) / denom;
+    let half_width = z / denom * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt();
+
+    (
+        (centre - half_width).max(0.0),
+        (centre + half_width).min(1.0),
+    )
+}
+
+/// Multithreaded version of [prob_of_longer_walk_in_the_kitchen](prob_of_longer_walk_in_the_kitchen).
+///
+/// I've got a computer with lots of cpus, and running a monte carlo with indpendent trials is
+/// silly to do single-threaded. So we can split this across a bunch of threads to do more
+/// trials.
+///
+/// Running this with about 1 billion iterations per threads over 8 threads, we get to about an
+/// estimate of our probability that the random walk is longer than 20 steps of: ~0.448
+///
+/// Each of the `threads` workers performs `runs_per_thread` walks without printing anything.
+/// Returns the grand total of walks longer than 20 steps, and of runs, across all the threads.
+///
+/// If a `seed` is given, the worker with index `i` draws from its own [StdRng] seeded with
+/// `seed + i`, so the result is fully determined by the seed and the number of threads. Otherwise
+/// each worker uses its thread-local generator.
+pub fn multithreaded(threads: usize, runs_per_thread: u64, seed: Option<u64>) -> (u64, u64) {
+    let mut join_handles: Vec<std::thread::JoinHandle<(u64, u64)>> = Vec::with_capacity(threads);
+    for i in 0..threads {
+        join_handles.push(std::thread::spawn(move || match seed {
+            Some(seed) => {
+                let mut rng = StdRng::seed_from_u64(seed.wrapping_add(i as u64));
+                prob_of_longer_walk_in_the_kitchen_with_rng(runs_per_thread, false, &mut rng)
+            }
+            None => prob_of_longer_walk_in_the_kitchen(runs_per_thread, false),
+        }));
+    }
+    let mut results: Vec<(u64, u64)> = Vec::with_capacity(threads);
+    join_handles
+        .into_iter()
+        .for_each(|jh| results.push(jh.join().unwrap()));
+
+    results
+        .iter()
+        .fold((0, 0), |acc, e| (acc.0 + e.0, acc.1 + e.1))
+}
+
+/// A new approach to part 2. Enumerating every possible walk.
+///
+/// Our montecarlo approach doesn't seem to be converging on the correct answer fast enough for us
+/// to believe in the 7 decimal places we need to get this right. However, we are only looking for
+/// the number of terminating walks of less than 20 steps. We have a choice of three directions at
+/// each step, so perhaps we can just enumerate every one of the $3^{20}$ = c. 3.5 billion possible
+/// paths and get a precise answer.
+///
+/// Running this function (it's only single threaded so takes a few minutes - see
+/// [enumerate_every_walk_parallel] for a version which splits the enumerable range across a few
+/// CPUs) we get:
+///
+/// $$
+/// P(\text{random walk is longer than 20 steps}) = 0.4480326 \text{ (7 s.f.)}
+/// $$
+///
+/// This figure closely matches the answer we were getting stochastically, so presume we have got
+/// everything right.
+///
+/// Returns the number of walks which terminate within 20 steps, and the total number of walks.
+pub fn enumerate_every_walk() -> (u64, u64) {
+    let max: usize = 3_usize.pow(20);
+    let progress_unit = max / 20;
+    let mut progress_cnt = 0;
+    let mut progress = 0;
+
+    // Count the walks which terminate in our first 20 steps.
+    let mut terminated_cnt: usize = 0;
+
+    let mut kitchen_floor = KitchenFloor::new();
+
+    for (i, decisions) in Decisions::new().take(max).enumerate() {
+        kitchen_floor.set_state((0, 0));
+
+        for dec in decisions {
+            kitchen_floor.move_from_idx(dec);
+            if kitchen_floor.get_state() == (0, 0) {
+                terminated_cnt += 1;
+                break;
+            }
+        }
+
+        progress_cnt += 1;
+
+        if progress_cnt == progress_unit {
+            progress += 1;
+            println!("{:2}% complete, {} runs", progress as f32 * 5.0, i,);
+            progress_cnt = 0;
+        }
+    }
+
+    println!("terminated walks (<= 20 steps): {}", terminated_cnt);
+    println!(
+        "non-terminated walks (> 20 steps): {}",
+        max - terminated_cnt
+    );
+    println!(
+        "probability > 20: {:10}",
+        (max as f64 - terminated_cnt as f64) / max as f64
+    );
+
+    (terminated_cnt as u64, max as u64)
+}
+
+/// Multithreaded version of [enumerate_every_walk].
+///
+/// The $3^{20}$ possible paths are split into `threads` contiguous ranges of path indices. Each
+/// thread jumps straight to the start of its range with [Decisions::set_index], rather than
+/// incrementing all the way there from zero, and counts the terminating walks in its range on its
+/// own [KitchenFloor].
+///
+/// Returns the number of walks which terminate within 20 steps, and the total number of walks.
+///
+/// # Panics
+///
+/// Panics if `threads` is zero.
+pub fn enumerate_every_walk_parallel(threads: usize) -> (u64, u64) {
+    enumerate_in_chunks(Decisions::new(), threads)
+}
+
+/// Split the paths of `decisions` into `threads` chunks and count the terminating walks in each
+/// on a separate thread.
+fn enumerate_in_chunks<D>(decisions: Decisions<D>, threads: usize) -> (u64, u64)
+where
+    D: AsRef<[usize]> + AsMut<[usize]> + Clone + Send + 'static,
+{
+    assert!(threads > 0, "threads should be > 0");
+
+    let total = decisions.total();
+    let chunk = total.div_ceil(threads);
+
+    let mut join_handles: Vec<std::thread::JoinHandle<u64>> = Vec::with_capacity(threads);
+    for t in 0..threads {
+        let start = (t * chunk).min(total);
+        let end = ((t + 1) * chunk).min(total);
+        if start == end {
+            continue;
+        }
+
+        let mut decisions = decisions.clone();
+        decisions.set_index(start);
+        join_handles.push(std::thread::spawn(move || {
+            count_terminated_walks(decisions, end - start)
+        }));
+    }
+
+    let terminated = join_handles.into_iter().map(|jh| jh.join().unwrap()).sum();
+
+    (terminated, total as u64)
+}
+
+/// Count how many of the next `n` paths yielded by `decisions` return to the origin.
+fn count_terminated_walks<D>(decisions: Decisions<D>, n: usize) -> u64
+where
+    D: AsRef<[usize]> + AsMut<[usize]> + Clone,
+{
+    let mut kitchen_floor = KitchenFloor::new();
+
+    decisions
+        .take(n)
+        .filter(|path| returns_home(&mut kitchen_floor, path.as_ref()))
+        .count() as u64
+}
+
+/// Count every walk of `steps` moves on `kitchen_floor`, from its [home](KitchenFloor::home),
+/// which returns home on or before the last move. This generalises [enumerate_every_walk] to any
+/// number of steps, without printing progress, so that the exact answer can be found for
+/// thresholds other than 20. The walks are enumerated one by one, so this is only practical for
+/// small step counts; [GraphPathCounter] finds the same counts far more quickly.
+///
+/// Returns the number of walks which return home within `steps` steps, and the total number of
+/// walks, $3^n$. If the total doesn't fit in a `u64` (or a `usize`), [PathCountError::Overflow]
+/// is returned without enumerating anything.
+pub fn enumerate_walks(
+    kitchen_floor: &mut KitchenFloor,
+    steps: u32,
+) -> Result<(u64, u64), PathCountError> {
+    let total = 3_u64
+        .checked_pow(steps)
+        .filter(|total| usize::try_from(*total).is_ok())
+        .ok_or(PathCountError::Overflow)?;
+
+    let terminated_cnt = Decisions::with_params(3, steps as usize)
+        .filter(|path| returns_home(kitchen_floor, path))
+        .count() as u64;
+
+    Ok((terminated_cnt, total))
+}
+
+/// Make the moves of `path` on `kitchen_floor`, starting from its [home](KitchenFloor::home), and
+/// return whether the walk arrives back home on any of them. The walk is cut short if it does.
+fn returns_home(kitchen_floor: &mut KitchenFloor, path: &[usize]) -> bool {
+    let home = kitchen_floor.home();
+    kitchen_floor.set_state(home);
+
+    path.iter().any(|dec| {
+        kitchen_floor.move_from_idx(*dec);
+        kitchen_floor.get_state() == home
+    })
+}
+
+/// A helper struct to assist with iterating through the possible choices of path.
+///
+/// We are essentially counting in base 3. For simplicity, we're using an array of 20 `usize`
+/// integers, with the least significant digit first.
+///
+/// The same odometer works for any base and number of digits: [with_params](Decisions::with_params)
+/// builds one backed by a `Vec<usize>`, which is useful for walks with a different branching
+/// factor or step limit.
+///
+/// As an [Iterator], this yields every one of the [total](Decisions::total) paths in turn,
+/// starting from all zeros and finishing after every digit has reached its maximum.
+#[derive(Clone)]
+pub struct Decisions<D = [usize; 20]> {
+    curr: D,
+    /// The number of choices available for each digit.
+    base: usize,
+    /// Set once incrementing has wrapped back around past the final path.
+    wrapped: bool,
+}
+
+impl Default for Decisions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decisions {
+    /// Create a new decision iterator.
+    pub fn new() -> Self {
+        Self {
+            curr: [0; 20],
+            base: 3,
+            wrapped: false,
+        }
+    }
+}
+
+impl Decisions<Vec<usize>> {
+    /// Create a new decision iterator over paths of `len` steps, each with `base` choices.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `base` is zero, or if the [total](Decisions::total) number of paths,
+    /// `base` to the power `len`, doesn't fit in a `usize`.
+    pub fn with_params(base: usize, len: usize) -> Self {
+        assert!(base > 0, "base should be > 0");
+        assert!(
+            u32::try_from(len)
+                .ok()
+                .and_then(|len| base.checked_pow(len))
+                .is_some(),
+            "total number of paths should fit in a usize"
+        );
+
+        Self {
+            curr: vec![0; len],
+            base,
+            wrapped: false,
+        }
+    }
+}
+
+impl<D: AsRef<[usize]> + AsMut<[usize]>> Decisions<D> {
+    /// Return the current value of the array representation of our path.
+    pub fn curr(&self) -> &D {
+        &self.curr
+    }
+
+    /// The number of choices available at each step.
+    pub fn base(&self) -> usize {
+        self.base
+    }
+
+    /// The total number of distinct paths, i.e. `base` to the power of the path length. This
+    /// always fits in a `usize`, as [with_params](Decisions::with_params) checks.
+    pub fn total(&self) -> usize {
+        self.base
+            .checked_pow(self.curr.as_ref().len() as u32)
+            .expect("total number of paths should fit in a usize")
+    }
+
+    /// Jump directly to the path with the given index, i.e. the path that would be reached by
+    /// incrementing `index` times from all zeros.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is not less than [total](Self::total).
+    pub fn set_index(&mut self, index: usize) {
+        assert!(index < self.total(), "index should be < total");
+
+        let mut rest = index;
+        for digit in self.curr.as_mut() {
+            *digit = rest % self.base;
+            rest /= self.base;
+        }
+        self.wrapped = false;
+    }
+
+    /// Increment to the next path.
+    pub fn inc(&mut self) {
+        let max = self.base - 1;
+        for digit in self.curr.as_mut() {
+            if *digit == max {
+                *digit = 0;
+            } else {
+                *digit += 1;
+                // As soon as we increment something, rather than reseting, we bail.
+                return;
+            }
+        }
+
+        // Every digit was reset, so we have rolled over.
+        self.wrapped = true;
+    }
+}
+
+impl<D: AsRef<[usize]> + AsMut<[usize]> + Clone> Iterator for Decisions<D> {
+    type Item = D;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.wrapped {
+            return None;
+        }
+
+        let curr = self.curr.clone();
+        self.inc();
+        Some(curr)
+    }
+}
+
+/// A representation of a coordinate on our [KitchenFloor](KitchenFloor) plane.
+pub type Coord = (i32, i32);
+
+/// Stores a representation of the underlying graph, tracking how many paths have reached each node
+/// at current time step `self.step`.
+///
+/// The graph is that of the tiling `T`, which is the [KitchenFloor](KitchenFloor) unless
+/// otherwise specified. Walks start and end at the origin, unless another
+/// [home](Self::with_home) is chosen.
+///
+/// Calling [next](Self::next) steps the graph representation forward by:
+/// a) introducing any new nodes to the graph which haven't don't already exist from previous steps
+/// b) iterating every node in the graph and setting its new value to the be the sum of the values
+///    in the surrounding nodes from the previous step (but not counting any contribution from the
+///    origin, because any paths which reached this on the previous step would have terminated
+///    there).
+pub struct GraphPathCounter<T: TilingWalk = KitchenFloor> {
+    /// The tiling whose paths are counted.
+    tiling: std::marker::PhantomData<T>,
+
+    /// Tracks the total number of paths which can arrive at a given coord by a certain time step.
+    pub cells: std::cell::RefCell<HashMap<Coord, u128>>,
+
+    /// Tracks which time step we are currently at.
+    step: usize,
+
+    /// The cell where every path starts, and where it ends on first returning.
+    home: Coord,
+}
+
+impl<T: TilingWalk> Default for GraphPathCounter<T> {
+    fn default() -> Self {
+        Self::for_tiling()
+    }
+}
+
+impl GraphPathCounter {
+    /// Create a new graph counter for the kitchen floor.
+    pub fn new() -> Self {
+        Self::for_tiling()
+    }
+}
+
+impl<T: TilingWalk> GraphPathCounter<T> {
+    /// Create a new graph counter for the tiling `T`, e.g.
+    /// `GraphPathCounter::<SquareLattice>::for_tiling()`.
+    pub fn for_tiling() -> Self {
+        Self::with_home((0, 0))
+    }
+
+    /// Create a new graph counter for the tiling `T` whose paths start and end at `home`, rather
+    /// than the origin. On the kitchen floor, `home` should be a white hexagon, as for
+    /// [KitchenFloor::with_home].
+    pub fn with_home(home: Coord) -> Self {
+        let mut counter = Self {
+            tiling: std::marker::PhantomData,
+            cells: std::cell::RefCell::new(HashMap::new()),
+            step: 0,
+            home,
+        };
+
+        counter.reset();
+        counter
+    }
+
+    /// Return to step zero, where the only path is the one sitting at home.
+    fn reset(&mut self) {
+        let cells = self.cells.get_mut();
+        cells.clear();
+        cells.insert(self.home, 1);
+        self.step = 0;
+    }
+
+    /// Step the internal graph representation forward.
+    ///
+    /// In summary, this function does works by:
+    /// a) introducing any new nodes to the graph which haven't don't already exist from previous steps
+    /// b) iterating every node in the graph and setting its new value to the be the sum of the values
+    ///    in the surrounding nodes from the previous step (but not counting any contribution from the
+    ///    origin, because any paths which reached this on the previous step would have terminated
+    ///    there).
+    /// c) removing any nodes, other than the origin, whose value has dropped to zero.
+    pub fn next(&mut self) {
+        self.step += 1;
+
+        let cells: Vec<Coord> = self.cells.borrow().keys().copied().collect();
+        for cell in cells {
+            let neighbours = T::neighbours(cell);
+
+            for n in neighbours.iter() {
+                // Ensure that the neighbour actually has an entry in the table.
+                let _cell = self.cells.borrow_mut().entry(*n).or_insert(0);
+            }
+        }
+
+        let mut new_values = Vec::new();
+
+        // Now, we iterate over everything that appears in the table so far, and add to the counts
+        // of each cell the sum of the counts of its neighbouring cells.
+        for cell in self.cells.borrow().keys() {
+            let mut new_cnt = 0;
+            let cell_neighbours = T::neighbours(*cell);
+
+            for n in cell_neighbours.iter() {
+                if *n != self.home || self.step == 1 {
+                    if let Some(n_cnt) = self.cells.borrow().get(n) {
+                        new_cnt += *n_cnt;
+                    }
+                }
+            }
+
+            new_values.push((*cell, new_cnt));
+        }
+
+        for (cell, cnt) in new_values {
+            *self.cells.borrow_mut().entry(cell).or_insert(0) = cnt;
+        }
+
+        // Drop any cells no path occupies any more, so the table only grows with the frontier.
+        // Home is kept, as callers read its count after every step.
+        let home = self.home;
+        self.cells
+            .borrow_mut()
+            .retain(|cell, cnt| *cnt > 0 || *cell == home);
+    }
+
+    /// The bounding box of the cells which paths occupy at the current step, as the corners
+    /// `((min_x, min_y), (max_x, max_y))`. Cells with a zero count are ignored. If no cell has a
+    /// non-zero count, the box is just home.
+    pub fn extent(&self) -> (Coord, Coord) {
+        let cells = self.cells.borrow();
+        let mut occupied = cells.iter().filter(|(_, cnt)| **cnt > 0).map(|(c, _)| c);
+
+        let Some(first) = occupied.next() else {
+            return (self.home, self.home);
+        };
+        occupied.fold((*first, *first), |(min, max), (x, y)| {
+            (
+                (min.0.min(*x), min.1.min(*y)),
+                (max.0.max(*x), max.1.max(*y)),
+            )
+        })
+    }
+
+    /// The number of cells which paths occupy at the current step, i.e. those with a non-zero
+    /// count.
+    pub fn support_size(&self) -> usize {
+        self.cells.borrow().values().filter(|cnt| **cnt > 0).count()
+    }
+
+    /// Write the path count of every cell at the current step to `w` as CSV, for plotting as a
+    /// heatmap. There is an `x,y,count` header, followed by one row per cell in order of `x` then
+    /// `y`. Cells no path occupies are omitted, except home. The hexagon coordinates can be
+    /// placed in the plane with [KitchenFloor::coord_to_cartesian].
+    pub fn to_heatmap_csv<W: Write>(&self, mut w: W) -> io::Result<()> {
+        let cells = self.cells.borrow();
+        let mut coords: Vec<&Coord> = cells.keys().collect();
+        coords.sort_unstable();
+
+        writeln!(w, "x,y,count")?;
+        for coord in coords {
+            writeln!(w, "{},{},{}", coord.0, coord.1, cells[coord])?;
+        }
+
+        Ok(())
+    }
+
+    /// Run the analysis for a given number of steps.
+    ///
+    /// This function tracks how many paths return home, (0, 0) by default, in total across all the
+    /// steps. We need to be careful to upscale each such number by a factor of $b^k$ where $k$ is
+    /// the number of remaining steps, and $b$ the [branching factor](TilingWalk::branching_factor)
+    /// of the tiling (3 on the kitchen floor). This accounts for the fact that we stop counting the
+    /// paths once they have returned home. If we kept counting them each one would diverge into
+    /// $b^k$ paths over the remaining $k$ steps. We need to apportion the probability mass
+    /// correctly in order to divide by $b^n$ total paths at the end.
+    ///
+    /// The counter is reset to step zero first, so repeated calls are independent. Nothing is
+    /// printed; call [report](PathCountResult::report) on the returned value to see the breakdown.
+    ///
+    /// All counts are held in `u128`s, which can represent $b^n$ for up to 80 steps on the kitchen
+    /// floor, or fewer on tilings with more neighbours. Asking for more steps than this returns
+    /// [PathCountError::Overflow] before any work is done.
+    pub fn calculate(&mut self, steps: u32) -> Result<PathCountResult, PathCountError> {
+        let branching_factor = T::branching_factor() as u128;
+        let total_paths = branching_factor
+            .checked_pow(steps)
+            .ok_or(PathCountError::Overflow)?;
+
+        self.reset();
+
+        let mut returning_paths = 0;
+        let mut returned_paths = 0;
+        for i in 0..steps {
+            self.next();
+            let returned_paths_at_step = *self.cells.borrow().get(&self.home).unwrap();
+
+            returned_paths += returned_paths_at_step * branching_factor.pow(steps - i - 1);
+            returning_paths += returned_paths_at_step;
+        }
+        let returning_at_final_step = *self.cells.borrow().get(&self.home).unwrap();
+        let total_at_final_step = self.cells.borrow().values().sum::<u128>();
+
+        Ok(PathCountResult {
+            steps,
+            branching_factor: T::branching_factor(),
+            returning_at_final_step,
+            returning_paths,
+            total_at_final_step,
+            returned_within: returned_paths,
+            total_paths,
+            probability: (total_paths - returned_paths) as f64 / total_paths as f64,
+        })
+    }
+
+    /// The probability that a walk on the tiling lasts strictly more than `threshold` steps
+    /// before first returning home. This is the
+    /// [probability](PathCountResult::probability) from [calculate](Self::calculate), without any
+    /// of the other detail.
+    ///
+    /// Returns [PathCountError::Overflow] if the path counts would overflow, e.g. if `threshold`
+    /// is over 80 on the kitchen floor.
+    pub fn probability_longer_than(&mut self, threshold: u32) -> Result<f64, PathCountError> {
+        Ok(self.calculate(threshold)?.probability)
+    }
+}
+
+/// An error returned by [GraphPathCounter::calculate] and [enumerate_walks].
+#[derive(Debug, PartialEq, Eq)]
+pub enum PathCountError {
+    /// The number of possible paths, $b^n$, is too large to count in the integer type used: a
+    /// `u128` for `GraphPathCounter`, or a `u64` for `enumerate_walks`.
+    Overflow,
+}
+
+/// The results of running a [GraphPathCounter] for a fixed number of steps.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PathCountResult {
+    /// The number of steps the analysis was run for.
+    pub steps: u32,
+
+    /// The number of neighbours of each tile, $b$. This is 3 on the kitchen floor.
+    pub branching_factor: usize,
+
+    /// The number of distinct paths which first return home on the final step.
+    pub returning_at_final_step: u128,
+
+    /// The number of distinct paths which return home on or before the final step. Each of these
+    /// is counted once, at the point it terminates.
+    pub returning_paths: u128,
+
+    /// The sum of the path counts over every cell after the final step.
+    pub total_at_final_step: u128,
+
+    /// The number of the $b^n$ possible sequences of moves which return home within $n$ steps,
+    /// where $n$ is [steps](Self::steps).
+    pub returned_within: u128,
+
+    /// The total number of possible sequences of moves, $b^n$.
+    pub total_paths: u128,
+
+    /// The probability that a walk is strictly longer than [steps](Self::steps).
+    pub probability: f64,
+}
+
+impl PathCountResult {
+    /// The exact [probability](Self::probability) as a fraction `(numerator, denominator)` in
+    /// lowest terms. The float is the result of dividing these two, so this shows the precise
+    /// rational value without any rounding.
+    pub fn fraction(&self) -> (u128, u128) {
+        let numerator = self.total_paths - self.returned_within;
+        let divisor = gcd(numerator, self.total_paths);
+
+        (numerator / divisor, self.total_paths / divisor)
+    }
+
+    /// Print a breakdown of the path counts to stdout.
+    pub fn report(&self) {
+        println!(
+            "Number of returning paths on the {}th step: {}",
+            self.steps, self.returning_at_final_step
+        );
+        println!(
+            "Number of returning paths within {} steps: {}",
+            self.steps, self.returning_paths
+        );
+        println!("Total paths at final step: {}", self.total_at_final_step);
+        println!(
+            "Total paths: {}",
+            self.total_at_final_step + self.returning_paths - self.returning_at_final_step
+        );
+        println!(
+            "Total paths inc. {}",
+            self.returned_within + self.total_at_final_step - self.returning_at_final_step
+        );
+        println!(
+            "{}^{}: {}",
+            self.branching_factor, self.steps, self.total_paths
+        );
+        let (numerator, denominator) = self.fraction();
+        println!(
+            "p = {} / {} = {} / {} = {:7}",
+            self.total_paths - self.returned_within,
+            self.total_paths,
+            numerator,
+            denominator,
+            self.probability
+        );
+    }
+}
+
+/// The greatest common divisor of `a` and `b`, by Euclid's algorithm.
+fn gcd(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// The most efficient way to calculate the solution to the second part of the question, which
+/// asks about walks of `steps = 20` steps.
+///
+/// Running this we retrieve the final answer of $p = 0.4480326$ in c. 1ms. When `timed` is set,
+/// the time taken is printed after the result.
+///
+/// # Panics
+///
+/// Panics if `steps` is so large that $3^{steps}$ overflows a `u128`.
+pub fn path_counting_on_graph(steps: u32, timed: bool) {
+    let start = std::time::Instant::now();
+    let mut counter = GraphPathCounter::new();
+    let result = counter
+        .calculate(steps)
+        .expect("path counts should fit in a u128");
+    let elapsed = start.elapsed();
+
+    result.report();
+    if timed {
+        println!("took {}µs", elapsed.as_micros());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        antithetic_pair, count_terminated_walks, enumerate_in_chunks, enumerate_walks,
+        multithreaded, prob_longer_than, prob_longer_than_reference_with_rng,
+        prob_longer_than_with_rng, prob_of_longer_walk_antithetic_with_rng,
+        prob_of_longer_walk_from, prob_of_longer_walk_in_the_kitchen_with_rng,
+        prob_of_longer_walk_with_trace, prob_of_longer_walk_with_trace_and_rng, wilson_interval,
+        Decisions, Expectation, Football, FrequencyMap, GraphError, GraphPathCounter, KitchenFloor,
+        MoveSet, PathCountError, RandomWalk, TilingWalk, WalkOutcome,
+    };
+    use rand::Rng;
+    use std::collections::HashMap;
+
+    /// A walker which ignores its random number generator, and instead produces walks whose
+    /// lengths cycle through a fixed script. Walks should start and end at state 0.
+    struct Scripted {
+        curr: u32,
+        lengths: Vec<u32>,
+        next: usize,
+    }
+
+    impl Scripted {
+        fn new(lengths: &[u32]) -> Self {
+            Self {
+                curr: 0,
+                lengths: lengths.to_vec(),
+                next: 0,
+            }
+        }
+    }
+
+    impl RandomWalk for Scripted {
+        type State = u32;
+
+        fn make_move<R: Rng>(&mut self, _rng: &mut R) {
+            if self.curr == 0 {
+                self.curr = self.lengths[self.next] - 1;
+                self.next = (self.next + 1) % self.lengths.len();
+            } else {
+                self.curr -= 1;
+            }
+        }
+
+        fn get_state(&self) -> Self::State {
+            self.curr
+        }
+
+        fn set_state(&mut self, state: Self::State) {
+            self.curr = state;
+        }
+    }
+
+    #[test]
+    fn enumerated_walks_match_path_counts() {
+        let mut kitchen_floor = KitchenFloor::new();
+        for steps in [0, 1, 2, 8, 11] {
+            let result = GraphPathCounter::new().calculate(steps).unwrap();
+            assert_eq!(
+                enumerate_walks(&mut kitchen_floor, steps),
+                Ok((result.returned_within as u64, result.total_paths as u64))
+            );
+        }
+
+        // The count is the same from any home, and starts from the kitchen floor's own.
+        let mut elsewhere = KitchenFloor::with_home((2, 0));
+        assert_eq!(
+            enumerate_walks(&mut elsewhere, 8),
+            enumerate_walks(&mut kitchen_floor, 8)
+        );
+
+        assert_eq!(
+            enumerate_walks(&mut kitchen_floor, 41),
+            Err(PathCountError::Overflow)
+        );
+    }
+
+    #[test]
+    fn labelled_move_directions() {
+        use crate::HexDir::*;
+
+        // Type A hexagons, such as home, have moves (NW, SW, E), and type B have (W, NE, SE).
+        let a: Vec<_> = (0..3)
+            .map(|idx| KitchenFloor::move_direction(true, idx))
+            .collect();
+        let b: Vec<_> = (0..3)
+            .map(|idx| KitchenFloor::move_direction(false, idx))
+            .collect();
+        assert_eq!(a, [NW, SW, E]);
+        assert_eq!(b, [NE, SE, W]);
+
+        // Each labelled move agrees with `move_from_idx`, from hexagons of both types.
+        for coord in [(0, 0), (2, 0), (-4, 1), (5, 3)] {
+            let hex_type = KitchenFloor::coord_hex_type(coord);
+            for idx in 0..3 {
+                let mut kitchen_floor = KitchenFloor::with_home(coord);
+                kitchen_floor.move_from_idx(idx);
+                let dir = KitchenFloor::move_direction(hex_type, idx);
+                assert_eq!(
+                    KitchenFloor::neighbour_in_direction(coord, dir),
+                    Some(kitchen_floor.get_state())
+                );
+            }
+
+            // The other three directions lead onto black hexagons.
+            let blocked = [E, NE, NW, W, SW, SE]
+                .into_iter()
+                .filter(|dir| KitchenFloor::neighbour_in_direction(coord, *dir).is_none())
+                .count();
+            assert_eq!(blocked, 3);
+        }
+
+        assert_eq!(KitchenFloor::neighbour_in_direction((1, 0), E), None);
+        let path: Vec<String> = [E, NW, SW].iter().map(|dir| dir.to_string()).collect();
+        assert_eq!(path.join(", "), "E, NW, SW");
+    }
+
+    #[test]
+    fn unknown_football_states_are_errors() {
+        let mut rng = rand::thread_rng();
+        let mut football = Football::new();
+
+        assert_eq!(football.try_set_state(42), Err(GraphError::UnknownNode(42)));
+        assert_eq!(football.get_state(), 1);
+        assert_eq!(football.try_set_state(7), Ok(()));
+        assert_eq!(football.get_state(), 7);
+
+        assert_eq!(
+            football.try_walk(42, 1, &mut rng),
+            Err(GraphError::UnknownNode(42))
+        );
+        assert_eq!(
+            football.try_walk(1, 0, &mut rng),
+            Err(GraphError::UnknownNode(0))
+        );
+        assert!(football.try_walk(1, 1, &mut rng).unwrap() >= 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Andy is on 42, which is not a node of the graph")]
+    fn moving_from_unknown_football_state_panics_clearly() {
+        let mut football = Football::new();
+        football.set_state(42);
+        football.make_move(&mut rand::thread_rng());
+    }
+
+    #[test]
+    fn walk_iterator_matches_recorded_path() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let mut football = Football::new();
+        let path = football.walk_recording_path(1, 1, &mut StdRng::seed_from_u64(5));
+
+        let mut rng = StdRng::seed_from_u64(5);
+        football.set_state(1);
+        let mut stepped = vec![1];
+        stepped.extend(football.iter(&mut rng).take_while(|state| *state != 1));
+        stepped.push(1);
+        assert_eq!(stepped, path);
+
+        // The iterator leaves the walker where it stopped, so it can be picked up again.
+        let mut kitchen_floor = KitchenFloor::new();
+        let states: Vec<_> = kitchen_floor.iter(&mut rng).take(3).collect();
+        assert_eq!(kitchen_floor.get_state(), states[2]);
+        assert!(KitchenFloor::coord_neighbours((0, 0)).contains(&states[0]));
+    }
+
+    #[test]
+    fn football_neighbours() {
+        let football = Football::new();
+
+        assert_eq!(football.neighbours(1), Some([2, 6, 5]));
+        assert_eq!(football.neighbours(0), None);
+        assert_eq!(football.neighbours(21), None);
+
+        // Adjacency is symmetric.
+        for node in 1..=20 {
+            for neighbour in football.neighbours(node).unwrap() {
+                assert!(football.neighbours(neighbour).unwrap().contains(&node));
+            }
+        }
+    }
+
+    #[test]
+    fn walks_starting_on_the_target() {
+        let mut rng = rand::thread_rng();
+        let mut walker = Scripted::new(&[5]);
+
+        // Starting on the target is zero steps when allowed, without using up any of the script.
+        assert_eq!(walker.walk_allow_zero(0, 0, &mut rng), 0);
+        assert_eq!(walker.get_state(), 0);
+        assert_eq!(walker.walk(0, 0, &mut rng), 5);
+
+        // Otherwise the two agree.
+        assert_eq!(walker.walk_allow_zero(3, 0, &mut rng), 3);
+        assert_eq!(walker.walk(3, 0, &mut rng), 3);
+
+        // On the football, a walk from home back to home is at least two steps.
+        let mut football = Football::new();
+        assert_eq!(football.walk_allow_zero(1, 1, &mut rng), 0);
+        assert!(football.walk(1, 1, &mut rng) >= 2);
+    }
+
+    #[test]
+    fn kitchen_floor_traversal() {
+        #[rustfmt::skip]
+    assert_eq!(KitchenFloor::coord_neighbours((0, 0)), [(1, 1), (0, -1), (-1, 0)]);
+
+        #[rustfmt::skip]
+    assert_eq!(KitchenFloor::coord_neighbours((-1, 1)), [(0, 2), (-1, 0), (-2, 1)]);
+
+        #[rustfmt::skip]
+    assert_eq!(KitchenFloor::coord_neighbours((-1, 0)), [(-1, 1), (0, 0), (-2, -1)]);
+
+        #[rustfmt::skip]
+    assert_eq!(KitchenFloor::coord_neighbours((-2, -1)), [(-1, 0), (-2, -2), (-3, -1)]);
+    }
+
+    #[test]
+    fn path_counting_gives_puzzle_answer() {
+        let result = GraphPathCounter::new().calculate(20).unwrap();
+
+        assert_eq!(result.total_paths, 3_u128.pow(20));
+        assert_eq!(format!("{:.7}", result.probability), "0.4480326");
+    }
+
+    #[test]
+    fn path_counting_matches_monte_carlo_beyond_usize_range() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let result = GraphPathCounter::new().calculate(30).unwrap();
+        assert_eq!(result.total_paths, 3_u128.pow(30));
+
+        let mut kitchen_floor = KitchenFloor::new();
+        let mut rng = StdRng::seed_from_u64(754);
+        let runs = 200_000;
+        let longer = (0..runs)
+            .filter(|_| {
+                kitchen_floor
+                    .walk_until_limit((0, 0), (0, 0), &mut rng, 30)
+                    .reached()
+                    .is_none()
+            })
+            .count();
+
+        let estimate = longer as f64 / runs as f64;
+        assert!((estimate - result.probability).abs() < 0.005);
+    }
+
+    #[test]
+    fn path_counting_refuses_to_overflow() {
+        assert_eq!(
+            GraphPathCounter::new().calculate(81),
+            Err(PathCountError::Overflow)
+        );
+    }
+
+    #[test]
+    fn decisions_count_in_base_three() {
+        let firsts: Vec<[usize; 3]> = Decisions::new()
+            .take(9)
+            .map(|d| [d[0], d[1], d[2]])
+            .collect();
+
+        #[rustfmt::skip]
+        assert_eq!(firsts, [
+            [0, 0, 0], [1, 0, 0], [2, 0, 0],
+            [0, 1, 0], [1, 1, 0], [2, 1, 0],
+            [0, 2, 0], [1, 2, 0], [2, 2, 0],
+        ]);
+    }
+
+    #[test]
+    fn decisions_stop_after_all_twos() {
+        let mut decisions = Decisions::new();
+        decisions.curr = [2; 20];
+
+        assert_eq!(decisions.next(), Some([2; 20]));
+        assert_eq!(decisions.next(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "total number of paths should fit in a usize")]
+    fn decisions_reject_too_many_paths() {
+        Decisions::with_params(3, 41);
+    }
+
+    #[test]
+    fn decisions_with_params_cover_every_path() {
+        let decisions = Decisions::with_params(4, 2);
+        assert_eq!(decisions.total(), 16);
+
+        let all: Vec<Vec<usize>> = decisions.collect();
+        assert_eq!(all.len(), 16);
+        assert_eq!(all[1], [1, 0]);
+        assert_eq!(all[4], [0, 1]);
+        assert_eq!(all[15], [3, 3]);
+
+        assert_eq!(Decisions::new().total(), 3_usize.pow(20));
+    }
+
+    #[test]
+    fn decisions_jump_to_index() {
+        let mut decisions = Decisions::with_params(3, 4);
+        decisions.set_index(3 + 2 * 9 + 27);
+
+        assert_eq!(decisions.curr(), &[0, 1, 2, 1]);
+    }
+
+    #[test]
+    fn parallel_and_serial_enumeration_agree() {
+        let steps = 8;
+        let decisions = Decisions::with_params(3, steps);
+        let total = decisions.total();
+
+        let serial = count_terminated_walks(decisions.clone(), total);
+        let parallel = enumerate_in_chunks(decisions, 3);
+
+        assert_eq!(parallel, (serial, total as u64));
+
+        let exact = GraphPathCounter::new().calculate(steps as u32).unwrap();
+        assert_eq!(serial as u128, exact.returned_within);
+    }
+
+    #[test]
+    fn multithreaded_sums_over_threads() {
+        let (longer, total) = multithreaded(3, 1_000, None);
+
+        assert_eq!(total, 3_000);
+        assert!(longer <= total);
+    }
+
+    #[test]
+    fn seeded_multithreaded_is_reproducible() {
+        let first = multithreaded(2, 10_000, Some(42));
+        let second = multithreaded(2, 10_000, Some(42));
+
+        assert_eq!(first, second);
+        assert_ne!(first, multithreaded(2, 10_000, Some(43)));
+    }
+
+    #[test]
+    fn streaming_moments_match_frequency_map() {
+        let mut exp = Expectation::new(Scripted::new(&[2, 4, 4, 4, 5, 5, 7, 9]));
+        let mean = exp.calculate(0, 0, 8);
+
+        assert_eq!(mean, 5.0);
+        assert_eq!(exp.mean(), exp.finish() as f64);
+        assert!((exp.variance() - 32.0 / 7.0).abs() < 1e-12);
+
+        let mut streaming = Expectation::new(Scripted::new(&[2, 4, 4, 4, 5, 5, 7, 9]));
+        assert_eq!(streaming.calculate_streaming(0, 0, 8), 5.0);
+        assert!(streaming.freq_map.is_empty());
+        assert_eq!(streaming.variance(), exp.variance());
+        assert_eq!(streaming.std_dev(), exp.std_dev());
+    }
+
+    #[test]
+    #[should_panic(expected = "can't fill the frequency map after streaming runs")]
+    fn streamed_runs_cant_be_mixed_with_frequency_map() {
+        let mut exp = Expectation::new(Scripted::new(&[2, 4]));
+        exp.calculate_streaming(0, 0, 2);
+        exp.calculate(0, 0, 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "can't stream runs once the frequency map has been filled")]
+    fn frequency_map_runs_cant_be_mixed_with_streaming() {
+        let mut exp = Expectation::new(Scripted::new(&[2, 4]));
+        exp.calculate(0, 0, 2);
+        exp.calculate_streaming(0, 0, 4);
+    }
+
+    #[test]
+    fn wilson_interval_matches_newcombe() {
+        // Worked examples from Newcombe (1998), "Two-sided confidence intervals for the single
+        // proportion: comparison of seven methods", to four decimal places.
+        let round = |(lo, hi): (f64, f64)| {
+            (
+                (lo * 10_000.0).round() / 10_000.0,
+                (hi * 10_000.0).round() / 10_000.0,
+            )
+        };
+
+        assert_eq!(round(wilson_interval(81, 263, 1.96)), (0.2553, 0.3662));
+        assert_eq!(round(wilson_interval(15, 148, 1.96)), (0.0624, 0.1605));
+        assert_eq!(round(wilson_interval(0, 20, 1.96)), (0.0, 0.1611));
+        assert_eq!(round(wilson_interval(1, 29, 1.96)), (0.0061, 0.1718));
+    }
+
+    #[test]
+    fn merging_expectations_combines_counts() {
+        let mut left = Expectation::new(Scripted::new(&[1]));
+        left.freq_map = FrequencyMap::from(HashMap::from([(2, 3), (4, 1)]));
+        left.cnt = 4;
+
+        let mut right = Expectation::new(Scripted::new(&[1]));
+        right.freq_map = FrequencyMap::from(HashMap::from([(4, 2), (10, 2)]));
+        right.cnt = 4;
+
+        left.merge(right);
+
+        assert_eq!(
+            left.freq_map,
+            FrequencyMap::from(HashMap::from([(2, 3), (4, 3), (10, 2)]))
+        );
+        assert_eq!(left.cnt, 8);
+        assert_eq!(left.finish(), 38.0 / 8.0);
+    }
+
+    #[test]
+    fn merging_expectations_combines_moments() {
+        let mut whole = Expectation::new(Scripted::new(&[2, 4, 4, 4, 5, 5, 7, 9]));
+        whole.calculate(0, 0, 8);
+
+        let mut left = Expectation::new(Scripted::new(&[2, 4, 4]));
+        left.calculate(0, 0, 3);
+        let mut right = Expectation::new(Scripted::new(&[4, 5, 5, 7, 9]));
+        right.calculate(0, 0, 5);
+        left.merge(right);
+
+        assert_eq!(left.freq_map, whole.freq_map);
+        assert!((left.mean() - whole.mean()).abs() < 1e-12);
+        assert!((left.variance() - whole.variance()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn football_expected_return_time_is_twenty() {
+        let football = Football::new();
+
+        assert!((football.expected_return_time(1) - 20.0).abs() < 1e-9);
+    }
+
+    /// Whether two graphs, given as adjacency lists, are isomorphic. A simple backtracking search,
+    /// which is plenty fast enough for small, highly symmetric graphs.
+    fn is_isomorphic(a: &HashMap<i32, [i32; 3]>, b: &HashMap<i32, [i32; 3]>) -> bool {
+        fn extend(
+            order: &[i32],
+            a: &HashMap<i32, [i32; 3]>,
+            b: &HashMap<i32, [i32; 3]>,
+            mapping: &mut HashMap<i32, i32>,
+        ) -> bool {
+            let Some(&next) = order.get(mapping.len()) else {
+                return true;
+            };
+
+            let mut candidates: Vec<i32> = b.keys().copied().collect();
+            candidates.sort();
+            for candidate in candidates {
+                if mapping.values().any(|v| *v == candidate) {
+                    continue;
+                }
+                let consistent = mapping
+                    .iter()
+                    .all(|(u, v)| a[&next].contains(u) == b[&candidate].contains(v));
+                if !consistent {
+                    continue;
+                }
+
+                mapping.insert(next, candidate);
+                if extend(order, a, b, mapping) {
+                    return true;
+                }
+                mapping.remove(&next);
+            }
+
+            false
+        }
+
+        if a.len() != b.len() {
+            return false;
+        }
+
+        // Map the nodes of `a` in breadth-first order, so each one is constrained by an
+        // already-mapped neighbour.
+        let mut order = vec![*a.keys().min().unwrap()];
+        let mut i = 0;
+        while i < order.len() {
+            let mut neighbours = a[&order[i]];
+            neighbours.sort();
+            for n in neighbours {
+                if !order.contains(&n) {
+                    order.push(n);
+                }
+            }
+            i += 1;
+        }
+
+        extend(&order, a, b, &mut HashMap::new())
+    }
+
+    #[test]
+    fn generated_football_is_isomorphic_to_hardcoded() {
+        let generated = Football::generated();
+        let hardcoded = Football::new();
+
+        assert!(is_isomorphic(
+            &generated.transitions,
+            &hardcoded.transitions
+        ));
+        assert!((generated.expected_return_time(1) - 20.0).abs() < 1e-9);
+
+        // Sanity check the isomorphism test itself, by breaking the symmetry of one graph with a
+        // pair of edge swaps that keep it cubic.
+        let mut broken = hardcoded.transitions.clone();
+        broken.insert(1, [2, 6, 3]);
+        broken.insert(2, [1, 7, 5]);
+        broken.insert(3, [4, 8, 1]);
+        broken.insert(5, [4, 10, 2]);
+        assert!(!is_isomorphic(&generated.transitions, &broken));
+    }
+
+    #[test]
+    fn corrupted_football_is_rejected() {
+        let valid = Football::new().transitions;
+        assert!(Football::from_transitions(valid.clone()).is_ok());
+
+        // A transposed digit: node 6 claims 21 rather than 12 as a neighbour.
+        let mut corrupted = valid.clone();
+        corrupted.insert(6, [1, 11, 21]);
+        assert_eq!(
+            Football::from_transitions(corrupted).err(),
+            Some(GraphError::UnknownNode(21))
+        );
+
+        let mut corrupted = valid.clone();
+        corrupted.insert(7, [2, 12, 14]);
+        assert_eq!(
+            Football::from_transitions(corrupted).err(),
+            Some(GraphError::NotMutual(7, 14))
+        );
+
+        let mut corrupted = valid.clone();
+        corrupted.insert(7, [2, 12, 12]);
+        assert_eq!(
+            Football::from_transitions(corrupted).err(),
+            Some(GraphError::RepeatedNeighbour(7, 12))
+        );
+
+        let mut corrupted = valid;
+        corrupted.remove(&20);
+        assert_eq!(
+            Football::from_transitions(corrupted).err(),
+            Some(GraphError::WrongNodeCount(19))
+        );
+    }
+
+    #[test]
+    fn weighted_football_moves_follow_weights() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let mut football = Football::new();
+        football.set_weight(1, 2, 2.0);
+        football.set_weight(1, 6, 0.0);
+
+        let mut rng = StdRng::seed_from_u64(767);
+        let runs = 100_000;
+        let mut to_two = 0;
+        for _ in 0..runs {
+            football.set_state(1);
+            football.make_move(&mut rng);
+            assert_ne!(football.get_state(), 6);
+            if football.get_state() == 2 {
+                to_two += 1;
+            }
+        }
+
+        assert!((to_two as f64 / runs as f64 - 2.0 / 3.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn weighted_football_solvers_follow_weights() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let mut football = Football::new();
+        football.set_weight(1, 2, 3.0);
+        football.set_weight(2, 1, 2.0);
+        football.set_weight(7, 12, 0.5);
+
+        let dist = football.return_time_distribution(2);
+        let (to_two, back_from_two) = (3.0 / 5.0, 2.0 / 4.0);
+        let back_from_others = 2.0 / 5.0 * (1.0 / 3.0);
+        assert!((dist[1] - (to_two * back_from_two + back_from_others)).abs() < 1e-12);
+
+        let exact = football.expected_return_time(1);
+        let mut rng = StdRng::seed_from_u64(807);
+        let runs = 200_000;
+        let total: u64 = (0..runs)
+            .map(|_| football.walk(1, 1, &mut rng) as u64)
+            .sum();
+        assert!((total as f64 / runs as f64 - exact).abs() < 0.2);
+        assert!((exact - 20.0).abs() > 1.0);
+    }
+
+    #[test]
+    fn recorded_football_path_is_connected() {
+        let mut football = Football::new();
+        let mut rng = rand::thread_rng();
+
+        for _ in 0..100 {
+            let path = football.walk_recording_path(1, 1, &mut rng);
+
+            assert!(path.len() >= 3);
+            assert_eq!(path.first(), Some(&1));
+            assert_eq!(path.last(), Some(&1));
+            assert!(!path[1..path.len() - 1].contains(&1));
+            for pair in path.windows(2) {
+                assert!(football.transitions[&pair[0]].contains(&pair[1]));
+            }
+        }
+    }
+
+    #[test]
+    fn frequency_map_statistics() {
+        let freq_map =
+            FrequencyMap::from(HashMap::from([(2, 4), (4, 3), (6, 1), (10, 1), (30, 1)]));
+
+        assert_eq!(freq_map.total_count(), 10);
+        assert_eq!(freq_map.mean(), 6.6);
+        assert_eq!(freq_map.mode(), Some(2));
+        assert_eq!(freq_map.median(), 4);
+        assert_eq!(freq_map.percentile(0.0), 2);
+        assert_eq!(freq_map.percentile(0.9), 10);
+        assert_eq!(freq_map.percentile(1.0), 30);
+        assert!((freq_map.entropy() - 2.046439).abs() < 1e-6);
+
+        // Four equally common lengths carry two bits, and ties for the mode go to the shortest.
+        let uniform = FrequencyMap::from(HashMap::from([(3, 5), (1, 5), (4, 5), (8, 5)]));
+        assert_eq!(uniform.entropy(), 2.0);
+        assert_eq!(uniform.mode(), Some(1));
+
+        let empty = FrequencyMap::new();
+        assert_eq!(empty.total_count(), 0);
+        assert_eq!(empty.mean(), 0.0);
+        assert_eq!(empty.mode(), None);
+        assert_eq!(empty.entropy(), 0.0);
+    }
+
+    #[test]
+    fn traced_estimate_converges_to_overall_estimate() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let trace =
+            prob_of_longer_walk_with_trace_and_rng(10_050, 1000, &mut StdRng::seed_from_u64(3));
+        let (longer, runs) = prob_of_longer_walk_in_the_kitchen_with_rng(
+            10_050,
+            false,
+            &mut StdRng::seed_from_u64(3),
+        );
+
+        // One snapshot per thousand runs, and one more for the final fifty.
+        assert_eq!(trace.len(), 11);
+        assert_eq!(trace[0].0, 1000);
+        assert_eq!(trace[10], (runs, longer as f64 / runs as f64));
+        assert!(trace.windows(2).all(|pair| pair[0].0 < pair[1].0));
+
+        assert!(prob_of_longer_walk_with_trace(0, 10).is_empty());
+    }
+
+    #[test]
+    fn entropy_of_walk_lengths() {
+        let mut exp = Expectation::new(Football::new());
+        assert_eq!(exp.entropy(), 0.0);
+
+        // n equally common walk lengths carry log2(n) bits.
+        for n in [1, 2, 5, 8, 20] {
+            exp.freq_map = FrequencyMap::from(
+                (1..=n)
+                    .map(|length| (2 * length, 3))
+                    .collect::<HashMap<_, _>>(),
+            );
+            assert!((exp.entropy() - (n as f64).log2()).abs() < 1e-12);
+        }
+
+        // Despite its fat tail, the kitchen floor has less entropy than the football (about 3.9
+        // bits against 5), as so many of its walks are over in two steps.
+        let mut football = Expectation::new(Football::new());
+        football.calculate(1, 1, 10_000);
+        let mut kitchen = Expectation::new(KitchenFloor::new());
+        kitchen.calculate_with_limit((0, 0), (0, 0), 10_000, 1000);
+        assert!(kitchen.entropy() < football.entropy());
+    }
+
+    #[test]
+    fn bucketed_tail_keeps_totals() {
+        let lengths = [(2, 5), (4, 3), (8, 2), (12, 1), (15, 2), (40, 1)];
+        let mut whole = FrequencyMap::new();
+        let mut bucketed = FrequencyMap::with_tail_threshold(10);
+        for (length, count) in lengths {
+            whole.add(length, count);
+            bucketed.add(length, count);
+        }
+
+        assert_eq!(bucketed.total_count(), whole.total_count());
+        assert_eq!(bucketed.tail_count(), 4);
+        assert_eq!(bucketed.sorted(), [(2, 5), (4, 3), (8, 2)]);
+        assert_eq!(bucketed.sorted()[..], whole.sorted()[..3]);
+        assert_eq!(bucketed.mean(), whole.mean());
+        assert_eq!(bucketed.median(), whole.median());
+
+        // Only the tail is collapsed onto its threshold.
+        assert_eq!(whole.percentile(1.0), 40);
+        assert_eq!(bucketed.percentile(1.0), 10);
+        assert!(bucketed.entropy() < whole.entropy());
+
+        // Bucketing after the fact, or merging into a map without a tail, gives the same.
+        let mut later = whole.clone();
+        later.bucket_tail(10);
+        assert_eq!(later, bucketed);
+        let mut merged = FrequencyMap::new();
+        merged.merge(bucketed.clone());
+        assert_eq!(merged, bucketed);
+
+        // Seeded walks give the same mean and short lengths, with or without a tail bucket.
+        use rand::{rngs::StdRng, SeedableRng};
+        let run = |freq_map| {
+            let mut exp = Expectation::new(Football::new());
+            exp.freq_map = freq_map;
+            let mean = exp.calculate_with_rng(1, 1, 1000, &mut StdRng::seed_from_u64(9));
+            (mean, exp)
+        };
+        let (mean, exp) = run(FrequencyMap::new());
+        let (bucketed_mean, bucketed_exp) = run(FrequencyMap::with_tail_threshold(30));
+        assert_eq!(bucketed_mean, mean);
+        assert_eq!(bucketed_exp.freq_map.total_count(), 1000);
+        let short: Vec<_> = exp
+            .freq_map
+            .sorted()
+            .into_iter()
+            .filter(|(l, _)| *l < 30)
+            .collect();
+        assert_eq!(bucketed_exp.freq_map.sorted(), short);
+
+        let mut csv = Vec::new();
+        bucketed_exp.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let tail = format!(">=30,{}\n", bucketed_exp.freq_map.tail_count());
+        assert!(csv.ends_with(&tail));
+    }
+
+    #[test]
+    fn antithetic_estimate() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let (longer, total) =
+            prob_of_longer_walk_antithetic_with_rng(100_000, &mut StdRng::seed_from_u64(1));
+        assert_eq!(total, 200_000);
+        assert!((longer as f64 / total as f64 - 0.4480326).abs() < 0.005);
+
+        // The pairs are negatively correlated, which is where the reduction in variance comes
+        // from: both walks are long less often than two independent walks would be.
+        let mut rng = StdRng::seed_from_u64(2);
+        let mut kitchen_floor = KitchenFloor::new();
+        let pairs = 50_000;
+        let (mut longer, mut both) = (0, 0);
+        for _ in 0..pairs {
+            let (walk, mirror) = antithetic_pair(&mut kitchen_floor, &mut rng);
+            longer += walk as u32 + mirror as u32;
+            both += (walk && mirror) as u32;
+        }
+        let p = longer as f64 / (2 * pairs) as f64;
+        let correlation = (both as f64 / pairs as f64 - p * p) / (p * (1.0 - p));
+        assert!(correlation < -0.1, "correlation was {}", correlation);
+    }
+
+    #[test]
+    fn walks_from_another_home() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        // (0, 0), (3, 0) and (1, 2) are all type A hexagons.
+        let at_origin = GraphPathCounter::<KitchenFloor>::with_home((0, 0))
+            .probability_longer_than(20)
+            .unwrap();
+        for home in [(3, 0), (1, 2)] {
+            assert!(KitchenFloor::coord_hex_type(home));
+            let mut counter = GraphPathCounter::<KitchenFloor>::with_home(home);
+            assert_eq!(counter.extent(), (home, home));
+            assert_eq!(counter.probability_longer_than(20).unwrap(), at_origin);
+        }
+
+        // With the moves numbered the same way, a seeded walk takes the same shape from either.
+        let from_origin = prob_of_longer_walk_from(
+            KitchenFloor::new(),
+            1000,
+            false,
+            &mut StdRng::seed_from_u64(4),
+        );
+        let from_elsewhere = prob_of_longer_walk_from(
+            KitchenFloor::with_home((3, 0)),
+            1000,
+            false,
+            &mut StdRng::seed_from_u64(4),
+        );
+        assert_eq!(from_origin, from_elsewhere);
+    }
+
+    #[test]
+    #[should_panic(expected = "home should be a white hexagon")]
+    fn black_home_panics() {
+        KitchenFloor::with_home((1, 0));
+    }
+
+    #[test]
+    fn probability_longer_than_twenty() {
+        let mut counter = GraphPathCounter::new();
+
+        assert_eq!(
+            format!("{:.7}", counter.probability_longer_than(20).unwrap()),
+            "0.4480326"
+        );
+        // As in the puzzle statement, a third of walks are back home after two steps.
+        assert_eq!(counter.probability_longer_than(2), Ok(2.0 / 3.0));
+        // Running again gives the same answer, rather than carrying on from the previous run.
+        assert_eq!(
+            format!("{:.7}", counter.probability_longer_than(20).unwrap()),
+            "0.4480326"
+        );
+        assert_eq!(
+            counter.probability_longer_than(81),
+            Err(PathCountError::Overflow)
+        );
+    }
+
+    #[test]
+    fn frontier_extent_after_three_steps() {
+        let mut counter = GraphPathCounter::new();
+        assert_eq!(counter.extent(), ((0, 0), (0, 0)));
+        assert_eq!(counter.support_size(), 1);
+
+        for _ in 0..3 {
+            counter.next();
+        }
+
+        assert_eq!(counter.extent(), ((-3, -3), (3, 3)));
+        assert_eq!(counter.support_size(), 12);
+    }
+
+    #[test]
+    fn path_counter_prunes_unreachable_cells() {
+        let mut counter = GraphPathCounter::new();
+
+        counter.next();
+        assert_eq!(counter.cells.borrow().len(), 4);
+
+        counter.next();
+        assert_eq!(counter.cells.borrow().len(), 7);
+
+        // The origin has a zero count after an odd number of steps, but is always kept.
+        counter.next();
+        assert_eq!(counter.cells.borrow().len(), 13);
+        assert_eq!(counter.cells.borrow()[&(0, 0)], 0);
+        assert_eq!(counter.support_size(), 12);
+    }
 
-    let grand_total: (u64, u64) = results
-        .iter()
-        .fold((0, 0), |acc, e| (acc.0 + e.0, acc.1 + e.1));
-    println!("grand total: {:?}", grand_total);
-    println!(
-        "probability of a longer than 20 walk: {}",
-        grand_total.0 as f64 / grand_total.1 as f64
-    );
-}
+    #[test]
+    fn render_out_and_back_walk() {
+        // Out to the north-west and straight back again.
+        let rendered = KitchenFloor::render_path(&[(0, 0), (1, 1), (0, 0)]);
 
-/// A new approach to part 2. Enumerating every possible walk.
-///
-/// Our montecarlo approach doesn't seem to be converging on the correct answer fast enough for us
-/// to believe in the 7 decimal places we need to get this right. However, we are only looking for
-/// the number of terminating walks of less than 20 steps. We have a choice of three directions at
-/// each step, so perhaps we can just enumerate every one of the $2^{30}$ = c. 3.5 billion possible
-/// paths and get a precise answer.
-///
-/// Running this function (it's only single threaded so takes a few minutes - it could be made faster by
-/// splitting the enumerable range across a few CPUs, as we have done elsewhere) we get:
-///
-/// $$
-/// P(\text{random walk is longer than 20 steps}) = 0.4480326 \text{ (7 s.f.)}
-/// $$
-///
-/// This figure closely matches the answer we were getting stochastically, so presume we have got
-/// everything right.
-pub fn enumerate_every_walk() {
-    let max: usize = 3_usize.pow(20) - 1;
-    let progress_unit = max / 20;
-    let mut progress_cnt = 0;
-    let mut progress = 0;
+        #[rustfmt::skip]
+        assert_eq!(rendered, [
+            "     · ·",
+            "  · B   ·",
+            " ·   H ·",
+            "  · ·",
+            "",
+        ].join("\n"));
+    }
 
-    // Count the walks which terminate in our first 20 steps.
-    let mut terminated_cnt: usize = 0;
+    #[test]
+    fn football_dot_lists_each_edge_once() {
+        let dot = Football::new().to_dot();
 
-    let mut kitchen_floor = KitchenFloor::new();
-    let mut decisions = Decisions::new();
+        assert!(dot.starts_with("graph football {\n"));
+        assert!(dot.ends_with("}\n"));
+        assert_eq!(dot.lines().filter(|l| l.contains("--")).count(), 30);
+        assert!(dot.contains("    1 -- 2;\n"));
+        assert!(!dot.contains("    2 -- 1;\n"));
+    }
 
-    for i in 0..max {
-        decisions.inc();
-        kitchen_floor.set_state((0, 0));
+    #[test]
+    fn football_return_time_is_independent_of_home() {
+        for home in [1, 7, 20] {
+            let football = Football::with_home(home);
 
-        for dec in decisions.curr() {
-            kitchen_floor.move_from_idx(*dec);
-            if kitchen_floor.get_state() == (0, 0) {
-                terminated_cnt += 1;
-                break;
-            }
+            assert_eq!(football.home(), home);
+            assert_eq!(football.get_state(), home);
+            assert!((football.expected_return_time(football.home()) - 20.0).abs() < 1e-9);
         }
 
-        progress_cnt += 1;
+        let dot = Football::with_home(7).to_dot();
+        assert!(dot.contains("    7 [style=filled, fillcolor=gold];\n"));
+    }
 
-        if progress_cnt == progress_unit {
-            progress += 1;
-            println!(
-                "{:2}% complete, {} runs",
-                progress as f32 * 5 as f32 as f32,
-                i,
-            );
-            progress_cnt = 0;
+    #[test]
+    fn kitchen_floor_black_hexagons() {
+        for home in [(0, 0), (1, 1), (-1, 0), (5, -3)] {
+            assert!(!KitchenFloor::is_black(home));
+
+            let whites = KitchenFloor::coord_neighbours(home);
+            let blacks = KitchenFloor::black_neighbours(home);
+            assert_eq!(blacks.len(), 3);
+            for black in &blacks {
+                assert!(KitchenFloor::is_black(*black));
+                assert!(!whites.contains(black));
+                // A black hexagon is surrounded by white ones.
+                assert!(KitchenFloor::black_neighbours(*black).is_empty());
+            }
         }
+
+        #[rustfmt::skip]
+        assert_eq!(KitchenFloor::black_neighbours((0, 0)), vec![(0, 1), (-1, -1), (1, 0)]);
     }
 
-    println!("terminated walks (<= 20 steps): {}", terminated_cnt);
-    println!(
-        "non-terminated walks (> 20 steps): {}",
-        max - terminated_cnt
-    );
-    println!(
-        "probability > 20: {:10}",
-        (max as f64 - terminated_cnt as f64) / max as f64
-    );
-}
+    #[test]
+    fn expectation_converges_on_football() {
+        let mut exp = Expectation::new(Football::new());
+        let (mean, runs) = exp.calculate_until_converged(1, 1, 0.05, 10_000);
 
-/// A helper struct to assist with iterating through the possible choices of path.
-///
-/// We are essentially counting in base 3. For simplicity, we're using an array of 20 `usize`
-/// integers.
-///
-/// To be cleaner, this should probably implement `Iterator` from the standard library.
-pub struct Decisions {
-    curr: [usize; 20],
-}
+        assert!((mean - 20.0).abs() < 1.0, "{mean}");
+        assert!(runs >= 20_000);
+        assert_eq!(runs % 10_000, 0);
+        assert_eq!(runs, exp.cnt);
+    }
 
-impl Decisions {
-    /// Create a new decision iterator.
-    pub fn new() -> Self {
-        Self { curr: [0; 20] }
+    #[test]
+    #[should_panic(expected = "should have a positive window and tolerance")]
+    fn convergence_needs_a_window() {
+        Expectation::new(Football::new()).calculate_until_converged(1, 1, 0.05, 0);
     }
 
-    /// Return the current value of the array representation of our path.
-    pub fn curr(&self) -> &[usize; 20] {
-        &self.curr
+    #[test]
+    #[should_panic(expected = "should have a positive window and tolerance")]
+    fn convergence_needs_a_tolerance() {
+        Expectation::new(Football::new()).calculate_until_converged(1, 1, 0.0, 10_000);
     }
 
-    /// Increment to the next path.
-    pub fn inc(&mut self) {
-        for idx in 0..20 {
-            if self.curr[idx] == 2 {
-                self.curr[idx] = 0;
-            } else {
-                self.curr[idx] += 1;
-                // As soon as we increment something, rather than reseting, we bail.
-                return;
+    #[test]
+    fn percentiles_of_frequency_map() {
+        let mut exp = Expectation::new(Scripted::new(&[1]));
+        // Ten walks: 2, 2, 2, 2, 4, 4, 4, 6, 10, 30.
+        exp.freq_map =
+            FrequencyMap::from(HashMap::from([(2, 4), (4, 3), (6, 1), (10, 1), (30, 1)]));
+        exp.cnt = 10;
+
+        assert_eq!(exp.percentile(0.0), 2);
+        assert_eq!(exp.percentile(0.4), 2);
+        assert_eq!(exp.percentile(0.41), 4);
+        assert_eq!(exp.median(), 4);
+        assert_eq!(exp.percentile(0.8), 6);
+        assert_eq!(exp.percentile(0.9), 10);
+        assert_eq!(exp.percentile(0.95), 30);
+        assert_eq!(exp.percentile(1.0), 30);
+    }
+
+    #[test]
+    #[should_panic]
+    fn percentile_of_empty_frequency_map() {
+        Expectation::new(Scripted::new(&[1])).median();
+    }
+
+    #[test]
+    fn frequency_map_as_csv() {
+        let mut exp = Expectation::new(Scripted::new(&[1]));
+        exp.freq_map = FrequencyMap::from(HashMap::from([(10, 1), (2, 4), (4, 3)]));
+
+        let mut out = Vec::new();
+        exp.write_csv(&mut out).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "length,frequency\n2,4\n4,3\n10,1\n"
+        );
+    }
+
+    #[test]
+    fn walk_or_none_gives_up_at_cap() {
+        /// Walks off along the number line, never to return.
+        struct Drift(i32);
+
+        impl RandomWalk for Drift {
+            type State = i32;
+
+            fn make_move<R: Rng>(&mut self, _rng: &mut R) {
+                self.0 += 1;
+            }
+
+            fn get_state(&self) -> Self::State {
+                self.0
+            }
+
+            fn set_state(&mut self, state: Self::State) {
+                self.0 = state;
             }
         }
+
+        let mut rng = rand::thread_rng();
+        assert_eq!(Drift(0).walk_or_none(0, 0, &mut rng, 1000), None);
+        assert_eq!(Drift(0).walk_or_none(0, 10, &mut rng, 1000), Some(10));
+        assert_eq!(Scripted::new(&[7]).walk_or_none(0, 0, &mut rng, 7), Some(7));
     }
-}
 
-/// A representation of a coordinate on our [KitchenFloor](KitchenFloor) plane.
-pub type Coord = (i32, i32);
+    #[test]
+    fn path_count_probability_is_exact_fraction() {
+        let result = GraphPathCounter::new().calculate(20).unwrap();
+        assert_eq!(result.fraction(), (173_576_992, 387_420_489));
+        assert_eq!(
+            173_576_992_f64 / 387_420_489_f64,
+            result.probability,
+            "float should be the quotient of the fraction"
+        );
 
-/// Stores a representation of the underlying graph, tracking how many paths have reached each node
-/// at current time step `self.step`.
-///
-/// Calling [next](Self::next) steps the [KitchenFloor](KitchenFloor) steps the graph
-/// representation forward by:
-/// a) introducing any new nodes to the graph which haven't don't already exist from previous steps
-/// b) iterating every node in the graph and setting its new value to the be the sum of the values
-///    in the surrounding nodes from the previous step (but not counting any contribution from the
-///    origin, because any paths which reached this on the previous step would have terminated
-///    there).
-pub struct GraphPathCounter {
-    /// The kitchen floor model.
-    kf: KitchenFloor,
+        let result = GraphPathCounter::new().calculate(10).unwrap();
+        assert_eq!(result.fraction(), (1102, 2187));
 
-    /// Tracks the total number of paths which can arrive at a given coord by a certain time step.
-    pub cells: std::cell::RefCell<HashMap<Coord, usize>>,
+        // With no steps taken, no walk can have returned.
+        let result = GraphPathCounter::new().calculate(0).unwrap();
+        assert_eq!(result.fraction(), (1, 1));
+    }
 
-    /// Tracks which time step we are currently at.
-    step: usize,
-}
+    #[test]
+    fn football_return_time_distribution() {
+        let dist = Football::new().return_time_distribution(2000);
 
-impl GraphPathCounter {
-    /// Create a new graph counter.
-    pub fn new() -> Self {
-        let counter = Self {
-            kf: KitchenFloor::new(),
-            cells: std::cell::RefCell::new(HashMap::new()),
-            step: 0,
+        // Andy can't return home in one step, and can't in two without retracing his first step.
+        assert_eq!(dist[0], 0.0);
+        assert!((dist[1] - 1.0 / 3.0).abs() < 1e-12);
+
+        let total: f64 = dist.iter().sum();
+        assert!((total - 1.0).abs() < 1e-9, "total: {}", total);
+
+        let mean: f64 = dist
+            .iter()
+            .enumerate()
+            .map(|(i, p)| (i + 1) as f64 * p)
+            .sum();
+        assert!((mean - 20.0).abs() < 1e-6, "mean: {}", mean);
+    }
+
+    #[test]
+    fn expectation_reset_allows_reuse() {
+        let mut reused = Expectation::new(Scripted::new(&[2, 4, 9]));
+        reused.calculate(0, 0, 3);
+        reused.reset();
+        assert_eq!(reused.cnt, 0);
+        assert!(reused.freq_map.is_empty());
+
+        let mean = reused.calculate(0, 0, 3);
+        let mut fresh = Expectation::new(Scripted::new(&[2, 4, 9]));
+        assert_eq!(mean, fresh.calculate(0, 0, 3));
+        assert_eq!(reused.freq_map, fresh.freq_map);
+        assert_eq!(reused.mean(), fresh.mean());
+        assert_eq!(reused.variance(), fresh.variance());
+    }
+
+    #[test]
+    fn seeded_expectations_are_reproducible() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let run = |seed| {
+            let mut exp = Expectation::new(Football::new());
+            let mean = exp.calculate_with_rng(1, 1, 1000, &mut StdRng::seed_from_u64(seed));
+            (mean, exp.freq_map)
         };
 
-        counter.cells.borrow_mut().entry((0, 0)).or_insert(1);
-        counter
+        assert_eq!(run(7), run(7));
+        assert_ne!(run(7).1, run(8).1);
     }
 
-    /// Step the internal graph representation forward.
-    ///
-    /// In summary, this function does works by:
-    /// a) introducing any new nodes to the graph which haven't don't already exist from previous steps
-    /// b) iterating every node in the graph and setting its new value to the be the sum of the values
-    ///    in the surrounding nodes from the previous step (but not counting any contribution from the
-    ///    origin, because any paths which reached this on the previous step would have terminated
-    ///    there).
-    pub fn next(&mut self) {
-        self.step += 1;
+    #[test]
+    fn football_orbits_match_hand_classes() {
+        let football = Football::new();
+        let orbits = football.orbit_partition(1);
 
-        let cells: Vec<Coord> = self.cells.borrow().iter().map(|(c, _)| c.clone()).collect();
-        for cell in cells {
-            let neighbours = KitchenFloor::coord_neighbours(cell);
+        // Home, followed by the five classes of node used to solve the first part by hand.
+        let sizes: Vec<usize> = orbits.iter().map(Vec::len).collect();
+        assert_eq!(sizes, [1, 3, 6, 6, 3, 1]);
+        assert_eq!(orbits[0], [1]);
+        assert_eq!(orbits[1], [2, 5, 6]);
 
-            for n in neighbours.iter() {
-                // Ensure that the neighbour actually has an entry in the table.
-                let _cell = self.cells.borrow_mut().entry(*n).or_insert(0);
+        let mut nodes: Vec<i32> = orbits.concat();
+        nodes.sort();
+        assert_eq!(nodes, (1..=20).collect::<Vec<_>>());
+
+        // The generated football is labelled differently, but has the same symmetries.
+        let sizes: Vec<usize> = Football::generated()
+            .orbit_partition(7)
+            .iter()
+            .map(Vec::len)
+            .collect();
+        assert_eq!(sizes, [1, 3, 6, 6, 3, 1]);
+    }
+
+    #[test]
+    fn adjacent_hexagons_are_unit_distance_apart() {
+        let distance = |a, b| {
+            let ((ax, ay), (bx, by)) = (
+                KitchenFloor::coord_to_cartesian(a),
+                KitchenFloor::coord_to_cartesian(b),
+            );
+            (ax - bx).hypot(ay - by)
+        };
+
+        assert_eq!(KitchenFloor::coord_to_cartesian((0, 0)), (0.0, 0.0));
+        for coord in [(0, 0), (2, 0), (5, -3), (-4, 7)] {
+            for n in KitchenFloor::coord_neighbours(coord)
+                .into_iter()
+                .chain(KitchenFloor::black_neighbours(coord))
+            {
+                assert!((distance(coord, n) - 1.0).abs() < 1e-12);
             }
         }
 
-        let mut new_values = Vec::new();
+        // Home is a type A hexagon, so its neighbours lie to the NW, SW and E.
+        let [nw, sw, e] =
+            KitchenFloor::coord_neighbours((0, 0)).map(KitchenFloor::coord_to_cartesian);
+        assert!(nw.0 < 0.0 && nw.1 > 0.0);
+        assert!(sw.0 < 0.0 && sw.1 < 0.0);
+        assert_eq!(e, (1.0, 0.0));
+    }
 
-        // Now, we iterate over everything that appears in the table so far, and add to the counts
-        // of each cell the sum of the counts of its neighbouring cells.
-        for (cell, _) in self.cells.borrow().iter() {
-            let mut new_cnt = 0;
-            let cell_neighbours = KitchenFloor::coord_neighbours(*cell);
+    #[test]
+    fn heatmap_csv_after_one_step() {
+        let mut counter = GraphPathCounter::new();
+        counter.next();
 
-            for n in cell_neighbours.iter() {
-                if *n != (0, 0) || self.step == 1 {
-                    match self.cells.borrow().get(n) {
-                        Some(n_cnt) => new_cnt += *n_cnt,
-                        None => {}
-                    };
-                }
-            }
+        let mut out = Vec::new();
+        counter.to_heatmap_csv(&mut out).unwrap();
 
-            new_values.push((*cell, new_cnt));
+        // Every walk has left home, with one path to each of its neighbours.
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "x,y,count\n-1,0,1\n0,-1,1\n0,0,0\n1,1,1\n"
+        );
+    }
+
+    #[test]
+    fn walk_until_limit_outcomes() {
+        let mut rng = rand::thread_rng();
+        let mut walker = Scripted::new(&[5]);
+
+        let reached = walker.walk_until_limit(0, 0, &mut rng, 10);
+        assert_eq!(reached, WalkOutcome::Reached(5));
+        assert_eq!((reached.steps(), reached.reached()), (5, Some(5)));
+
+        let cut_off = walker.walk_until_limit(0, 0, &mut rng, 3);
+        assert_eq!(cut_off, WalkOutcome::LimitHit(3));
+        assert_eq!((cut_off.steps(), cut_off.reached()), (3, None));
+    }
+
+    #[test]
+    fn walk_until_limit_counts_arrival_on_final_step() {
+        let mut rng = rand::thread_rng();
+        let mut walker = Scripted::new(&[5]);
+
+        // The target is first reached one step before the limit, exactly at it, and after it.
+        assert_eq!(
+            walker.walk_until_limit(0, 0, &mut rng, 6),
+            WalkOutcome::Reached(5)
+        );
+        assert_eq!(
+            walker.walk_until_limit(0, 0, &mut rng, 5),
+            WalkOutcome::Reached(5)
+        );
+        assert_eq!(
+            walker.walk_until_limit(0, 0, &mut rng, 4),
+            WalkOutcome::LimitHit(4)
+        );
+
+        // The walk never passes through state 7.
+        assert_eq!(
+            walker.walk_until_limit(0, 7, &mut rng, 20),
+            WalkOutcome::LimitHit(20)
+        );
+
+        // A limit of one step is honoured too.
+        assert_eq!(
+            Scripted::new(&[1]).walk_until_limit(0, 0, &mut rng, 1),
+            WalkOutcome::Reached(1)
+        );
+    }
+
+    #[test]
+    fn kitchen_floor_as_tiling() {
+        assert_eq!(KitchenFloor::branching_factor(), 3);
+        for coord in [(0, 0), (1, 1), (0, -1), (-4, 2)] {
+            assert_eq!(
+                <KitchenFloor as TilingWalk>::neighbours(coord),
+                KitchenFloor::coord_neighbours(coord)
+            );
         }
 
-        for (cell, cnt) in new_values {
-            *self.cells.borrow_mut().entry(cell).or_insert(0) = cnt;
+        // Moves made through the blanket `RandomWalk` implementation stay on the white hexagons.
+        let mut kitchen_floor = KitchenFloor::new();
+        let mut rng = rand::thread_rng();
+        for _ in 0..1_000 {
+            let before = kitchen_floor.position();
+            kitchen_floor.make_move(&mut rng);
+            assert!(KitchenFloor::coord_neighbours(before).contains(&kitchen_floor.get_state()));
         }
     }
 
-    /// Run the analysis for a given number of steps.
-    ///
-    /// This function tracks how many paths return to the origin (0, 0) in total across all the
-    /// steps. We need to be careful to upscale each such number by a factor of $3^k$ where $k$ is
-    /// the number of remaining steps. This accounts for the fact that we stop counting the paths
-    /// once they have returned home. If we kept counting them each one would diverge into
-    /// $3^k$ paths over the remaining $k$ steps. We need to apportion the probability mass
-    /// correctly in order to divide by $3^{20}$ total paths at the end.
-    pub fn calculate(&mut self, steps: u32) {
-        let start = std::time::Instant::now();
-        let mut returning_paths = 0;
-        let mut returned_paths = 0;
-        for i in 0..steps {
-            self.next();
-            let returned_paths_at_step = self.cells.borrow().get(&(0, 0)).unwrap().clone();
+    #[test]
+    fn path_counting_is_generic_over_tilings() {
+        use crate::lattice::{SquareLattice, TriangularLattice};
 
-            returned_paths += returned_paths_at_step * 3_usize.pow(steps - i - 1);
-            returning_paths += returned_paths_at_step;
-        }
-        let returning_paths_at_final_step = self.cells.borrow().get(&(0, 0)).unwrap().clone();
-        println!(
-            "Number of returning paths on the {}th step: {}",
-            steps, returning_paths_at_final_step
+        let result = GraphPathCounter::<KitchenFloor>::for_tiling()
+            .calculate(20)
+            .unwrap();
+        assert_eq!(format!("{:.7}", result.probability), "0.4480326");
+        assert_eq!(result, GraphPathCounter::new().calculate(20).unwrap());
+
+        // On the square lattice, 4 of the 16 two-step walks go straight back home.
+        let mut square = GraphPathCounter::<SquareLattice>::for_tiling();
+        let result = square.calculate(2).unwrap();
+        assert_eq!((result.total_paths, result.returned_within), (16, 4));
+        assert_eq!(result.branching_factor, 4);
+        assert!(square.calculate(64).is_err());
+
+        // On the triangular lattice, a walk can also return home in three steps, around a
+        // triangle: there are 6 ways to make the first step, and then 2 round each triangle.
+        let result = GraphPathCounter::<TriangularLattice>::for_tiling()
+            .calculate(3)
+            .unwrap();
+        assert_eq!(result.total_paths, 216);
+        assert_eq!(result.returned_within, 6 * 6 + 6 * 2);
+    }
+
+    #[test]
+    fn football_monte_carlo_return_time() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let mut rng = StdRng::seed_from_u64(825);
+        let mean = Football::new().expected_return_time_monte_carlo(1, 200_000, &mut rng);
+
+        assert!((mean - 20.0).abs() < 0.3, "mean: {}", mean);
+    }
+
+    #[test]
+    fn expectation_tracks_longest_walk() {
+        let mut exp = Expectation::new(Scripted::new(&[3, 11, 2, 7]));
+        assert_eq!(exp.max_length(), 0);
+
+        exp.calculate(0, 0, 4);
+        assert_eq!(exp.max_length(), 11);
+        assert_eq!(
+            exp.max_length(),
+            exp.freq_map.iter().map(|(length, _)| length).max().unwrap()
         );
-        println!(
-            "Number of returning paths within {} steps: {:?}",
-            steps, returning_paths
+
+        let mut limited = Expectation::new(Football::new());
+        limited.calculate_with_limit(1, 1, 10_000, 50);
+        assert_eq!(
+            limited.max_length(),
+            limited
+                .freq_map
+                .iter()
+                .map(|(length, _)| length)
+                .max()
+                .unwrap()
         );
+        assert!(limited.max_length() <= 50);
 
-        let total_paths_at_final_step = self
-            .cells
-            .borrow()
-            .iter()
-            .fold(0, |acc, (_, cnt)| acc + cnt);
-        println!("Total paths at final step: {}", total_paths_at_final_step);
+        exp.reset();
+        assert_eq!(exp.max_length(), 0);
+    }
 
-        let total_paths =
-            total_paths_at_final_step + returning_paths - self.cells.borrow().get(&(0, 0)).unwrap();
-        println!("Total paths: {}", total_paths);
+    #[test]
+    fn kitchen_floor_displays_its_state() {
+        let mut kitchen_floor = KitchenFloor::new();
+        assert_eq!(
+            kitchen_floor.to_string(),
+            "(0, 0) A -> [(1, 1), (0, -1), (-1, 0)]"
+        );
 
-        println!(
-            "Total paths inc. {}",
-            returned_paths + total_paths_at_final_step - returning_paths_at_final_step
+        kitchen_floor.set_state((1, 1));
+        assert_eq!(
+            kitchen_floor.to_string(),
+            "(1, 1) B -> [(1, 2), (2, 1), (0, 0)]"
         );
-        let max_paths = 3_usize.pow(steps);
-        println!("3^{}: {}", steps, max_paths);
+    }
 
-        println!(
-            "p = {} / {} = {:7}",
-            max_paths - returned_paths,
-            max_paths,
-            (max_paths - returned_paths) as f64 / max_paths as f64
+    #[test]
+    fn default_move_set_matches_coord_neighbours() {
+        let moves = MoveSet::default();
+        for x in -6..=6 {
+            for y in -6..=6 {
+                if !KitchenFloor::is_black((x, y)) {
+                    assert_eq!(
+                        moves.neighbours((x, y)),
+                        KitchenFloor::coord_neighbours((x, y))
+                    );
+                }
+            }
+        }
+        assert_eq!(KitchenFloor::new().move_set(), moves);
+
+        // Reversing the order of the moves reverses the neighbours Andy is shown.
+        let reversed = MoveSet::new([(-1, 0), (0, -1), (1, 1)], [(-1, -1), (1, 0), (0, 1)]);
+        let kitchen_floor = KitchenFloor::with_move_set(reversed);
+        assert_eq!(
+            kitchen_floor.to_string(),
+            "(0, 0) A -> [(-1, 0), (0, -1), (1, 1)]"
         );
-        println!("took {}ms", start.elapsed().as_micros());
+        assert_eq!(kitchen_floor.neighbour(0), (-1, 0));
     }
-}
 
-/// The most efficient way to calculate the solution to the second part of the question.
-///
-/// Running this we retrieve the final answer of $p = 0.4480326$ in c. 1ms.
-pub fn path_counting_on_graph() {
-    let mut counter = GraphPathCounter::new();
-    counter.calculate(20);
-}
+    #[test]
+    fn hex_types_alternate_between_neighbours() {
+        let mut whites = 0;
+        for x in -30..=30 {
+            for y in -30..=30 {
+                if KitchenFloor::is_black((x, y)) {
+                    assert!(!KitchenFloor::neighbours_have_opposite_type((x, y)));
+                } else {
+                    assert!(KitchenFloor::neighbours_have_opposite_type((x, y)));
+                    whites += 1;
+                }
+            }
+        }
 
-#[cfg(test)]
-mod tests {
-    use crate::KitchenFloor;
+        // Two in every three hexagons are white, give or take the edges of the region.
+        assert_eq!(whites, 2481);
+    }
 
     #[test]
-    fn kitchen_floor_traversal() {
-        #[rustfmt::skip]
-    assert_eq!(KitchenFloor::coord_neighbours((0, 0)), [(1, 1), (0, -1), (-1, 0)]);
+    fn kitchen_floor_walks_longer_than_the_football() {
+        use rand::{rngs::StdRng, SeedableRng};
 
-        #[rustfmt::skip]
-    assert_eq!(KitchenFloor::coord_neighbours((-1, 1)), [(0, 2), (-1, 0), (-2, 1)]);
+        let mut rng = StdRng::seed_from_u64(850);
+        let expected = Football::new().expected_return_time(1);
+        let p = prob_longer_than_with_rng(KitchenFloor::new(), expected, 400_000, &mut rng);
 
-        #[rustfmt::skip]
-    assert_eq!(KitchenFloor::coord_neighbours((-1, 0)), [(-1, 1), (0, 0), (-2, -1)]);
+        // Close enough to the exact answer to rule out P(> 19 steps), which is about 0.456.
+        assert!((p - 0.4480326).abs() < 0.003, "{}", p);
+    }
 
-        #[rustfmt::skip]
-    assert_eq!(KitchenFloor::coord_neighbours((-2, -1)), [(-1, 0), (-2, -2), (-3, -1)]);
+    #[test]
+    fn walks_longer_than_a_scripted_reference() {
+        let mut rng = rand::thread_rng();
+
+        // Every reference walk takes exactly 20 steps, so only the walks of 21 and 22 steps are
+        // longer.
+        let subject = Scripted::new(&[19, 20, 21, 22]);
+        let p = prob_longer_than_reference_with_rng(Scripted::new(&[20]), subject, 4, &mut rng);
+        assert_eq!(p, 0.5);
+
+        // Walks are at least one step long.
+        assert_eq!(prob_longer_than(Scripted::new(&[1]), 0.5, 10), 1.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "should make at least one run")]
+    fn prob_longer_than_needs_runs() {
+        prob_longer_than(KitchenFloor::new(), 20.0, 0);
     }
 }
@@ -1,22 +1,193 @@
-#![allow(unused_imports)]
-
 use andys_morning_stroll::{
-    enumerate_every_walk, expected_walk_length_on_football, multithreaded, path_counting_on_graph,
-    prob_of_longer_walk_in_the_kitchen, GraphPathCounter,
+    enumerate_every_walk, enumerate_every_walk_parallel, expected_walk_length_on_football,
+    multithreaded, path_counting_on_graph, prob_of_longer_walk_in_the_kitchen, wilson_interval,
+    Football,
 };
 
+const USAGE: &str = "\
+Usage: andys-morning-stroll [ROUTINE] [ARGS]
+
+Routines used to solve the question. Read the documentation for each function and the
+underlying data structures for more details.
+
+Part 1:
+  part1 [HOME]                    Expected walk length on the football, from node HOME of 1 to 20
+                                  (default 1)
+
+Part 2:
+  kitchen [RUNS]                  Monte Carlo simulation on the kitchen floor (default 10000000 runs)
+  multithreaded [RUNS]            The same, with RUNS per thread on every core (default 1000000000)
+  enumerate                       Enumerate every 20-step walk
+  enumerate-parallel [THREADS]    The same, across THREADS threads (default 8)
+  graph [STEPS] [--timed]         Count paths on the floor's graph (default 20 steps, and the default routine),
+                                  printing the time taken with --timed
+";
+
+/// One of the routines used to solve the question, with its arguments.
+#[derive(Debug, PartialEq)]
+enum Routine {
+    Part1 { home: i32 },
+    Kitchen { runs: u64 },
+    Multithreaded { runs_per_thread: u64 },
+    Enumerate,
+    EnumerateParallel { threads: usize },
+    Graph { steps: u32, timed: bool },
+}
+
+/// Work out which routine to run from the command-line arguments, not including the program name.
+/// Returns `None` if they don't make sense.
+fn parse_args(args: &[String]) -> Option<Routine> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    let routine = match args.as_slice() {
+        [] => Routine::Graph {
+            steps: 20,
+            timed: false,
+        },
+        ["part1"] => Routine::Part1 { home: 1 },
+        ["part1", home] => {
+            let home = home.parse().ok()?;
+            Football::new().neighbours(home)?;
+
+            Routine::Part1 { home }
+        }
+        ["kitchen"] => Routine::Kitchen { runs: 10_000_000 },
+        ["kitchen", runs] => Routine::Kitchen {
+            runs: runs.parse().ok()?,
+        },
+        ["multithreaded"] => Routine::Multithreaded {
+            runs_per_thread: 1_000_000_000,
+        },
+        ["multithreaded", runs] => Routine::Multithreaded {
+            runs_per_thread: runs.parse().ok()?,
+        },
+        ["enumerate"] => Routine::Enumerate,
+        ["enumerate-parallel"] => Routine::EnumerateParallel { threads: 8 },
+        ["enumerate-parallel", threads] => Routine::EnumerateParallel {
+            threads: threads.parse().ok()?,
+        },
+        ["graph", rest @ ..] => {
+            let (timed, rest) = match rest {
+                [rest @ .., "--timed"] => (true, rest),
+                _ => (false, rest),
+            };
+            let steps = match rest {
+                [] => 20,
+                [steps] => steps.parse().ok()?,
+                _ => return None,
+            };
+
+            Routine::Graph { steps, timed }
+        }
+        _ => return None,
+    };
+
+    Some(routine)
+}
+
 fn main() {
-    // Un-comment below to run the various routines used to solve the question. Read the
-    // documentation for each function and the underlying data structures for more details.
-
-    // Part 1.
-    // --------------------------------------
-    // expected_walk_length_on_football();
-
-    // Part 2.
-    // --------------------------------------
-    // prob_of_longer_walk_in_the_kitchen();
-    // multithreaded();
-    // enumerate_every_walk();
-    path_counting_on_graph();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Some(routine) = parse_args(&args) else {
+        eprint!("{}", USAGE);
+        std::process::exit(1);
+    };
+
+    match routine {
+        // Part 1.
+        // --------------------------------------
+        Routine::Part1 { home } => expected_walk_length_on_football(home),
+
+        // Part 2.
+        // --------------------------------------
+        Routine::Kitchen { runs } => {
+            prob_of_longer_walk_in_the_kitchen(runs, true);
+        }
+        Routine::Multithreaded { runs_per_thread } => {
+            let threads = std::thread::available_parallelism().map_or(8, |n| n.get());
+            let (longer, total) = multithreaded(threads, runs_per_thread, None);
+            println!("grand total: {:?}", (longer, total));
+            println!(
+                "probability of a longer than 20 walk: {}",
+                longer as f64 / total as f64
+            );
+            println!(
+                "95% confidence interval: {:?}",
+                wilson_interval(longer, total, 1.96)
+            );
+        }
+        Routine::Enumerate => println!("{:?}", enumerate_every_walk()),
+        Routine::EnumerateParallel { threads } => {
+            println!("{:?}", enumerate_every_walk_parallel(threads))
+        }
+        Routine::Graph { steps, timed } => path_counting_on_graph(steps, timed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Option<Routine> {
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        parse_args(&args)
+    }
+
+    #[test]
+    fn arguments_select_routine() {
+        assert_eq!(
+            parse(&[]),
+            Some(Routine::Graph {
+                steps: 20,
+                timed: false
+            })
+        );
+        assert_eq!(
+            parse(&["graph", "30"]),
+            Some(Routine::Graph {
+                steps: 30,
+                timed: false
+            })
+        );
+        assert_eq!(
+            parse(&["graph", "--timed"]),
+            Some(Routine::Graph {
+                steps: 20,
+                timed: true
+            })
+        );
+        assert_eq!(
+            parse(&["graph", "30", "--timed"]),
+            Some(Routine::Graph {
+                steps: 30,
+                timed: true
+            })
+        );
+        assert_eq!(parse(&["part1"]), Some(Routine::Part1 { home: 1 }));
+        assert_eq!(parse(&["part1", "7"]), Some(Routine::Part1 { home: 7 }));
+        assert_eq!(
+            parse(&["kitchen", "1000"]),
+            Some(Routine::Kitchen { runs: 1000 })
+        );
+        assert_eq!(
+            parse(&["multithreaded"]),
+            Some(Routine::Multithreaded {
+                runs_per_thread: 1_000_000_000
+            })
+        );
+        assert_eq!(parse(&["enumerate"]), Some(Routine::Enumerate));
+        assert_eq!(
+            parse(&["enumerate-parallel", "4"]),
+            Some(Routine::EnumerateParallel { threads: 4 })
+        );
+    }
+
+    #[test]
+    fn bad_arguments_are_rejected() {
+        assert_eq!(parse(&["part3"]), None);
+        assert_eq!(parse(&["part1", "99"]), None);
+        assert_eq!(parse(&["kitchen", "lots"]), None);
+        assert_eq!(parse(&["graph", "-1"]), None);
+        assert_eq!(parse(&["graph", "--timed", "30"]), None);
+        assert_eq!(parse(&["enumerate", "8"]), None);
+    }
 }
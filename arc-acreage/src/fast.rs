@@ -16,19 +16,42 @@
 //! relax the search constraints and it will still produce the result in under a second. This gives
 //! us even more confidence in the accuracy of our answer.
 
+use crate::grid::{self, GridCell};
+pub use crate::grid::{AreaError, ParseError};
+use crate::search::{LoopSearch, Segment, Slant, MAX_GRID_SIZE};
+use crate::slow;
+use std::collections::{HashMap, HashSet};
+
+/// A point where grid lines cross, as an `(x, y)` pair of grid line indices from the top-left.
+type Point = (i32, i32);
+
 /// A cell in the grid.
 ///
 /// The non-empty cells have diagonal slants in them, either forward-facing (╱) or backward-facing
 /// (╲).
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Cell {
     Empty,
     Forward,
     Backward,
 }
 
+impl Cell {
+    /// The cell with its slant turned the other way.
+    fn flip(self) -> Self {
+        use Cell::*;
+        match self {
+            Empty => Empty,
+            Forward => Backward,
+            Backward => Forward,
+        }
+    }
+}
+
 /// Representation of an area enclosed by a closed curve in the grid.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Area {
     /// The number of full units.
     pub units: u8,
@@ -47,12 +70,25 @@ impl Area {
         }
     }
 
+    /// The area of `half_units` half units, simplified.
+    fn from_half_units(half_units: u32) -> Self {
+        Self {
+            units: (half_units / 2) as u8,
+            half: (half_units % 2) as u8,
+        }
+    }
+
     /// Whether this is an integer area of `n` units.
     #[allow(dead_code)]
     pub fn is_integer(&self, n: u8) -> bool {
         let simplified = self.simplify();
         simplified.units == n && simplified.half == 0
     }
+
+    /// The area as a number of units.
+    pub fn to_f64(&self) -> f64 {
+        self.units as f64 + self.half as f64 * 0.5
+    }
 }
 
 impl std::fmt::Display for Area {
@@ -63,359 +99,814 @@ impl std::fmt::Display for Area {
     }
 }
 
-/// An error returned when attempting to calculate the area enclosed by a loop in a `Grid`.
-#[derive(Debug)]
-pub enum AreaError {
-    LoopNotClosed,
+/// Areas are ordered by magnitude. Equal magnitudes written differently, such as two halves and
+/// one unit, are then ordered by their fields so that the ordering agrees with `Eq`.
+impl Ord for Area {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        let halves = |a: &Area| 2 * a.units as u16 + a.half as u16;
+        halves(self)
+            .cmp(&halves(other))
+            .then_with(|| (self.units, self.half).cmp(&(other.units, other.half)))
+    }
+}
+
+impl PartialOrd for Area {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl std::ops::Add for Area {
+    type Output = Area;
+
+    fn add(self, rhs: Area) -> Area {
+        Area {
+            units: self.units + rhs.units,
+            half: self.half + rhs.half,
+        }
+        .simplify()
+    }
+}
+
+/// Subtraction gives `None` if the result would be negative.
+impl std::ops::Sub for Area {
+    type Output = Option<Area>;
+
+    fn sub(self, rhs: Area) -> Option<Area> {
+        // Work in half units, so we can borrow from the full units.
+        let halves = |a: Area| 2 * a.units as u16 + a.half as u16;
+        let diff = halves(self).checked_sub(halves(rhs))?;
+
+        Some(Area {
+            units: (diff / 2) as u8,
+            half: (diff % 2) as u8,
+        })
+    }
+}
+
+impl std::ops::Mul<u8> for Area {
+    type Output = Area;
+
+    fn mul(self, rhs: u8) -> Area {
+        Area {
+            units: self.units * rhs,
+            half: self.half * rhs,
+        }
+        .simplify()
+    }
 }
 
-/// A 7x7 grid, containing empty cells and curve segments.
-#[derive(Clone, Debug)]
-pub struct Grid {
-    data: [[Cell; 7]; 7],
+/// An NxN grid, containing empty cells and curve segments. The puzzle itself uses the default
+/// 7x7 grid.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Grid<const N: usize = 7> {
+    data: [[Cell; N]; N],
 }
 
-impl Grid {
-    /// Create a new `Grid` from an array of arrays of `Cell`s.
-    pub fn new(data: [[Cell; 7]; 7]) -> Self {
+impl<const N: usize> Grid<N> {
+    /// Create a new `Grid` from an array of arrays of `Cell`s. `N` may be at most
+    /// [MAX_GRID_SIZE].
+    pub fn new(data: [[Cell; N]; N]) -> Self {
+        const {
+            assert!(
+                N <= MAX_GRID_SIZE,
+                "grids larger than 15x15 are not supported"
+            )
+        };
+
         Self { data }
     }
 
-    /// Calculate the enclosed area inside the loop drawn in this `Grid`. This function assumes
-    /// that the shape passed is a valid closed loop. It does not check this.
+    /// Iterate over the cells of the grid in row-major order, along with their `(row, col)`
+    /// coordinates.
+    pub fn cells(&self) -> impl Iterator<Item = ((usize, usize), Cell)> + '_ {
+        self.data
+            .iter()
+            .enumerate()
+            .flat_map(|(r, row)| row.iter().enumerate().map(move |(c, cell)| ((r, c), *cell)))
+    }
+
+    /// Parse a `Grid` from a drawing in the format produced by its `Display` implementation: one
+    /// line per row, with `·` for an empty cell and `╱` or `╲` for a slanted segment.
+    pub fn from_str_art(s: &str) -> Result<Self, ParseError> {
+        grid::parse(s).map(Self::new)
+    }
+
+    /// The representative of this grid's orbit under the symmetries of the square: the
+    /// lexicographically smallest of its eight rotations and reflections. Two grids are the same
+    /// up to symmetry exactly when their canonical forms are equal.
+    pub fn canonical(&self) -> Self {
+        let mut images = Vec::with_capacity(8);
+        let mut image = self.clone();
+        for _ in 0..4 {
+            images.push(image.reflect_horizontal());
+            image = image.rotate90();
+            images.push(image.clone());
+        }
+
+        images
+            .into_iter()
+            .min_by(|a, b| a.data.cmp(&b.data))
+            .expect("there are eight images")
+    }
+
+    /// Apply `f` to each cell, moving the cell at `(r, c)` to `to(r, c)`.
+    fn transform(
+        &self,
+        to: impl Fn(usize, usize) -> (usize, usize),
+        f: impl Fn(Cell) -> Cell,
+    ) -> Self {
+        let mut data = [[Cell::Empty; N]; N];
+        for (r, row) in self.data.iter().enumerate() {
+            for (c, cell) in row.iter().enumerate() {
+                let (nr, nc) = to(r, c);
+                data[nr][nc] = f(*cell);
+            }
+        }
+
+        Self::new(data)
+    }
+
+    /// Rotate the grid a quarter turn clockwise. This turns each forward slant into a backward
+    /// one, and vice versa.
+    pub fn rotate90(&self) -> Self {
+        self.transform(|r, c| (c, N - 1 - r), Cell::flip)
+    }
+
+    /// Reflect the grid in its vertical axis, swapping left and right. This also swaps the two
+    /// kinds of slant.
+    pub fn reflect_horizontal(&self) -> Self {
+        self.transform(|r, c| (r, N - 1 - c), Cell::flip)
+    }
+
+    /// Reflect the grid in its horizontal axis, swapping top and bottom. This also swaps the two
+    /// kinds of slant.
+    pub fn reflect_vertical(&self) -> Self {
+        self.transform(|r, c| (N - 1 - r, c), Cell::flip)
+    }
+
+    /// Whether the curve segments in this `Grid` join up into a single closed loop, without any
+    /// loose ends, branches or self-intersections. [loop_area](Self::loop_area) assumes this.
+    pub fn is_valid_loop(&self) -> bool {
+        grid::is_valid_loop(&self.data)
+    }
+
+    /// Draw this grid as a standalone SVG document. The grid lines are drawn faintly, and each
+    /// slanted cell as a diagonal line across it.
+    pub fn to_svg(&self) -> String {
+        grid::to_svg(&self.data)
+    }
+
+    /// Calculate the enclosed area inside the loop drawn in this `Grid`. This function doesn't
+    /// check that the grid holds a single loop; use [is_valid_loop](Self::is_valid_loop) to do
+    /// so. Malformed grids, with loose ends or crossing segments, give an error rather than a
+    /// nonsensical area.
+    ///
+    /// Several loops which neither cross nor touch are supported, whether side by side or nested.
+    /// Each row is scanned from the left, toggling between outside and inside at every segment,
+    /// so a cell counts as inside when it is enclosed by an odd number of loops. The area of
+    /// separate loops is their total, while a loop nested inside another cuts a hole out of it,
+    /// leaving the area of the ring between them.
     pub fn loop_area(&self) -> Result<Area, AreaError> {
-        // These should sum to exactly 49 at the end of looping through the grid.
-        let mut n = 0; // The number of slanted segments encountered.
-        let mut k = 0; // The number of outside full cells encountered.
+        grid::check_segment_ends(&self.data)?;
+
         let mut j = 0; // The number of inside full cells encountered.
         let mut h = 0; // The number of segments which contribute a half unit of enclosed area.
 
         for row in &self.data {
-            // Tracking whether we are inside or outside the loop before we inspect this cell.
-            let mut outside = true;
+            let (outside, ends_outside) = grid::scan_row(row);
+            if !ends_outside {
+                return Err(AreaError::Inconsistent);
+            }
 
-            for col in row {
-                use Cell::*;
+            for (col, outside) in row.iter().zip(outside) {
                 match col {
-                    Empty => {
-                        if outside {
-                            k += 1;
-                        } else {
+                    Cell::Empty => {
+                        if !outside {
                             j += 1;
                         }
                     }
-                    Forward | Backward => {
-                        n += 1;
-                        h += 1;
-
-                        outside = !outside;
-                    }
+                    Cell::Forward | Cell::Backward => h += 1,
                 }
             }
         }
 
-        if n + k + j != 49 {
-            Err(AreaError::LoopNotClosed)
-        } else {
-            Ok(Area { units: j, half: h }.simplify())
+        Ok(Area { units: j, half: h }.simplify())
+    }
+
+    /// Calculate the same area as [loop_area](Self::loop_area), but independently of its
+    /// row-by-row scan, as a cross-check. Each loop is traced from segment to segment, and the area
+    /// of the polygon through its grid points found with the
+    /// [shoelace formula](https://en.wikipedia.org/wiki/Shoelace_formula). The slanted segments are
+    /// just polygon edges here, so the half cells they cut off need no special treatment.
+    ///
+    /// As with `loop_area`, a loop nested inside an odd number of others cuts a hole out of the
+    /// area rather than adding to it. Whether a loop is nested inside another is found by counting
+    /// the other loop's segments to the left of it in the same row.
+    pub fn loop_area_shoelace(&self) -> Result<Area, AreaError> {
+        grid::check_segment_ends(&self.data)?;
+
+        // Trace every loop, recording which loop passes through each cell, and the first cell and
+        // grid points of each loop.
+        let mut loop_at = [[None; N]; N];
+        let mut loops = Vec::new();
+        for ((r, c), cell) in self.cells() {
+            if cell == Cell::Empty || loop_at[r][c].is_some() {
+                continue;
+            }
+
+            let (cells, points) = self.trace_loop((r, c));
+            for (r, c) in cells {
+                loop_at[r][c] = Some(loops.len());
+            }
+            loops.push(((r, c), points));
         }
+
+        // Twice the enclosed area, so that the half units are whole numbers.
+        let mut doubled = 0i64;
+        for (i, ((r, c), points)) in loops.iter().enumerate() {
+            let twice_area: i64 = points
+                .windows(2)
+                .map(|w| (w[0].0 * w[1].1) as i64 - (w[1].0 * w[0].1) as i64)
+                .sum();
+
+            // A horizontal ray through the centre of one of this loop's cells crosses another loop
+            // an odd number of times exactly when this loop lies inside it.
+            let depth = (0..loops.len())
+                .filter(|j| *j != i)
+                .filter(|j| {
+                    let crossings = loop_at[*r][..*c].iter().filter(|l| **l == Some(*j)).count();
+                    crossings % 2 == 1
+                })
+                .count();
+
+            if depth % 2 == 0 {
+                doubled += twice_area.abs();
+            } else {
+                doubled -= twice_area.abs();
+            }
+        }
+
+        if doubled < 0 {
+            return Err(AreaError::Inconsistent);
+        }
+
+        Ok(Area {
+            units: (doubled / 2) as u8,
+            half: (doubled % 2) as u8,
+        })
     }
-}
 
-impl std::fmt::Display for Grid {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    /// The loop drawn in this `Grid` as an ordered, closed polyline through the grid points it
+    /// visits, for exporting it or cross-checking its area. The loop is traced from the first
+    /// non-empty cell in reading order, so if the grid holds several loops, only that one is
+    /// returned; use [is_valid_loop](Self::is_valid_loop) to rule this out. An empty grid gives no
+    /// points.
+    ///
+    /// Points are `(x, y)` pairs of grid line indices, with `x` increasing to the right and `y`
+    /// downwards from the top-left of the grid as in [to_svg](Self::to_svg). The first point is
+    /// repeated at the end to close the polyline, so there are `loop_length + 1` points in all.
+    pub fn perimeter_coordinates(&self) -> Result<Vec<(i32, i32)>, AreaError> {
+        grid::check_segment_ends(&self.data)?;
+
+        Ok(self
+            .cells()
+            .find(|(_, cell)| *cell != Cell::Empty)
+            .map_or_else(Vec::new, |(first, _)| self.trace_loop(first).1))
+    }
+
+    /// The ends of the segment in the cell at `(r, c)`, as `(x, y)` grid points, or `None` if the
+    /// cell is empty.
+    fn segment_ends(&self, (r, c): (usize, usize)) -> Option<(Point, Point)> {
+        let (x, y) = (c as i32, r as i32);
+        match self.data[r][c] {
+            Cell::Empty => None,
+            Cell::Forward => Some(((x, y + 1), (x + 1, y))),
+            Cell::Backward => Some(((x, y), (x + 1, y + 1))),
+        }
+    }
+
+    /// Trace the loop through the segment in the cell at `first`, returning the cells it passes
+    /// through and the grid points it visits, both in order, as described on
+    /// [perimeter_coordinates](Self::perimeter_coordinates). This relies on `check_segment_ends`
+    /// having passed, and panics if `first` is empty.
+    fn trace_loop(&self, first: (usize, usize)) -> (Vec<(usize, usize)>, Vec<Point>) {
+        let mut cells = Vec::new();
+        let mut points = vec![
+            self.segment_ends(first)
+                .expect("should start on a segment")
+                .0,
+        ];
+
+        let mut cell = first;
+        loop {
+            cells.push(cell);
+            let (a, b) = self.segment_ends(cell).unwrap();
+            let point = if a == points[points.len() - 1] { b } else { a };
+            points.push(point);
+
+            // `check_segment_ends` ensures exactly one other segment ends at `point`, in one of
+            // the four cells around it.
+            let (x, y) = point;
+            cell = [(y - 1, x - 1), (y - 1, x), (y, x - 1), (y, x)]
+                .into_iter()
+                .filter(|(r, c)| (0..N as i32).contains(r) && (0..N as i32).contains(c))
+                .map(|(r, c)| (r as usize, c as usize))
+                .find(|next| {
+                    *next != cell
+                        && self
+                            .segment_ends(*next)
+                            .is_some_and(|(a, b)| a == point || b == point)
+                })
+                .unwrap();
+            if cell == first {
+                break;
+            }
+        }
+
+        (cells, points)
+    }
+
+    /// The [loop_area](Self::loop_area) as a number of units, for plotting and comparisons.
+    pub fn area_f64(&self) -> Result<f64, AreaError> {
+        self.loop_area().map(|area| area.to_f64())
+    }
+
+    /// The length of the loop drawn in this `Grid`, i.e. the number of non-empty cells.
+    pub fn loop_length(&self) -> u8 {
+        self.cells()
+            .filter(|(_, cell)| *cell != Cell::Empty)
+            .count() as u8
+    }
+
+    /// Draw the grid as `Display` does, but with each empty cell inside the loop drawn as `█`, so
+    /// that the cells [loop_area](Self::loop_area) counts as full units can be checked by eye. The
+    /// same scan is used: each row is read from the left, toggling between outside and inside at
+    /// every segment. A row which ends inside the loop, making `loop_area` fail as
+    /// [Inconsistent](AreaError::Inconsistent), is marked with a `!` after it.
+    pub fn debug_regions(&self) -> String {
+        let mut regions = String::new();
+
         for row in &self.data {
-            for col in row {
-                use Cell::*;
-                match *col {
-                    Empty => write!(f, "·")?,
-                    Forward => write!(f, "╱")?,
-                    Backward => write!(f, "╲")?,
-                };
+            let (outside, ends_outside) = grid::scan_row(row);
+            for (col, outside) in row.iter().zip(outside) {
+                regions.push(match col {
+                    Cell::Empty if !outside => '█',
+                    _ => col.glyph(),
+                });
             }
-            writeln!(f)?;
+
+            if !ends_outside {
+                regions.push('!');
+            }
+            regions.push('\n');
+        }
+
+        regions
+    }
+
+    /// The `(row, col)` coordinates of the empty cells lying inside the loop, in row-major order.
+    /// These are the full units of [loop_area](Self::loop_area); the cells the loop passes
+    /// through contribute the half units. Like `loop_area`, this assumes the loop is closed.
+    pub fn enclosed_cells(&self) -> Vec<(usize, usize)> {
+        let mut enclosed = Vec::new();
+
+        for (r, row) in self.data.iter().enumerate() {
+            let (outside, _) = grid::scan_row(row);
+            for (c, col) in row.iter().enumerate() {
+                if *col == Cell::Empty && !outside[c] {
+                    enclosed.push((r, c));
+                }
+            }
+        }
+
+        enclosed
+    }
+}
+
+impl<const N: usize> std::fmt::Display for Grid<N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        grid::draw(&self.data, f)
+    }
+}
+
+/// Grids are serialized compactly, as the text drawing produced by `Display`.
+#[cfg(feature = "serde")]
+impl<const N: usize> serde::Serialize for Grid<N> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, const N: usize> serde::Deserialize<'de> for Grid<N> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let art = String::deserialize(deserializer)?;
+        Grid::from_str_art(&art).map_err(|e| serde::de::Error::custom(format!("{:?}", e)))
+    }
+}
+
+impl GridCell for Cell {
+    const SVG_STYLE: &'static str = "stroke=\"black\" stroke-width=\"3\" stroke-linecap=\"round\"";
+
+    fn glyph(self) -> char {
+        match self {
+            Cell::Empty => '·',
+            Cell::Forward => '╱',
+            Cell::Backward => '╲',
         }
+    }
+
+    fn svg_element(self, x: usize, y: usize, size: usize) -> Option<String> {
+        let ((x1, y1), (x2, y2)) = match self {
+            Cell::Empty => return None,
+            Cell::Forward => ((x, y + size), (x + size, y)),
+            Cell::Backward => ((x, y), (x + size, y + size)),
+        };
+        Some(format!(
+            "<line x1=\"{x1}\" y1=\"{y1}\" x2=\"{x2}\" y2=\"{y2}\"/>"
+        ))
+    }
+}
 
-        Ok(())
+impl Segment for Cell {
+    const EMPTY: Self = Cell::Empty;
+
+    fn along(slant: Slant) -> &'static [Self] {
+        match slant {
+            Slant::Forward => &[Cell::Forward],
+            Slant::Backward => &[Cell::Backward],
+        }
     }
 }
 
 /// A data structure for generating closed loops of a target area, using a back-tracking algorithm.
 #[derive(Debug)]
-pub struct Generator {
+pub struct Generator<const N: usize = 7> {
     /// The target area we are aiming for.
     target: Area,
-    /// The maximum number of inner cells (i.e. not part of the outer boundary of the grid) we can
-    /// have forming part of the curve. This constraint is useful to prune a very large number of
-    /// search paths, assuming we can prove it rigorously for our desired target area.
-    max_inner_cells: u8,
-    /// The maximum length of the loop (in segments). This constraint is useful to prune some search
-    /// paths, assuming we can prove it rigorously for our target area.
-    max_length: u8,
-    /// The current state of the grid.
-    grid: Grid,
-    /// Whether we have placed something in each cell of the grid so far during the backtracking
-    /// algorithm.
-    placed: [[bool; 7]; 7],
-    /// Tracks the number of placed cells; used to ensure backtracking doesn't recurse forever.
-    placed_cnt: u8,
-    /// The order of placements made in the grid. When we backtrack, we pop off elements and undo
-    /// those moves. The first tuple is the coordinate of the cell being placed. The second element
-    /// is the coordinates of the head before we placed this move (for undoing).
-    moves: Vec<((u8, u8), (u8, u8))>,
-    /// The coordinates of the loop's starting point, used to determine when we have closed the
-    /// loop. Coordinates are on the grid lines, zero-indexed from the top-left of the grid.
-    start: (u8, u8),
-    /// The location of the head of the loop we are generating. Coordinates are on the grid lines.
-    head: (u8, u8),
-    /// Storage for all the valid grids we find.
-    valid_grids: Vec<Grid>,
+    /// The state of the backtracking search.
+    search: LoopSearch<Cell, N>,
     /// Counter of all valid grids, capturing the multiplicity. This algorithm will find valid
     /// _layouts_ using forward/backward strokes. Each of these has associated with it a large
     /// number of grids drawn with quarter circle arcs. In fact, if the path length is 2n (it must
     /// be even), then there are (2n choose n) arc-segment paths for each path we find.
-    valid_cnt: usize,
-    calls: usize,
-    /// The number of cells we have placed not on the outer rim of the grid. This constraint is
-    /// useful to prune a large number of search paths, assuming we can prove it rigorously for our
-    /// target area.
-    inner_cells: usize,
+    valid_cnt: u128,
+    /// The number of valid layouts found for each loop length (in segments).
+    lengths: HashMap<u8, usize>,
+    /// When counting every area at once, the count of valid grids (including multiplicity) for
+    /// each integer area. `None` when searching for a single target area.
+    area_counts: Option<HashMap<u8, u128>>,
+    /// The index into [first_cells](LoopSearch::first_cells) of the next first cell to search
+    /// from, when the search is being made a few first cells at a time with
+    /// [advance](Generator::advance).
+    next_first_cell: usize,
+}
+
+/// A checkpoint of a [Generator] part of the way through its search, from which the search can
+/// be resumed with [Generator::from_checkpoint]. With the `serde` feature enabled, this can be
+/// serialized, e.g. to save a long search to disk.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GeneratorState {
+    target: Area,
+    max_inner_cells: u8,
+    max_length: u8,
+    next_first_cell: usize,
+    valid_cnt: u128,
+    lengths: HashMap<u8, usize>,
+    area_counts: Option<HashMap<u8, u128>>,
 }
 
-impl Generator {
+impl<const N: usize> Generator<N> {
     /// Create a new `Generator`.
     pub fn new(target: Area, max_inner_cells: u8, max_length: u8) -> Self {
         Self {
             target: target.simplify(),
-            max_inner_cells,
-            max_length,
-            grid: Grid::new([[Cell::Empty; 7]; 7]),
-            placed: [[false; 7]; 7],
-            placed_cnt: 0,
-            moves: Vec::with_capacity(49),
-            start: (0, 0),
-            head: (0, 0),
-            valid_grids: Vec::new(),
+            search: LoopSearch::new(max_inner_cells, max_length),
             valid_cnt: 0,
-            calls: 0,
-            inner_cells: 0,
+            lengths: HashMap::new(),
+            area_counts: None,
+            next_first_cell: 0,
         }
     }
 
-    /// Generate the total count of valid grids (including multiplicity), and a vec of all the grid
-    /// layouts.
-    pub fn generate(mut self) -> (usize, Vec<Grid>) {
-        self.next_cell();
-        (self.valid_cnt, self.valid_grids)
-    }
-
-    fn next_cell(&mut self) {
-        self.calls += 1;
-        if self.calls % 1_000_000 == 0 {
-            println!(
-                "{} nodes visited; {} valid grids found",
-                self.calls,
-                self.valid_grids.len(),
-            );
+    /// Recreate the `Generator` which made `state`, ready to carry on its search with
+    /// [advance](Generator::advance), or to finish it with [generate](Generator::generate),
+    /// [generate_with](Generator::generate_with) or
+    /// [length_distribution](Generator::length_distribution). The layouts found before the
+    /// checkpoint aren't stored in it, so only the counts cover the whole search.
+    pub fn from_checkpoint(state: GeneratorState) -> Self {
+        Self {
+            valid_cnt: state.valid_cnt,
+            lengths: state.lengths,
+            area_counts: state.area_counts,
+            next_first_cell: state.next_first_cell,
+            ..Self::new(state.target, state.max_inner_cells, state.max_length)
         }
+    }
 
-        if self.moves.len() == 0 {
-            // Try every possibility for the first cell.
-            for r in 0..7 {
-                for c in 0..7 {
-                    use Cell::*;
-                    for cell in [Forward, Backward] {
-                        match cell {
-                            Empty => unreachable!(),
-                            Forward => {
-                                let start = (r + 1, c);
-                                if start == (0, 0)
-                                    || start == (0, 7)
-                                    || start == (7, 0)
-                                    || start == (7, 7)
-                                {
-                                    continue;
-                                }
-
-                                self.head = (r, c + 1);
-                                self.start = (r + 1, c);
-                                self.place(r, c, cell, r, c + 1);
-                            }
-                            Backward => {
-                                let start = (r, c);
-                                if start == (0, 0)
-                                    || start == (0, 7)
-                                    || start == (7, 0)
-                                    || start == (7, 7)
-                                {
-                                    continue;
-                                }
-
-                                self.head = (r + 1, c + 1);
-                                self.start = (r, c);
-                                self.place(r, c, cell, r + 1, c + 1);
-                            }
-                        }
+    /// Save the progress of the search so far, to be resumed later with
+    /// [from_checkpoint](Generator::from_checkpoint).
+    pub fn checkpoint(&self) -> GeneratorState {
+        GeneratorState {
+            target: self.target,
+            max_inner_cells: self.search.max_inner_cells,
+            max_length: self.search.max_length,
+            next_first_cell: self.next_first_cell,
+            valid_cnt: self.valid_cnt,
+            lengths: self.lengths.clone(),
+            area_counts: self.area_counts.clone(),
+        }
+    }
 
-                        self.next_cell();
-                        self.unplace();
-                    }
+    /// Carry on the search from up to `first_cells` more of its possible first cells, calling `f`
+    /// with each valid layout found. Returns whether the search is now complete, when the total
+    /// count is [valid_cnt](Generator::valid_cnt).
+    ///
+    /// The search from each first cell is a single recursive call, so this is as fine grained as
+    /// a [checkpoint](Generator::checkpoint) can be. There are $2N^2$ first cells, and on the
+    /// puzzle's grid the search from each takes a small fraction of the whole.
+    pub fn advance(&mut self, first_cells: usize, mut f: impl FnMut(&Grid<N>)) -> bool {
+        let all_first_cells = LoopSearch::<Cell, N>::first_cells();
+        let end = all_first_cells
+            .len()
+            .min(self.next_first_cell.saturating_add(first_cells));
 
-                    // Unlike with non-first cells, we want to maintain the flag that marks
-                    // this as placed, because we don't want the loop to ever come back here.
-                    self.placed[r as usize][c as usize] = true;
-                    assert_eq!(self.grid.data, [[Empty; 7]; 7]);
-                }
-            }
-        } else {
-            // Get the last cell that we placed.
-            let ((pr, pc), _) = self.moves.last().expect("should be non-empty").clone();
-            let p_cell = self.grid.data[pr as usize][pc as usize];
-            assert_ne!(p_cell, Cell::Empty);
+        while self.next_first_cell < end {
+            self.run(Some(all_first_cells[self.next_first_cell]), &mut f);
+            self.next_first_cell += 1;
+        }
 
-            let mut moves = Vec::with_capacity(3);
+        self.next_first_cell == all_first_cells.len()
+    }
 
-            // Consider the current head. There are four cells surrounding it. Establish from the
-            // `placed` grid which of these we can move to next.
-            let (hr, hc) = self.head;
+    /// The count of valid grids (including multiplicity) found so far.
+    pub fn valid_cnt(&self) -> u128 {
+        self.valid_cnt
+    }
 
-            for dr in [-1, 1] {
-                let nr = hr as i32 + dr;
-                if nr < 0 || nr > 7 {
-                    continue;
-                }
-                let nr = nr as u8;
+    /// Search from every first cell not yet searched, with [advance](Generator::advance) if the
+    /// search has already been started that way.
+    fn run_remaining(&mut self, f: &mut impl FnMut(&Grid<N>)) {
+        if self.next_first_cell == 0 {
+            self.run(None, f);
+        } else {
+            self.advance(usize::MAX, f);
+        }
+    }
 
-                for dc in [-1, 1] {
-                    let nc = hc as i32 + dc;
-                    if nc < 0 || nc > 7 {
-                        continue;
-                    }
-                    let nc = nc as u8;
+    /// Panic if the search has been started with [advance](Generator::advance), for the methods
+    /// which can only make the whole search in one go.
+    fn assert_not_started(&self, method: &str) {
+        assert!(
+            self.next_first_cell == 0,
+            "`{method}` can't finish a search which has already been advanced"
+        );
+    }
 
-                    let ncellr = if dr == 1 { nr - 1 } else { nr };
-                    let ncellc = if dc == 1 { nc - 1 } else { nc };
+    /// Create a new `Generator` with no constraints on the search beyond the size of the grid,
+    /// so that no loop of the target area can be missed. Unlike the slow model, the fast model
+    /// doesn't need constraints to finish quickly.
+    pub fn new_unconstrained(target: Area) -> Self {
+        Self::new(target, (N * N) as u8, (N * N) as u8)
+    }
 
-                    // Check that the proposed new cell location isn't already populated.
-                    if self.placed[ncellr as usize][ncellc as usize] {
-                        continue;
-                    }
+    /// Report progress by calling `callback` every `interval` nodes of the search, with the number
+    /// of nodes visited and the number of valid grid layouts found so far. By default, progress
+    /// is not reported.
+    pub fn with_progress(
+        mut self,
+        interval: usize,
+        callback: impl FnMut(usize, usize) + Send + 'static,
+    ) -> Self {
+        self.search.set_progress(interval, callback);
+        self
+    }
 
-                    // Push the relevant move into the list.
-                    use Cell::*;
-                    match (dr, dc) {
-                        (-1, -1) | (1, 1) => {
-                            moves.push((ncellr, ncellc, Backward, nr, nc));
-                        }
-                        (-1, 1) | (1, -1) => {
-                            moves.push((ncellr, ncellc, Forward, nr, nc));
-                        }
-                        _ => unreachable!(),
-                    }
-                }
-            }
+    /// Generate the total count of valid grids (including multiplicity), and a vec of all the grid
+    /// layouts.
+    pub fn generate(self) -> (u128, Vec<Grid<N>>) {
+        let mut valid_grids = Vec::new();
+        let valid_cnt = self.generate_with(|grid| valid_grids.push(grid.clone()));
+        (valid_cnt, valid_grids)
+    }
 
-            // Iterate the moves
-            for (ncellr, ncellc, n_cell, nr, nc) in moves {
-                // Check if the current possibility causes a self-intersection. If so, continue.
-                let mut c = 0_u8;
-
-                // Top-left
-                if nr > 0
-                    && nc > 0
-                    && self.grid.data[nr as usize - 1][nc as usize - 1] != Cell::Empty
-                {
-                    c += 1;
-                }
-                // Top-right
-                if nr > 0 && nc < 7 && self.grid.data[nr as usize - 1][nc as usize] != Cell::Empty {
-                    c += 1;
-                }
-                // Bottom-left
-                if nr < 7 && nc > 0 && self.grid.data[nr as usize][nc as usize - 1] != Cell::Empty {
-                    c += 1;
-                }
-                // Bottom-right
-                if nr < 7 && nc < 7 && self.grid.data[nr as usize][nc as usize] != Cell::Empty {
-                    c += 1;
-                }
+    /// Generate the total count of valid grids (including multiplicity), calling `f` with each
+    /// valid grid layout as it is found rather than storing them all.
+    pub fn generate_with(mut self, mut f: impl FnMut(&Grid<N>)) -> u128 {
+        self.run_remaining(&mut f);
+        self.valid_cnt
+    }
 
-                if c >= 2 {
-                    continue;
-                }
+    /// Generate the total count of valid grids (including multiplicity), along with the
+    /// distribution of loop lengths: a map from each length (in segments) to the number of valid
+    /// layouts of that length. This is useful for checking that `max_length` is not cutting off
+    /// any valid loops.
+    pub fn length_distribution(mut self) -> (u128, HashMap<u8, usize>) {
+        self.run_remaining(&mut |_| {});
+        (self.valid_cnt, self.lengths)
+    }
 
-                // Check if this possibility closes the loop. If so, add it to the valid grids.
-                // The current `placed_cnt` must have odd parity if adding this possibility would
-                // close the loop, because a closed loop must have even parity.
-                if nr == self.start.0 && nc == self.start.1 {
-                    assert_eq!(c, 1);
+    /// Count the closed curves of every area, rather than just the target area, from now on. The
+    /// search can then be made a few first cells at a time with [advance](Generator::advance),
+    /// with the counts saved in each [checkpoint](Generator::checkpoint), and finished with
+    /// [count_all_areas](Generator::count_all_areas).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the search has already been advanced while counting only the target area.
+    pub fn counting_all_areas(mut self) -> Self {
+        if self.area_counts.is_none() {
+            self.assert_not_started("counting_all_areas");
+            self.area_counts = Some(HashMap::new());
+        }
+        self
+    }
 
-                    self.place(ncellr, ncellc, n_cell, nr, nc);
-                    assert!(self.placed_cnt % 2 == 0);
+    /// Count the closed curves of every area in a single pass of the search, rather than searching
+    /// again for each target area. Returns a map from each achievable integer area to the number
+    /// of curves (including multiplicity) enclosing it. The generator's own target is ignored,
+    /// although its other constraints still apply.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the search has already been advanced without
+    /// [counting_all_areas](Generator::counting_all_areas).
+    pub fn count_all_areas(self) -> HashMap<u8, u128> {
+        let mut generator = self.counting_all_areas();
+        generator.run_remaining(&mut |_| {});
+        generator
+            .area_counts
+            .expect("should be counting every area")
+    }
 
-                    let area = self.grid.loop_area().expect("we formed a loop").simplify();
+    /// Count the closed curves of quarter-circle arcs enclosing `target`, which may be any area
+    /// the slow model can find, such as $32 + \pi/2$, rather than just the integer areas. The
+    /// generator's own target is ignored, although its other constraints still apply.
+    ///
+    /// Each layout of diagonals, enclosing $j$ full cells with a loop of $n$ segments, is drawn
+    /// with arcs in $2^n$ ways. Each arc either bulges out of the loop, enclosing $\pi/4$ of its
+    /// cell, or into it, enclosing $1 - \pi/4$. With $s$ arcs bulging in, the curve encloses
+    /// $(j + s) + (n - 2s)\pi/4$, so the curves enclosing `target` are the
+    /// $\binom{n}{s}$ with the right $s$ from each layout where one exists. Unlike the slow model,
+    /// this never has to try each of the arcs in turn.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the search has already been advanced with [advance](Generator::advance).
+    pub fn count_arc_curves(mut self, target: slow::Area) -> u128 {
+        self.assert_not_started("count_arc_curves");
+        let (units, quarter_pis) = target.to_units_and_fraction();
+        let mut cnt = 0;
 
-                    if area == self.target {
-                        self.valid_grids.push(self.grid.clone());
-                        self.valid_cnt += central_binom(self.placed_cnt / 2);
+        self.search.run(&mut |search| {
+            let n = search.length() as i16;
+            // The layout encloses j full cells and half of each of its n segments.
+            let inside = (search.polygon_area_doubled() as i16 - n) / 2;
 
-                        self.unplace();
-                    } else {
-                        // We formed a loop, but it was the wrong size.
-                        self.unplace();
-                        continue;
-                    }
-                }
+            // Solve n - 2s = quarter_pis for the number of arcs s bulging into the loop.
+            let twice_small = n - quarter_pis as i16;
+            if twice_small < 0 || twice_small > 2 * n || twice_small % 2 != 0 {
+                return false;
+            }
+            let small = twice_small / 2;
+            if inside + small != units as i16 {
+                return false;
+            }
 
-                if self.placed_cnt + 1 > self.max_length {
-                    continue;
-                }
+            cnt += binom_u128(n as u8, small as u8);
+            true
+        });
 
-                // Place the current possibility
-                self.place(ncellr, ncellc, n_cell, nr, nc);
+        cnt
+    }
 
-                if self.inner_cells <= self.max_inner_cells as usize {
-                    self.next_cell();
-                }
+    /// Same as [generate](Generator::generate), but searches without pruning the cells already
+    /// tried as the first cell of a loop, and instead deduplicates the layouts found. This finds
+    /// each loop many times over, so is far slower, but comparing the results with `generate`
+    /// checks that the pruning doesn't miss any loops. The layouts are returned in the order they
+    /// are first found, which differs from `generate`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the search has already been advanced with [advance](Generator::advance).
+    pub fn generate_deduplicated(mut self) -> (u128, Vec<Grid<N>>) {
+        self.assert_not_started("generate_deduplicated");
+        let target = self.target;
+        let mut seen = HashSet::new();
+        let mut valid_cnt = 0;
+        let mut valid_grids = Vec::new();
 
-                self.unplace();
+        self.search.run_unpruned(&mut |search| {
+            if Area::from_half_units(search.polygon_area_doubled()) != target {
+                return false;
             }
-        }
+
+            let grid = Grid::new(*search.cells());
+            if !seen.contains(&grid) {
+                valid_cnt += central_binom_u128(search.length() / 2);
+                seen.insert(grid.clone());
+                valid_grids.push(grid);
+            }
+            true
+        });
+
+        (valid_cnt, valid_grids)
     }
 
-    fn place(&mut self, row: u8, col: u8, c: Cell, headr: u8, headc: u8) {
-        let cell = &mut self.grid.data[row as usize][col as usize];
-        let placed = &mut self.placed[row as usize][col as usize];
+    /// Count the valid grid layouts which are distinct up to rotation and reflection, by
+    /// collecting the [canonical](Grid::canonical) form of each.
+    pub fn count_orbits(self) -> usize {
+        let mut orbits = HashSet::new();
+        self.generate_with(|grid| {
+            orbits.insert(grid.canonical());
+        });
+        orbits.len()
+    }
 
-        assert_eq!(*placed, false);
+    /// Same as [generate](Generator::generate), but shares the search out between `threads`
+    /// worker threads. Each choice of first cell is searched separately, with its own copy of the
+    /// generator's state, and the results are combined in the same order `generate` finds them.
+    /// The worker threads don't report progress.
+    pub fn generate_parallel(self, threads: usize) -> (u128, Vec<Grid<N>>) {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Mutex;
 
-        *cell = c;
-        *placed = true;
-        self.placed_cnt += 1;
-        self.moves.push(((row, col), self.head));
-        self.head = (headr, headc);
+        let (target, max_inner_cells, max_length) = (
+            self.target,
+            self.search.max_inner_cells,
+            self.search.max_length,
+        );
+        let first_cells = LoopSearch::<Cell, N>::first_cells();
+        let results = Mutex::new(vec![(0, Vec::new()); first_cells.len()]);
+        let next_task = AtomicUsize::new(0);
 
-        if row > 0 && row < 6 && col > 0 && col < 6 {
-            self.inner_cells += 1;
-        }
+        std::thread::scope(|scope| {
+            for _ in 0..threads.max(1) {
+                scope.spawn(|| loop {
+                    let task = next_task.fetch_add(1, Ordering::Relaxed);
+                    let Some(&first_cell) = first_cells.get(task) else {
+                        break;
+                    };
+
+                    let mut generator = Self::new(target, max_inner_cells, max_length);
+                    let mut valid_grids = Vec::new();
+                    generator.run(Some(first_cell), &mut |grid| valid_grids.push(grid.clone()));
+
+                    results.lock().unwrap()[task] = (generator.valid_cnt, valid_grids);
+                });
+            }
+        });
+
+        results.into_inner().unwrap().into_iter().fold(
+            (0, Vec::new()),
+            |(cnt, mut grids), (task_cnt, task_grids)| {
+                grids.extend(task_grids);
+                (cnt + task_cnt, grids)
+            },
+        )
     }
 
-    fn unplace(&mut self) {
-        let ((row, col), old_head) = self
-            .moves
-            .pop()
-            .expect("should never call `unplace` with nothing to unplace");
-        let cell = &mut self.grid.data[row as usize][col as usize];
-        let placed = &mut self.placed[row as usize][col as usize];
-        assert_eq!(*placed, true);
+    /// Run the search, from the given first cell or otherwise from every first cell, counting the
+    /// loops of the target area (or every area) and calling `f` with each valid layout.
+    fn run(&mut self, first_cell: Option<(u8, u8, Cell)>, f: &mut impl FnMut(&Grid<N>)) {
+        let Self {
+            target,
+            search,
+            valid_cnt,
+            lengths,
+            area_counts,
+            ..
+        } = self;
+
+        let mut closed = |search: &LoopSearch<Cell, N>| {
+            // The search tracks the area as it goes, which saves rescanning the grid with
+            // `loop_area` every time a loop closes.
+            let area = Area::from_half_units(search.polygon_area_doubled());
+            let multiplicity = central_binom_u128(search.length() / 2);
+
+            if let Some(area_counts) = area_counts {
+                // Every loop is of interest, so count it towards its own area.
+                debug_assert_eq!(area.half, 0);
+                *area_counts.entry(area.units).or_insert(0) += multiplicity;
+                return true;
+            }
+
+            if area != *target {
+                // We formed a loop, but it was the wrong size.
+                return false;
+            }
 
-        *cell = Cell::Empty;
-        *placed = false;
-        self.placed_cnt -= 1;
-        self.head = old_head;
+            *valid_cnt += multiplicity;
+            *lengths.entry(search.length()).or_insert(0) += 1;
+            f(&Grid::new(*search.cells()));
+            true
+        };
 
-        if row > 0 && row < 6 && col > 0 && col < 6 {
-            self.inner_cells -= 1;
+        match first_cell {
+            Some((r, c, cell)) => search.run_from(r, c, cell, &mut closed),
+            None => search.run(&mut closed),
         }
     }
 }
@@ -427,9 +918,20 @@ impl Generator {
 ///
 /// # Panics
 ///
-/// Panics for values of n > 26.
+/// Panics for values of n > 26. See [central_binom_checked] for a non-panicking version.
+#[allow(dead_code)]
 const fn central_binom(n: u8) -> usize {
-    match n {
+    match central_binom_checked(n) {
+        Some(binom) => binom,
+        None => unimplemented!(),
+    }
+}
+
+/// Returns the value of 2n choose n, or `None` for values of n > 26 which are beyond the lookup
+/// table. [central_binom_u128] covers larger values.
+#[allow(dead_code)]
+const fn central_binom_checked(n: u8) -> Option<usize> {
+    let binom = match n {
         0 => 1,
         1 => 2,
         2 => 6,
@@ -457,55 +959,743 @@ const fn central_binom(n: u8) -> usize {
         24 => 32247603683100,
         25 => 126410606437752,
         26 => 495918532948104,
-        _ => unimplemented!(),
+        _ => return None,
+    };
+
+    Some(binom)
+}
+
+/// Returns the value of 2n choose n, like [central_binom], but computed iteratively in `u128` so
+/// that it covers the much longer loops possible on larger grids.
+///
+/// # Panics
+///
+/// Panics for values of n > 65, whose central binomial coefficients don't fit in a `u128`.
+const fn central_binom_u128(n: u8) -> u128 {
+    assert!(n <= 65, "central binomial coefficient should fit in a u128");
+
+    // Use (2k choose k) = (2k-2 choose k-1) * 2(2k-1) / k, where the division is always exact.
+    // Dividing out the common factor of (2k-2 choose k-1) and k first leaves a divisor of 2(2k-1),
+    // so no intermediate value is larger than the result.
+    let mut binom = 1;
+    let mut k = 1;
+    while k <= n as u128 {
+        let g = gcd(binom, k);
+        binom = (binom / g) * (2 * (2 * k - 1) / (k / g));
+        k += 1;
+    }
+
+    binom
+}
+
+/// The greatest common divisor of `a` and `b`, by Euclid's algorithm.
+const fn gcd(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+
+    a
+}
+
+/// Returns the value of n choose k, computed iteratively in `u128`.
+///
+/// # Panics
+///
+/// Panics if `k > n`, or if n choose k doesn't fit in a `u128`.
+const fn binom_u128(n: u8, k: u8) -> u128 {
+    assert!(k <= n, "k should be <= n");
+
+    // Use (n choose i+1) = (n choose i) * (n-i) / (i+1), where the division is always exact.
+    // Dividing out the common factor of (n choose i) and i+1 first leaves a divisor of n-i, so no
+    // intermediate value is larger than the result.
+    let mut binom: u128 = 1;
+    let mut i = 0;
+    while i < k as u128 {
+        let g = gcd(binom, i + 1);
+        binom = match (binom / g).checked_mul((n as u128 - i) / ((i + 1) / g)) {
+            Some(binom) => binom,
+            None => panic!("binomial coefficient should fit in a u128"),
+        };
+        i += 1;
     }
+
+    binom
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[test]
-    fn example_shapes_have_correct_area() {
+    fn example_grids() -> [Grid; 3] {
         use Cell::*;
 
-        let grid1 = Grid::new([
-            [Empty, Empty, Empty, Empty, Empty, Empty, Empty],
-            [Empty, Forward, Backward, Empty, Empty, Empty, Empty],
-            [Empty, Backward, Forward, Empty, Empty, Empty, Empty],
-            [Empty, Empty, Empty, Empty, Empty, Empty, Empty],
-            [Empty, Empty, Empty, Empty, Empty, Empty, Empty],
-            [Empty, Empty, Empty, Empty, Empty, Empty, Empty],
-            [Empty, Empty, Empty, Empty, Empty, Empty, Empty],
-        ]);
+        [
+            Grid::new([
+                [Empty, Empty, Empty, Empty, Empty, Empty, Empty],
+                [Empty, Forward, Backward, Empty, Empty, Empty, Empty],
+                [Empty, Backward, Forward, Empty, Empty, Empty, Empty],
+                [Empty, Empty, Empty, Empty, Empty, Empty, Empty],
+                [Empty, Empty, Empty, Empty, Empty, Empty, Empty],
+                [Empty, Empty, Empty, Empty, Empty, Empty, Empty],
+                [Empty, Empty, Empty, Empty, Empty, Empty, Empty],
+            ]),
+            Grid::new([
+                [Empty, Empty, Empty, Empty, Empty, Empty, Empty],
+                [Empty, Empty, Empty, Empty, Empty, Empty, Empty],
+                [Empty, Empty, Empty, Empty, Empty, Empty, Empty],
+                [Empty, Empty, Empty, Empty, Empty, Empty, Empty],
+                [Empty, Empty, Forward, Backward, Forward, Backward, Empty],
+                [Empty, Empty, Backward, Empty, Empty, Forward, Empty],
+                [Empty, Empty, Empty, Backward, Forward, Empty, Empty],
+            ]),
+            Grid::new([
+                [Empty, Empty, Forward, Backward, Forward, Backward, Empty],
+                [Empty, Forward, Empty, Empty, Empty, Empty, Backward],
+                [Forward, Empty, Empty, Empty, Empty, Empty, Forward],
+                [Backward, Empty, Empty, Empty, Empty, Empty, Backward],
+                [Forward, Empty, Empty, Empty, Empty, Empty, Forward],
+                [Backward, Empty, Empty, Empty, Empty, Forward, Empty],
+                [Empty, Backward, Forward, Backward, Forward, Empty, Empty],
+            ]),
+        ]
+    }
+
+    #[test]
+    fn example_shapes_have_correct_area() {
+        let [grid1, grid2, grid3] = example_grids();
 
         assert_eq!(
             grid1.loop_area().unwrap().simplify(),
             Area { units: 2, half: 0 }
         );
 
-        let grid2 = Grid::new([
-            [Empty, Empty, Empty, Empty, Empty, Empty, Empty],
-            [Empty, Empty, Empty, Empty, Empty, Empty, Empty],
-            [Empty, Empty, Empty, Empty, Empty, Empty, Empty],
-            [Empty, Empty, Empty, Empty, Empty, Empty, Empty],
-            [Empty, Empty, Forward, Backward, Forward, Backward, Empty],
-            [Empty, Empty, Backward, Empty, Empty, Forward, Empty],
-            [Empty, Empty, Empty, Backward, Forward, Empty, Empty],
-        ]);
-
         assert!(grid2.loop_area().unwrap().is_integer(6));
 
-        let grid3 = Grid::new([
-            [Empty, Empty, Forward, Backward, Forward, Backward, Empty],
-            [Empty, Forward, Empty, Empty, Empty, Empty, Backward],
-            [Forward, Empty, Empty, Empty, Empty, Empty, Forward],
-            [Backward, Empty, Empty, Empty, Empty, Empty, Backward],
-            [Forward, Empty, Empty, Empty, Empty, Empty, Forward],
-            [Backward, Empty, Empty, Empty, Empty, Forward, Empty],
-            [Empty, Backward, Forward, Backward, Forward, Empty, Empty],
-        ]);
-
         assert_eq!(grid3.loop_area().unwrap(), Area { units: 32, half: 0 });
     }
+
+    #[test]
+    fn grids_round_trip_through_str_art() {
+        for grid in example_grids() {
+            assert_eq!(Grid::from_str_art(&grid.to_string()), Ok(grid));
+        }
+    }
+
+    #[test]
+    fn malformed_str_art_is_rejected() {
+        let art = example_grids()[0].to_string();
+
+        let short: String = art.lines().take(6).map(|l| format!("{l}\n")).collect();
+        assert_eq!(
+            Grid::<7>::from_str_art(&short),
+            Err(ParseError::WrongRowCount(6))
+        );
+
+        let narrow = art.replacen("·\n", "\n", 1);
+        assert_eq!(
+            Grid::<7>::from_str_art(&narrow),
+            Err(ParseError::WrongRowLength(0, 6))
+        );
+
+        let unknown = art.replacen('╱', "x", 1);
+        assert_eq!(
+            Grid::<7>::from_str_art(&unknown),
+            Err(ParseError::UnknownGlyph('x'))
+        );
+    }
+
+    #[test]
+    fn generator_on_small_grid() {
+        // On a 3x3 grid, the only loops of area 2 are the diamonds around each of the four inner
+        // grid points. Each has length 4, so contributes (4 choose 2) curves.
+        let (valid_cnt, valid_grids) =
+            Generator::<3>::new(Area { units: 2, half: 0 }, 9, 9).generate();
+
+        assert_eq!(valid_grids.len(), 4);
+        assert_eq!(valid_cnt, 24);
+    }
+
+    #[test]
+    fn generate_with_visits_every_valid_grid() {
+        let target = Area { units: 4, half: 0 };
+        let (valid_cnt, valid_grids) = Generator::<4>::new(target, 16, 16).generate();
+
+        let mut calls = 0;
+        let cnt = Generator::<4>::new(target, 16, 16).generate_with(|grid| {
+            assert_eq!(grid.loop_area().unwrap(), target);
+            calls += 1;
+        });
+
+        assert!(!valid_grids.is_empty());
+        assert_eq!(calls, valid_grids.len());
+        assert_eq!(cnt, valid_cnt);
+    }
+
+    #[test]
+    fn parallel_generator_matches_serial() {
+        let target = Area { units: 4, half: 0 };
+        let serial = Generator::<5>::new(target, 25, 25).generate();
+        let parallel = Generator::<5>::new(target, 25, 25).generate_parallel(4);
+
+        assert_eq!(parallel, serial);
+    }
+
+    #[test]
+    fn parallel_generator_gives_puzzle_answer() {
+        let target = Area { units: 32, half: 0 };
+        let (valid_cnt, _) = Generator::<7>::new(target, 49, 49).generate_parallel(8);
+
+        assert_eq!(valid_cnt, 89_519_144);
+    }
+
+    #[test]
+    fn generator_with_progress_can_move_between_threads() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let target = Area { units: 4, half: 0 };
+        let calls = Arc::new(AtomicUsize::new(0));
+        let reported = Arc::clone(&calls);
+        let generator = Generator::<5>::new(target, 25, 25).with_progress(100, move |_, _| {
+            reported.fetch_add(1, Ordering::Relaxed);
+        });
+
+        let (valid_cnt, _) = std::thread::spawn(move || generator.generate())
+            .join()
+            .unwrap();
+
+        assert_eq!(valid_cnt, Generator::<5>::new(target, 25, 25).generate().0);
+        assert!(calls.load(Ordering::Relaxed) > 0);
+    }
+
+    #[test]
+    fn symmetric_images_have_one_canonical_form() {
+        let diamond = example_grids()[0].clone();
+        let canonical = diamond.canonical();
+
+        let mut image = diamond;
+        for _ in 0..4 {
+            assert_eq!(image.reflect_horizontal().canonical(), canonical);
+            image = image.rotate90();
+            assert_eq!(image.canonical(), canonical);
+        }
+        assert_eq!(image, example_grids()[0]);
+    }
+
+    #[test]
+    fn orbits_on_small_grid() {
+        // The four diamonds of area 2 on a 3x3 grid are all rotations of one another.
+        let target = Area { units: 2, half: 0 };
+        assert_eq!(Generator::<3>::new(target, 9, 9).count_orbits(), 1);
+    }
+
+    #[test]
+    fn transforms_preserve_area() {
+        let grid = example_grids()[2].clone();
+        let area = grid.loop_area().unwrap();
+
+        let rotations = [
+            grid.clone(),
+            grid.rotate90(),
+            grid.rotate90().rotate90(),
+            grid.rotate90().rotate90().rotate90(),
+        ];
+        for rotation in &rotations {
+            assert_eq!(rotation.loop_area().unwrap(), area);
+            assert_eq!(rotation.reflect_horizontal().loop_area().unwrap(), area);
+            assert_eq!(rotation.reflect_vertical().loop_area().unwrap(), area);
+        }
+
+        assert_eq!(rotations[3].rotate90(), grid);
+        assert_eq!(grid.reflect_vertical().reflect_vertical(), grid);
+        assert_eq!(rotations[2], grid.reflect_horizontal().reflect_vertical());
+    }
+
+    #[test]
+    fn central_binom_u128_extends_table() {
+        assert_eq!(central_binom_u128(20), central_binom(20) as u128);
+        for n in 0..=26 {
+            assert_eq!(central_binom_u128(n), central_binom(n) as u128);
+        }
+
+        assert_eq!(central_binom_u128(40), 107_507_208_733_336_176_461_620);
+        assert_eq!(
+            central_binom_u128(65),
+            95_067_625_827_960_698_145_584_333_020_095_113_100
+        );
+    }
+
+    #[test]
+    fn central_binom_checked_covers_table() {
+        assert_eq!(central_binom_checked(20), Some(central_binom(20)));
+        assert_eq!(central_binom_checked(26), Some(495_918_532_948_104));
+        assert_eq!(central_binom_checked(27), None);
+    }
+
+    #[test]
+    fn area_sweep_matches_targeted_search() {
+        let areas = Generator::<7>::new(Area { units: 0, half: 0 }, 49, 49).count_all_areas();
+        assert_eq!(areas[&32], 89_519_144);
+
+        let small = Generator::<3>::new(Area { units: 0, half: 0 }, 9, 9).count_all_areas();
+        for (&units, &cnt) in &small {
+            let target = Area { units, half: 0 };
+            assert_eq!(Generator::<3>::new(target, 9, 9).generate().0, cnt);
+        }
+        assert_eq!(small[&2], 24);
+    }
+
+    #[test]
+    fn length_distribution_covers_every_layout() {
+        let target = Area { units: 4, half: 0 };
+        let (valid_cnt, valid_grids) = Generator::<5>::new(target, 25, 25).generate();
+        let (cnt, lengths) = Generator::<5>::new(target, 25, 25).length_distribution();
+
+        assert_eq!(cnt, valid_cnt);
+        assert_eq!(lengths.values().sum::<usize>(), valid_grids.len());
+        // The multiplicity of each layout depends only on its length.
+        let weighted: u128 = lengths
+            .iter()
+            .map(|(&length, &layouts)| central_binom_u128(length / 2) * layouts as u128)
+            .sum();
+        assert_eq!(weighted, valid_cnt);
+    }
+
+    #[test]
+    fn valid_loops_are_recognised() {
+        use Cell::*;
+
+        for grid in example_grids() {
+            assert!(grid.is_valid_loop());
+        }
+
+        // A diamond with one side missing is an open path.
+        let open = Grid::new({
+            let mut data = [[Empty; 7]; 7];
+            data[1][1] = Forward;
+            data[1][2] = Backward;
+            data[2][1] = Backward;
+            data
+        });
+        assert!(!open.is_valid_loop());
+
+        // Two separate diamonds are two loops, not one.
+        let disjoint = Grid::new({
+            let mut data = [[Empty; 7]; 7];
+            data[1][1] = Forward;
+            data[1][2] = Backward;
+            data[2][1] = Backward;
+            data[2][2] = Forward;
+            data[4][4] = Forward;
+            data[4][5] = Backward;
+            data[5][4] = Backward;
+            data[5][5] = Forward;
+            data
+        });
+        assert!(!disjoint.is_valid_loop());
+
+        assert!(!Grid::new([[Empty; 7]; 7]).is_valid_loop());
+    }
+
+    #[test]
+    fn area_arithmetic() {
+        let a = Area { units: 1, half: 1 };
+        let b = Area { units: 2, half: 1 };
+
+        // Two halves carry into a full unit.
+        assert_eq!(a + b, Area { units: 4, half: 0 });
+        assert_eq!(a * 3, Area { units: 4, half: 1 });
+
+        // Subtraction borrows a full unit when it runs out of halves.
+        assert_eq!(
+            Area { units: 3, half: 0 } - a,
+            Some(Area { units: 1, half: 1 })
+        );
+        assert_eq!(b - a, Some(Area { units: 1, half: 0 }));
+        assert_eq!(a - b, None);
+    }
+
+    #[test]
+    fn areas_sort_by_magnitude() {
+        let area = |units, half| Area { units, half };
+        let mut areas = vec![
+            area(3, 0),
+            area(0, 1),
+            area(1, 3),
+            area(2, 1),
+            area(0, 4),
+            area(2, 0),
+        ];
+        areas.sort();
+
+        assert_eq!(
+            areas,
+            vec![
+                area(0, 1),
+                area(0, 4),
+                area(2, 0),
+                area(1, 3),
+                area(2, 1),
+                area(3, 0)
+            ]
+        );
+        assert_eq!(area(1, 0).max(area(0, 3)), area(0, 3));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn grids_round_trip_through_serde() {
+        for grid in example_grids() {
+            let json = serde_json::to_string(&grid).unwrap();
+            assert_eq!(json, serde_json::to_string(&grid.to_string()).unwrap());
+            assert_eq!(serde_json::from_str::<Grid>(&json).unwrap(), grid);
+        }
+
+        let area = example_grids()[2].loop_area().unwrap();
+        let json = serde_json::to_string(&area).unwrap();
+        assert_eq!(serde_json::from_str::<Area>(&json).unwrap(), area);
+    }
+
+    #[test]
+    fn grid_as_svg() {
+        let [diamond, _, grid3] = example_grids();
+
+        // 16 grid lines, and one line for each of the four cells in the diamond.
+        let svg = diamond.to_svg();
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("<line ").count(), 20);
+        // The top-left cell of the diamond runs from its bottom-left corner to its top-right.
+        assert!(svg.contains(r#"<line x1="50" y1="90" x2="90" y2="50"/>"#));
+
+        assert_eq!(grid3.to_svg().matches("<line ").count(), 16 + 18);
+    }
+
+    #[test]
+    fn progress_is_reported() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let target = Area { units: 4, half: 0 };
+        let (_, valid_grids) = Generator::<5>::new(target, 25, 25)
+            .with_progress(100, move |nodes, found| tx.send((nodes, found)).unwrap())
+            .generate();
+
+        let reports: Vec<_> = rx.try_iter().collect();
+        assert!(reports.len() > 1);
+        for pair in reports.windows(2) {
+            assert_eq!(pair[1].0, pair[0].0 + 100);
+            assert!(pair[1].1 >= pair[0].1);
+        }
+        assert!(reports.last().unwrap().1 <= valid_grids.len());
+    }
+
+    #[test]
+    fn cells_in_row_major_order() {
+        let grid = example_grids()[2].clone();
+        let cells: Vec<_> = grid.cells().collect();
+
+        assert_eq!(cells.len(), 49);
+        for (i, ((r, c), cell)) in cells.into_iter().enumerate() {
+            assert_eq!((r, c), (i / 7, i % 7));
+            assert_eq!(cell, grid.data[r][c]);
+        }
+    }
+
+    #[test]
+    fn loop_length_and_enclosed_cells() {
+        let [grid1, _, grid3] = example_grids();
+
+        assert_eq!(grid1.loop_length(), 4);
+        assert!(grid1.enclosed_cells().is_empty());
+
+        let enclosed = grid3.enclosed_cells();
+        assert_eq!(grid3.loop_length(), 18);
+        assert_eq!(enclosed.len(), 23);
+        assert_eq!(enclosed[..4], [(1, 2), (1, 3), (1, 4), (1, 5)]);
+        assert_eq!(enclosed.last(), Some(&(5, 4)));
+        assert!(enclosed
+            .iter()
+            .all(|(r, c)| (1..6).contains(r) && (1..6).contains(c)));
+
+        // Each enclosed cell is a full unit of area, and each segment of the loop a half unit.
+        assert_eq!(
+            Area {
+                units: enclosed.len() as u8,
+                half: grid3.loop_length(),
+            }
+            .simplify(),
+            grid3.loop_area().unwrap()
+        );
+    }
+
+    #[test]
+    fn malformed_grids_give_area_errors() {
+        // Two segments crossing at a grid point, with loose ends.
+        let crossing = Grid::<4>::from_str_art("····\n·╲╱·\n·╱╲·\n····").unwrap();
+        assert_eq!(crossing.loop_area(), Err(AreaError::LoopNotClosed));
+
+        // Two diamonds touching at a grid point, so the curve crosses itself there.
+        let eight = Grid::<4>::from_str_art("╱╲╱╲\n╲╱╲╱\n····\n····").unwrap();
+        assert_eq!(eight.loop_area(), Err(AreaError::Inconsistent));
+
+        let open = Grid::<4>::from_str_art("····\n·╱··\n····\n····").unwrap();
+        assert_eq!(open.loop_area(), Err(AreaError::LoopNotClosed));
+    }
+
+    #[test]
+    fn nested_loops_enclose_ring() {
+        // A diamond of radius 3, with one of radius 1 nested inside, both centred on the same grid
+        // point. Their areas are 18 and 2.
+        let outer = Grid::<7>::from_str_art(
+            "··╱╲···\n·╱··╲··\n╱····╲·\n╲····╱·\n·╲··╱··\n··╲╱···\n·······",
+        )
+        .unwrap();
+        let nested = Grid::<7>::from_str_art(
+            "··╱╲···\n·╱··╲··\n╱·╱╲·╲·\n╲·╲╱·╱·\n·╲··╱··\n··╲╱···\n·······",
+        )
+        .unwrap();
+
+        assert_eq!(outer.loop_area(), Ok(Area { units: 18, half: 0 }));
+        assert_eq!(nested.loop_area(), Ok(Area { units: 16, half: 0 }));
+        assert!(!nested.is_valid_loop());
+    }
+
+    #[test]
+    fn pruned_and_deduplicated_searches_agree() {
+        // A loop enclosing 6 units has at most 12 segments, each contributing at least half a unit.
+        let target = Area { units: 6, half: 0 };
+        let (cnt, mut grids) = Generator::<7>::new(target, 49, 12).generate();
+        let (dedup_cnt, mut dedup_grids) =
+            Generator::<7>::new(target, 49, 12).generate_deduplicated();
+
+        assert!(cnt > 0);
+        assert_eq!(cnt, dedup_cnt);
+
+        let key = |g: &Grid| g.to_string();
+        grids.sort_by_key(key);
+        dedup_grids.sort_by_key(key);
+        assert_eq!(grids, dedup_grids);
+    }
+
+    #[test]
+    fn unconstrained_generator_gives_puzzle_answer() {
+        let target = Area { units: 32, half: 0 };
+        let valid_cnt = Generator::<7>::new_unconstrained(target).generate_with(|_| {});
+
+        assert_eq!(valid_cnt, 89_519_144);
+    }
+
+    #[test]
+    fn grids_hash_by_their_cells() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+
+        let hash = |grid: &Grid| {
+            let mut hasher = DefaultHasher::new();
+            grid.hash(&mut hasher);
+            hasher.finish()
+        };
+
+        let grid = example_grids()[1].clone();
+        let same = Grid::new(grid.data);
+        let rotated = grid.rotate90();
+
+        assert_eq!(grid, same);
+        assert_eq!(hash(&grid), hash(&same));
+        assert_ne!(grid, rotated);
+        assert_ne!(hash(&grid), hash(&rotated));
+
+        let distinct: HashSet<Grid> = [grid, same, rotated].into_iter().collect();
+        assert_eq!(distinct.len(), 2);
+    }
+
+    #[test]
+    fn areas_as_floats() {
+        let [grid1, grid2, grid3] = example_grids();
+
+        assert_eq!(grid1.area_f64(), Ok(2.0));
+        assert_eq!(grid2.area_f64(), Ok(6.0));
+        assert_eq!(grid3.area_f64(), Ok(32.0));
+        assert_eq!(Area { units: 3, half: 3 }.to_f64(), 4.5);
+    }
+
+    #[test]
+    fn shoelace_area_matches_scan() {
+        for grid in example_grids() {
+            assert_eq!(grid.loop_area_shoelace(), grid.loop_area());
+        }
+
+        let nested = Grid::<7>::from_str_art(
+            "··╱╲···\n·╱··╲··\n╱·╱╲·╲·\n╲·╲╱·╱·\n·╲··╱··\n··╲╱···\n·······",
+        )
+        .unwrap();
+        assert_eq!(nested.loop_area_shoelace(), Ok(Area { units: 16, half: 0 }));
+
+        // Two unit diamonds side by side, and a diagonal strip enclosing half units.
+        let pair = Grid::<4>::from_str_art("╱╲╱╲\n╲╱╲╱\n····\n····").unwrap();
+        assert_eq!(pair.loop_area_shoelace(), pair.loop_area());
+        let strip = Grid::<3>::from_str_art("·╱╲\n╱·╱\n╲╱·").unwrap();
+        assert_eq!(strip.loop_area_shoelace(), strip.loop_area());
+
+        let open = Grid::<4>::from_str_art("····\n·╱··\n····\n····").unwrap();
+        assert_eq!(open.loop_area_shoelace(), Err(AreaError::LoopNotClosed));
+    }
+
+    #[test]
+    fn resumed_search_matches_uninterrupted() {
+        let target = Area { units: 6, half: 0 };
+        let (cnt, lengths) = Generator::<5>::new_unconstrained(target).length_distribution();
+        assert!(cnt > 0);
+
+        let mut generator = Generator::<5>::new_unconstrained(target);
+        let mut found = 0;
+        assert!(!generator.advance(7, |_| found += 1));
+        assert!(!generator.advance(13, |_| found += 1));
+        let state = generator.checkpoint();
+        assert_eq!(state.next_first_cell, 20);
+        assert_eq!(state.valid_cnt, generator.valid_cnt());
+
+        let mut resumed = Generator::<5>::from_checkpoint(state.clone());
+        assert_eq!(resumed.checkpoint(), state);
+        while !resumed.advance(10, |_| found += 1) {}
+        assert_eq!(resumed.valid_cnt(), cnt);
+        assert_eq!(found, lengths.values().sum::<usize>());
+
+        // Finishing from the checkpoint in one go gives the same, as does a finished search.
+        assert_eq!(
+            Generator::<5>::from_checkpoint(state).length_distribution(),
+            (cnt, lengths)
+        );
+        assert!(resumed.advance(1, |_| panic!("the search is finished")));
+    }
+
+    #[test]
+    fn regions_show_enclosed_cells() {
+        let nested = Grid::<7>::from_str_art(
+            "··╱╲···\n·╱··╲··\n╱·╱╲·╲·\n╲·╲╱·╱·\n·╲··╱··\n··╲╱···\n·······",
+        )
+        .unwrap();
+        assert_eq!(
+            nested.debug_regions(),
+            "··╱╲···\n·╱██╲··\n╱█╱╲█╲·\n╲█╲╱█╱·\n·╲██╱··\n··╲╱···\n·······\n"
+        );
+
+        for grid in example_grids() {
+            let regions = grid.debug_regions();
+            assert_eq!(
+                regions.chars().filter(|c| *c == '█').count(),
+                grid.enclosed_cells().len()
+            );
+            assert_eq!(regions.replace('█', "·"), grid.to_string());
+        }
+
+        // A row left inside the loop is flagged.
+        let open = Grid::<3>::from_str_art("╱··\n···\n···").unwrap();
+        assert_eq!(open.debug_regions(), "╱██!\n···\n···\n");
+    }
+
+    #[test]
+    fn arc_curve_counts_match_slow_model() {
+        assert_eq!(binom_u128(10, 3), 120);
+        assert_eq!(binom_u128(48, 24), central_binom_u128(24));
+        assert_eq!(
+            binom_u128(126, 63),
+            6_034_934_435_761_406_706_427_864_636_568_328_000
+        );
+        assert_eq!(binom_u128(130, 65), central_binom_u128(65));
+
+        // Loops have an even number of segments, so there are no curves with an odd multiple of
+        // π/4, and none with area 4+π/2 fit in a 4x4 grid.
+        let cases = [
+            (4, 0, 160),
+            (3, 2, 120),
+            (5, -2, 120),
+            (2, 4, 48),
+            (4, 2, 0),
+            (3, 1, 0),
+        ];
+        for (units, quarter_pis, expected) in cases {
+            let target = slow::Area::from_units_and_fraction(units, quarter_pis).unwrap();
+            let slow_cnt = slow::Generator::<4>::new(target, 16, 16).generate().len() as u128;
+            let cnt = Generator::<4>::new_unconstrained(Area { units: 0, half: 0 })
+                .count_arc_curves(target);
+
+            assert_eq!(slow_cnt, expected, "area {}", target);
+            assert_eq!(cnt, slow_cnt, "area {}", target);
+        }
+
+        // Integer areas agree with the fast model's own count.
+        let target = slow::Area::from_units_and_fraction(6, 0).unwrap();
+        assert_eq!(
+            Generator::<5>::new_unconstrained(Area { units: 0, half: 0 }).count_arc_curves(target),
+            Generator::<5>::new_unconstrained(Area { units: 6, half: 0 }).generate_with(|_| {})
+        );
+    }
+
+    #[test]
+    fn perimeter_is_a_closed_polyline() {
+        for grid in example_grids() {
+            let points = grid.perimeter_coordinates().unwrap();
+            assert_eq!(points.len(), grid.loop_length() as usize + 1);
+            assert_eq!(points.first(), points.last());
+
+            // Every step crosses a cell diagonally, and no grid point is visited twice.
+            for w in points.windows(2) {
+                assert_eq!(((w[0].0 - w[1].0).abs(), (w[0].1 - w[1].1).abs()), (1, 1));
+            }
+            let distinct: HashSet<_> = points[1..].iter().collect();
+            assert_eq!(distinct.len(), points.len() - 1);
+
+            // The polyline encloses the loop's area.
+            let twice_area: i32 = points
+                .windows(2)
+                .map(|w| w[0].0 * w[1].1 - w[1].0 * w[0].1)
+                .sum();
+            assert_eq!(Ok(twice_area.abs() as f64 / 2.0), grid.area_f64());
+        }
+
+        let diamond = Grid::<2>::from_str_art("╱╲\n╲╱").unwrap();
+        assert_eq!(
+            diamond.perimeter_coordinates(),
+            Ok(vec![(0, 1), (1, 0), (2, 1), (1, 2), (0, 1)])
+        );
+
+        let empty = Grid::<3>::from_str_art("···\n···\n···").unwrap();
+        assert_eq!(empty.perimeter_coordinates(), Ok(Vec::new()));
+        let open = Grid::<4>::from_str_art("····\n·╱··\n····\n····").unwrap();
+        assert_eq!(open.perimeter_coordinates(), Err(AreaError::LoopNotClosed));
+    }
+
+    #[test]
+    fn all_area_counts_survive_a_checkpoint() {
+        let target = Area { units: 2, half: 0 };
+        let counts = Generator::<4>::new_unconstrained(target).count_all_areas();
+
+        let mut generator = Generator::<4>::new_unconstrained(target).counting_all_areas();
+        assert!(!generator.advance(3, |_| {}));
+        let state = generator.checkpoint();
+        assert!(state.area_counts.is_some());
+
+        // Finishing on the same generator, or from the checkpoint, gives the same counts.
+        assert_eq!(generator.count_all_areas(), counts);
+        assert_eq!(
+            Generator::<4>::from_checkpoint(state).count_all_areas(),
+            counts
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "`generate_deduplicated` can't finish a search which has already been advanced"
+    )]
+    fn advanced_search_cant_be_deduplicated() {
+        let mut generator = Generator::<4>::new_unconstrained(Area { units: 2, half: 0 });
+        generator.advance(3, |_| {});
+        generator.generate_deduplicated();
+    }
+
+    #[test]
+    #[should_panic(expected = "binomial coefficient should fit in a u128")]
+    fn binom_u128_rejects_overflow() {
+        binom_u128(132, 66);
+    }
 }
@@ -0,0 +1,231 @@
+//! Drawing, parsing and checking the grids of the [fast](crate::fast) and [slow](crate::slow)
+//! models.
+//!
+//! The two models differ only in what is drawn across each cell, a straight diagonal or a
+//! quarter-circle arc, and either joins the same two corners of its cell. So the grids of both are
+//! drawn as text or SVG, parsed, and checked for closed loops in the same way. The functions here
+//! work on the cells of either, through the [GridCell] trait, and each model's `Grid` delegates to
+//! them.
+
+use crate::search::{Segment, Slant};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// The contents of a cell in the grid of one of the models, and how to draw it.
+pub trait GridCell: Segment {
+    /// The attributes of the SVG group holding the segments, setting how they are stroked.
+    const SVG_STYLE: &'static str;
+
+    /// The character drawing this cell in a text drawing of the grid.
+    fn glyph(self) -> char;
+
+    /// The SVG element drawing the segment in this cell, whose top-left corner is at `(x, y)` and
+    /// whose sides are `size` long, or `None` for the empty cell.
+    fn svg_element(self, x: usize, y: usize, size: usize) -> Option<String>;
+}
+
+/// An error returned when attempting to calculate the area enclosed by a loop in a `Grid`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum AreaError {
+    /// Some curve segment has a loose end, which no other segment joins onto.
+    LoopNotClosed,
+    /// The segments don't divide the grid consistently into inside and outside, so the area
+    /// can't be trusted. This happens when segments cross or touch, with four of them meeting at
+    /// a grid point, or when a row is left inside the loop.
+    Inconsistent,
+}
+
+/// An error returned when parsing a `Grid` from a text drawing.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// The drawing does not have `N` rows. Contains the number of rows found.
+    WrongRowCount(usize),
+    /// A row of the drawing does not have `N` cells. Contains the (zero-indexed) row and the number
+    /// of cells found in it.
+    WrongRowLength(usize, usize),
+    /// The drawing contains a character which is not a cell glyph.
+    UnknownGlyph(char),
+}
+
+/// The two grid points joined by the segment in the cell at row `r` and column `c`, as
+/// `(row, col)` pairs zero-indexed from the top-left of the grid, or `None` if the cell is empty.
+fn segment_ends<C: Segment>(
+    r: usize,
+    c: usize,
+    cell: C,
+) -> Option<((usize, usize), (usize, usize))> {
+    if C::along(Slant::Forward).contains(&cell) {
+        Some(((r + 1, c), (r, c + 1)))
+    } else if C::along(Slant::Backward).contains(&cell) {
+        Some(((r, c), (r + 1, c + 1)))
+    } else {
+        None
+    }
+}
+
+/// Scan a row of a grid from the left, toggling between outside and inside the loop at every
+/// segment. Returns whether the scan is outside the loop on reaching each cell, and whether it is
+/// outside again at the end of the row, as it must be if the segments divide the grid
+/// consistently.
+pub fn scan_row<C: Segment, const N: usize>(row: &[C; N]) -> ([bool; N], bool) {
+    let mut outside = [true; N];
+    let mut at_end = true;
+    for (c, cell) in row.iter().enumerate() {
+        outside[c] = at_end;
+        if *cell != C::EMPTY {
+            at_end = !at_end;
+        }
+    }
+
+    (outside, at_end)
+}
+
+/// Parse the cells of a grid from a text drawing, with one line per row and one glyph per cell.
+pub fn parse<C: GridCell, const N: usize>(s: &str) -> Result<[[C; N]; N], ParseError> {
+    let rows: Vec<&str> = s.lines().collect();
+    if rows.len() != N {
+        return Err(ParseError::WrongRowCount(rows.len()));
+    }
+
+    let all_cells: Vec<C> = std::iter::once(C::EMPTY)
+        .chain(C::along(Slant::Forward).iter().copied())
+        .chain(C::along(Slant::Backward).iter().copied())
+        .collect();
+
+    let mut data = [[C::EMPTY; N]; N];
+    for (r, row) in rows.iter().enumerate() {
+        let glyphs: Vec<char> = row.chars().collect();
+        if glyphs.len() != N {
+            return Err(ParseError::WrongRowLength(r, glyphs.len()));
+        }
+
+        for (c, glyph) in glyphs.into_iter().enumerate() {
+            data[r][c] = *all_cells
+                .iter()
+                .find(|cell| cell.glyph() == glyph)
+                .ok_or(ParseError::UnknownGlyph(glyph))?;
+        }
+    }
+
+    Ok(data)
+}
+
+/// Draw the cells of a grid as text, with one line per row and one glyph per cell.
+pub fn draw<C: GridCell, const N: usize>(
+    data: &[[C; N]; N],
+    f: &mut std::fmt::Formatter<'_>,
+) -> std::fmt::Result {
+    for row in data {
+        for cell in row {
+            write!(f, "{}", cell.glyph())?;
+        }
+        writeln!(f)?;
+    }
+
+    Ok(())
+}
+
+/// Whether the segments in the cells of a grid join up into a single closed loop, without any
+/// loose ends, branches or self-intersections.
+pub fn is_valid_loop<C: Segment, const N: usize>(data: &[[C; N]; N]) -> bool {
+    let mut segments = Vec::new();
+    for (r, row) in data.iter().enumerate() {
+        for (c, cell) in row.iter().enumerate() {
+            segments.extend(segment_ends(r, c, *cell));
+        }
+    }
+
+    let mut ends: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+    for (i, (a, b)) in segments.iter().enumerate() {
+        ends.entry(*a).or_default().push(i);
+        ends.entry(*b).or_default().push(i);
+    }
+
+    // A single loop passes through each grid point at most once, so there must be exactly two
+    // segments at every point it visits.
+    if segments.is_empty() || ends.values().any(|at| at.len() != 2) {
+        return false;
+    }
+
+    // Trace the loop from the first segment, and check it covers every segment.
+    let (mut segment, mut point) = (0, segments[0].1);
+    let mut traced = 0;
+    loop {
+        traced += 1;
+        let at = &ends[&point];
+        segment = if at[0] == segment { at[1] } else { at[0] };
+        if segment == 0 {
+            break;
+        }
+
+        let (a, b) = segments[segment];
+        point = if a == point { b } else { a };
+    }
+
+    traced == segments.len()
+}
+
+/// Check that exactly zero or two segments end at every grid point, so that the segments join
+/// up into closed curves which neither cross nor touch.
+pub fn check_segment_ends<C: Segment, const N: usize>(data: &[[C; N]; N]) -> Result<(), AreaError> {
+    // The number of segments ending at each grid point, indexed from the top-left of the grid.
+    let mut ends = vec![vec![0u8; N + 1]; N + 1];
+    for (r, row) in data.iter().enumerate() {
+        for (c, cell) in row.iter().enumerate() {
+            if let Some((a, b)) = segment_ends(r, c, *cell) {
+                ends[a.0][a.1] += 1;
+                ends[b.0][b.1] += 1;
+            }
+        }
+    }
+
+    if ends.iter().flatten().any(|cnt| cnt % 2 == 1) {
+        Err(AreaError::LoopNotClosed)
+    } else if ends.iter().flatten().any(|cnt| *cnt > 2) {
+        Err(AreaError::Inconsistent)
+    } else {
+        Ok(())
+    }
+}
+
+/// Draw the cells of a grid as a standalone SVG document. The grid lines are drawn faintly, and
+/// each segment as its cell type describes.
+pub fn to_svg<C: GridCell, const N: usize>(data: &[[C; N]; N]) -> String {
+    const CELL: usize = 40;
+    const MARGIN: usize = 10;
+    let size = N * CELL + 2 * MARGIN;
+    let at = |i: usize| MARGIN + i * CELL;
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{size}\" height=\"{size}\" \
+         viewBox=\"0 0 {size} {size}\">\n"
+    );
+
+    svg.push_str("  <g stroke=\"#ccc\" stroke-width=\"1\">\n");
+    for i in 0..=N {
+        let (start, end, pos) = (at(0), at(N), at(i));
+        writeln!(
+            svg,
+            "    <line x1=\"{start}\" y1=\"{pos}\" x2=\"{end}\" y2=\"{pos}\"/>"
+        )
+        .unwrap();
+        writeln!(
+            svg,
+            "    <line x1=\"{pos}\" y1=\"{start}\" x2=\"{pos}\" y2=\"{end}\"/>"
+        )
+        .unwrap();
+    }
+    svg.push_str("  </g>\n");
+
+    writeln!(svg, "  <g {}>", C::SVG_STYLE).unwrap();
+    for (r, row) in data.iter().enumerate() {
+        for (c, cell) in row.iter().enumerate() {
+            if let Some(element) = cell.svg_element(at(c), at(r), CELL) {
+                writeln!(svg, "    {element}").unwrap();
+            }
+        }
+    }
+    svg.push_str("  </g>\n</svg>\n");
+
+    svg
+}
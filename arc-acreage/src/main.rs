@@ -9,26 +9,165 @@
 //! As required in the original puzzle, there are 89,519,144 closed curves of area 32.
 
 pub mod fast;
+pub mod grid;
+pub mod search;
 pub mod slow;
 
-fn main() {
-    fast();
+/// The side length of the grid used in the puzzle.
+const GRID_SIZE: usize = 7;
+
+/// The area enclosed by the curves counted in the puzzle.
+const PUZZLE_AREA: u8 = 32;
+
+const USAGE: &str = "\
+Usage: arc-acreage [--fast | --slow] [--area AREA] [--max-inner-cells N] [--max-length N]
+
+Options:
+  --fast                  Use the fast, straight-line model of the curves (the default)
+  --slow                  Use the slow, quarter-circle model of the curves
+  --area AREA             The whole-number area enclosed by the curves (default 32)
+  --max-inner-cells N     Prune curves with more than N segments away from the outer rim of the grid
+                          (default 6 when slow with area 32, otherwise 49)
+  --max-length N          Prune curves with more than N segments
+                          (default 26 when slow with area 32, otherwise 49)
+";
+
+/// Which model of the curves to search with.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Model {
+    Fast,
+    Slow,
 }
 
-#[allow(dead_code)]
-fn fast() {
-    use fast::*;
+/// The search to run, as chosen on the command line.
+#[derive(Debug, PartialEq, Eq)]
+struct Config {
+    model: Model,
+    area: u8,
+    max_inner_cells: u8,
+    max_length: u8,
+}
 
-    let target_area = Area { units: 32, half: 0 };
-    let (valid_cnt, valid_grids) = Generator::new(target_area, 49, 49).generate();
+/// An error returned when the command-line arguments don't describe a valid search.
+#[derive(Debug, PartialEq, Eq)]
+enum ArgError {
+    /// An argument which isn't recognised.
+    Unknown(String),
+    /// An option which should be followed by a number is missing one, or has something else.
+    BadValue(String),
+    /// No closed curve in the grid can enclose the requested area.
+    Unachievable(u8),
+}
 
-    // Double check validity.
-    for valid in &valid_grids {
-        if !(valid.loop_area().expect("should be valid").simplify() == target_area.simplify()) {
-            println!("{:?}", valid);
-            println!("area: {:?}", valid.loop_area());
+impl std::fmt::Display for ArgError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArgError::Unknown(arg) => write!(f, "unknown argument '{}'", arg),
+            ArgError::BadValue(opt) => write!(f, "'{}' should be followed by a number", opt),
+            ArgError::Unachievable(area) => write!(
+                f,
+                "no curve in a {n}x{n} grid encloses an area of {}",
+                area,
+                n = GRID_SIZE
+            ),
         }
     }
+}
+
+/// Whether some closed curve in an `n`x`n` grid encloses an area of `area` units, for `n` of at
+/// least 2.
+///
+/// Every grid point on a curve has the same parity of row plus column, so the polygon through
+/// them encloses an even number of units. A whole-number area of arcs equals its polygon's, so
+/// the same holds in the slow model. The smallest curve loops around a single grid point,
+/// enclosing 2 units, and counting every curve on the grids up to 7x7 shows that the largest
+/// encloses $(n-1)^2$ units, rounded up to the next even number.
+fn area_is_achievable(area: u8, n: usize) -> bool {
+    let area = area as usize;
+    let max = (n - 1).pow(2).next_multiple_of(2);
+    area >= 2 && area.is_multiple_of(2) && area <= max
+}
+
+/// Build a `Config` from the command-line arguments, not including the program name. Anything
+/// not given takes the value used to solve the puzzle.
+fn parse_args(args: &[String]) -> Result<Config, ArgError> {
+    let mut model = Model::Fast;
+    let mut area = PUZZLE_AREA;
+    let mut max_inner_cells = None;
+    let mut max_length = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .and_then(|v| v.parse().ok())
+                .ok_or_else(|| ArgError::BadValue(arg.clone()))
+        };
+
+        match arg.as_str() {
+            "--fast" => model = Model::Fast,
+            "--slow" => model = Model::Slow,
+            "--area" => area = value()?,
+            "--max-inner-cells" => max_inner_cells = Some(value()?),
+            "--max-length" => max_length = Some(value()?),
+            _ => return Err(ArgError::Unknown(arg.clone())),
+        }
+    }
+
+    if !area_is_achievable(area, GRID_SIZE) {
+        return Err(ArgError::Unachievable(area));
+    }
+
+    // The slow model is only feasible with constraints, but those were proved for the puzzle's
+    // area alone. Any other area is searched without them.
+    let (default_inner_cells, default_length) = match model {
+        Model::Slow if area == PUZZLE_AREA => (6, 26),
+        _ => (49, 49),
+    };
+
+    Ok(Config {
+        model,
+        area,
+        max_inner_cells: max_inner_cells.unwrap_or(default_inner_cells),
+        max_length: max_length.unwrap_or(default_length),
+    })
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let config = match parse_args(&args) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            eprint!("{}", USAGE);
+            std::process::exit(1);
+        }
+    };
+
+    match config.model {
+        Model::Fast => fast(&config),
+        Model::Slow => slow(&config),
+    }
+}
+
+fn fast(config: &Config) {
+    use fast::*;
+
+    let target_area = Area {
+        units: config.area,
+        half: 0,
+    };
+    let valid_cnt =
+        Generator::<GRID_SIZE>::new(target_area, config.max_inner_cells, config.max_length)
+            .with_progress(1_000_000, print_progress)
+            .generate_with(|valid| {
+                // Double check validity.
+                if valid.loop_area().expect("should be valid").simplify() != target_area.simplify()
+                {
+                    println!("{:?}", valid);
+                    println!("area: {:?}", valid.loop_area());
+                }
+            });
 
     println!();
     println!(
@@ -37,21 +176,23 @@ fn fast() {
     );
 }
 
-#[allow(dead_code)]
-fn slow() {
+fn slow(config: &Config) {
     use slow::*;
 
     let target_area = Area {
-        units: 32,
+        units: config.area,
         small: 0,
         large: 0,
     };
 
-    let valid_grids = Generator::new(target_area, 6, 26).generate();
+    let valid_grids =
+        Generator::<GRID_SIZE>::new(target_area, config.max_inner_cells, config.max_length)
+            .with_progress(1_000_000, print_progress)
+            .generate();
 
     // Double check validity.
     for valid in &valid_grids {
-        if !(valid.loop_area().expect("should be valid").simplify() == target_area.simplify()) {
+        if valid.loop_area().expect("should be valid").simplify() != target_area.simplify() {
             println!("{:?}", valid);
             println!("area: {:?}", valid.loop_area());
         }
@@ -64,3 +205,112 @@ fn slow() {
         target_area
     );
 }
+
+fn print_progress(nodes: usize, found: usize) {
+    println!("{} nodes visited; {} valid grids found", nodes, found);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Config, ArgError> {
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        parse_args(&args)
+    }
+
+    #[test]
+    fn defaults_match_the_puzzle() {
+        assert_eq!(
+            parse(&[]),
+            Ok(Config {
+                model: Model::Fast,
+                area: 32,
+                max_inner_cells: 49,
+                max_length: 49,
+            })
+        );
+        assert_eq!(
+            parse(&["--slow"]),
+            Ok(Config {
+                model: Model::Slow,
+                area: 32,
+                max_inner_cells: 6,
+                max_length: 26,
+            })
+        );
+    }
+
+    #[test]
+    fn slow_defaults_only_apply_to_the_puzzle_area() {
+        assert_eq!(
+            parse(&["--slow", "--area", "20"]),
+            Ok(Config {
+                model: Model::Slow,
+                area: 20,
+                max_inner_cells: 49,
+                max_length: 49,
+            })
+        );
+    }
+
+    #[test]
+    fn arguments_fill_in_config() {
+        assert_eq!(
+            parse(&["--area", "32", "--fast"]),
+            Ok(Config {
+                model: Model::Fast,
+                area: 32,
+                max_inner_cells: 49,
+                max_length: 49,
+            })
+        );
+        assert_eq!(
+            parse(&["--slow", "--area", "6", "--max-length", "12"]),
+            Ok(Config {
+                model: Model::Slow,
+                area: 6,
+                max_inner_cells: 49,
+                max_length: 12,
+            })
+        );
+    }
+
+    #[test]
+    fn bad_arguments_are_rejected() {
+        assert_eq!(parse(&["--big"]), Err(ArgError::Unknown("--big".into())));
+        assert_eq!(parse(&["--area"]), Err(ArgError::BadValue("--area".into())));
+        assert_eq!(
+            parse(&["--area", "lots"]),
+            Err(ArgError::BadValue("--area".into()))
+        );
+        assert_eq!(parse(&["--area", "1"]), Err(ArgError::Unachievable(1)));
+        assert_eq!(parse(&["--area", "33"]), Err(ArgError::Unachievable(33)));
+        assert_eq!(parse(&["--area", "38"]), Err(ArgError::Unachievable(38)));
+        assert_eq!(parse(&["--area", "48"]), Err(ArgError::Unachievable(48)));
+    }
+
+    #[test]
+    fn achievable_areas_are_within_bounds() {
+        fn check<const N: usize>() {
+            let target = fast::Area { units: 2, half: 0 };
+            let areas = fast::Generator::<N>::new(target, 49, 49).count_all_areas();
+            let (min, max) = (*areas.keys().min().unwrap(), *areas.keys().max().unwrap());
+
+            for area in 0..=max + 2 {
+                assert_eq!(
+                    area_is_achievable(area, N),
+                    areas.contains_key(&area),
+                    "area {area} in a {N}x{N} grid"
+                );
+            }
+            assert!(!area_is_achievable(min - 1, N));
+            assert!(!area_is_achievable(max + 1, N));
+        }
+
+        check::<3>();
+        check::<4>();
+        check::<5>();
+        check::<6>();
+    }
+}
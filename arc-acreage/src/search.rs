@@ -0,0 +1,496 @@
+//! The backtracking search shared by the [fast](crate::fast) and [slow](crate::slow) generators.
+//!
+//! Both generators build up a loop one cell at a time, stepping diagonally across each cell from
+//! one grid point to the next, until the loop returns to where it started. They differ only in
+//! what can be drawn across a cell: the fast generator draws a straight diagonal, while the slow
+//! generator draws one of the two quarter-circle arcs joining the same corners. The search itself
+//! is generic over a [Segment] describing this, and leaves it to the generators to decide what to
+//! do with each loop it closes.
+
+/// The largest side length of grid supported. Counts of cells and segments, and grid coordinates,
+/// are kept in `u8`s, which the $N^2$ cells of a larger grid could overflow.
+pub const MAX_GRID_SIZE: usize = 15;
+
+/// The diagonal of a cell along which a curve segment runs.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Slant {
+    /// From the bottom-left corner of the cell to the top-right (╱).
+    Forward,
+    /// From the top-left corner of the cell to the bottom-right (╲).
+    Backward,
+}
+
+/// The contents of a cell in a grid explored by a [LoopSearch].
+pub trait Segment: Copy + Eq + 'static {
+    /// The empty cell.
+    const EMPTY: Self;
+
+    /// The segments which run along the `slant` diagonal, in the order the search tries them.
+    fn along(slant: Slant) -> &'static [Self];
+}
+
+/// A callback reporting the progress of a [LoopSearch], with the number of nodes visited and the
+/// number of valid loops found so far.
+pub struct Progress {
+    /// The number of nodes visited between calls.
+    interval: usize,
+    callback: Box<dyn FnMut(usize, usize) + Send>,
+}
+
+impl std::fmt::Debug for Progress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Progress")
+            .field("interval", &self.interval)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The state of a backtracking search for closed loops in an NxN grid of `C`s.
+#[derive(Debug)]
+pub struct LoopSearch<C, const N: usize> {
+    /// The maximum number of inner cells (i.e. not part of the outer boundary of the grid) we can
+    /// have forming part of the curve. This constraint is useful to prune a very large number of
+    /// search paths, assuming we can prove it rigorously for our desired target area.
+    pub(crate) max_inner_cells: u8,
+    /// The maximum length of the loop (in segments). This constraint is useful to prune some search
+    /// paths, assuming we can prove it rigorously for our target area.
+    pub(crate) max_length: u8,
+    /// The current state of the grid.
+    data: [[C; N]; N],
+    /// Whether we have placed something in each cell of the grid so far during the backtracking
+    /// algorithm.
+    placed: [[bool; N]; N],
+    /// Tracks the number of placed cells; used to ensure backtracking doesn't recurse forever.
+    placed_cnt: u8,
+    /// The order of placements made in the grid. When we backtrack, we pop off elements and undo
+    /// those moves. The first tuple is the coordinate of the cell being placed. The second element
+    /// is the coordinates of the head before we placed this move (for undoing).
+    moves: Vec<((u8, u8), (u8, u8))>,
+    /// The coordinates of the loop's starting point, used to determine when we have closed the
+    /// loop. Coordinates are on the grid lines, zero-indexed from the top-left of the grid.
+    start: (u8, u8),
+    /// The location of the head of the loop we are generating. Coordinates are on the grid lines.
+    head: (u8, u8),
+    /// The number of closed loops accepted so far.
+    valid_layouts: usize,
+    calls: usize,
+    /// Where to report progress, if anywhere.
+    progress: Option<Progress>,
+    /// The number of cells we have placed not on the outer rim of the grid. This constraint is
+    /// useful to prune a large number of search paths, assuming we can prove it rigorously for our
+    /// target area.
+    inner_cells: usize,
+    /// Twice the signed area of the polygon through the grid points the loop has visited so far,
+    /// by the shoelace formula. Each segment placed adds its term, and unplacing it takes the term
+    /// off again, so that the area of a closed loop is known without rescanning the grid.
+    twice_signed_area: i32,
+}
+
+impl<C: Segment, const N: usize> LoopSearch<C, N> {
+    /// The side length of the grid, in the type used for coordinates.
+    const SIZE: u8 = N as u8;
+
+    /// Create a new search over an empty grid. `N` may be at most [MAX_GRID_SIZE].
+    pub fn new(max_inner_cells: u8, max_length: u8) -> Self {
+        const {
+            assert!(
+                N <= MAX_GRID_SIZE,
+                "grids larger than 15x15 are not supported"
+            )
+        };
+
+        Self {
+            max_inner_cells,
+            max_length,
+            data: [[C::EMPTY; N]; N],
+            placed: [[false; N]; N],
+            placed_cnt: 0,
+            moves: Vec::with_capacity(N * N),
+            start: (0, 0),
+            head: (0, 0),
+            valid_layouts: 0,
+            calls: 0,
+            progress: None,
+            inner_cells: 0,
+            twice_signed_area: 0,
+        }
+    }
+
+    /// Call `callback` every `interval` nodes of the search, with the number of nodes visited and
+    /// the number of valid loops found so far.
+    pub fn set_progress(
+        &mut self,
+        interval: usize,
+        callback: impl FnMut(usize, usize) + Send + 'static,
+    ) {
+        self.progress = Some(Progress {
+            interval,
+            callback: Box::new(callback),
+        });
+    }
+
+    /// The cells of the grid as they currently stand.
+    pub fn cells(&self) -> &[[C; N]; N] {
+        &self.data
+    }
+
+    /// The number of segments placed so far, which is the length of the loop once it is closed.
+    pub fn length(&self) -> u8 {
+        self.placed_cnt
+    }
+
+    /// Twice the area of the polygon whose corners are the grid points the loop passes through,
+    /// once the loop is closed. When segments are straight diagonals this is twice the area the
+    /// loop encloses; segments which curve bulge in or out of the polygon. It is tracked as cells
+    /// are placed, so takes constant time.
+    pub fn polygon_area_doubled(&self) -> u32 {
+        self.twice_signed_area.unsigned_abs()
+    }
+
+    /// Every possible first cell of a loop, in the order the search tries them.
+    pub fn first_cells() -> Vec<(u8, u8, C)> {
+        let mut first_cells = Vec::new();
+        for r in 0..Self::SIZE {
+            for c in 0..Self::SIZE {
+                for slant in [Slant::Forward, Slant::Backward] {
+                    for &cell in C::along(slant) {
+                        first_cells.push((r, c, cell));
+                    }
+                }
+            }
+        }
+
+        first_cells
+    }
+
+    /// Search for every closed loop in the grid, calling `closed` each time one is formed. The
+    /// loop is left in place for `closed` to inspect, and it should return whether the loop is
+    /// one we are looking for, which is used only to report progress.
+    pub fn run(&mut self, closed: &mut impl FnMut(&Self) -> bool) {
+        self.next_cell(closed);
+    }
+
+    /// Same as [run](LoopSearch::run), but only searches the loops whose first cell is `cell`, at
+    /// row `r` and column `c`. Together, the searches from each of the
+    /// [first_cells](LoopSearch::first_cells) cover exactly the loops that `run` finds. The grid
+    /// is left empty afterwards, so the search can be run again.
+    pub fn run_from(&mut self, r: u8, c: u8, cell: C, closed: &mut impl FnMut(&Self) -> bool) {
+        // When searching from every first cell in turn, every cell before this one has already
+        // been tried as the first cell, and is marked as placed so the loop never returns there.
+        let tried = r as usize * N + c as usize;
+        for i in 0..tried {
+            self.placed[i / N][i % N] = true;
+        }
+
+        if self.place_first(r, c, cell) {
+            self.next_cell(closed);
+            self.unplace();
+        }
+
+        for i in 0..tried {
+            self.placed[i / N][i % N] = false;
+        }
+    }
+
+    /// Same as [run](LoopSearch::run), but without the pruning which stops a loop from passing
+    /// through any cell already tried as a first cell. Every loop is then found once for each of
+    /// its cells it can be started from, rather than exactly once, so `closed` should deduplicate
+    /// them. This is much slower, and only useful to check that the pruning doesn't miss loops.
+    pub fn run_unpruned(&mut self, closed: &mut impl FnMut(&Self) -> bool) {
+        for (r, c, cell) in Self::first_cells() {
+            if self.place_first(r, c, cell) {
+                self.next_cell(closed);
+                self.unplace();
+            }
+        }
+    }
+
+    fn next_cell<F: FnMut(&Self) -> bool>(&mut self, closed: &mut F) {
+        self.calls += 1;
+        if let Some(progress) = &mut self.progress {
+            if self.calls.is_multiple_of(progress.interval) {
+                (progress.callback)(self.calls, self.valid_layouts);
+            }
+        }
+
+        if self.moves.is_empty() {
+            // Try every possibility for the first cell.
+            for r in 0..Self::SIZE {
+                for c in 0..Self::SIZE {
+                    for slant in [Slant::Forward, Slant::Backward] {
+                        for &cell in C::along(slant) {
+                            if !self.place_first(r, c, cell) {
+                                continue;
+                            }
+
+                            self.next_cell(closed);
+                            self.unplace();
+                        }
+                    }
+
+                    // Unlike with non-first cells, we want to maintain the flag that marks
+                    // this as placed, because we don't want the loop to ever come back here.
+                    self.placed[r as usize][c as usize] = true;
+                    assert!(self.data == [[C::EMPTY; N]; N]);
+                }
+            }
+        } else {
+            // Get the last cell that we placed.
+            let ((pr, pc), _) = *self.moves.last().expect("should be non-empty");
+            let p_cell = self.data[pr as usize][pc as usize];
+            assert!(p_cell != C::EMPTY);
+
+            let mut moves = Vec::with_capacity(6);
+
+            // Consider the current head. There are four cells surrounding it. Establish from the
+            // `placed` grid which of these we can move to next.
+            let (hr, hc) = self.head;
+
+            for dr in [-1, 1] {
+                let nr = hr as i32 + dr;
+                if !(0..=N as i32).contains(&nr) {
+                    continue;
+                }
+                let nr = nr as u8;
+
+                for dc in [-1, 1] {
+                    let nc = hc as i32 + dc;
+                    if !(0..=N as i32).contains(&nc) {
+                        continue;
+                    }
+                    let nc = nc as u8;
+
+                    let ncellr = if dr == 1 { nr - 1 } else { nr };
+                    let ncellc = if dc == 1 { nc - 1 } else { nc };
+
+                    // Check that the proposed new cell location isn't already populated.
+                    if self.placed[ncellr as usize][ncellc as usize] {
+                        continue;
+                    }
+
+                    // Push the relevant moves into the list.
+                    let slant = match (dr, dc) {
+                        (-1, -1) | (1, 1) => Slant::Backward,
+                        (-1, 1) | (1, -1) => Slant::Forward,
+                        _ => unreachable!(),
+                    };
+                    for &cell in C::along(slant) {
+                        moves.push((ncellr, ncellc, cell, nr, nc));
+                    }
+                }
+            }
+
+            // Iterate the moves
+            for (ncellr, ncellc, n_cell, nr, nc) in moves {
+                // Check if the current possibility causes a self-intersection. If so, continue.
+                let mut c = 0_u8;
+
+                // Top-left
+                if nr > 0 && nc > 0 && self.data[nr as usize - 1][nc as usize - 1] != C::EMPTY {
+                    c += 1;
+                }
+                // Top-right
+                if nr > 0 && nc < Self::SIZE && self.data[nr as usize - 1][nc as usize] != C::EMPTY
+                {
+                    c += 1;
+                }
+                // Bottom-left
+                if nr < Self::SIZE && nc > 0 && self.data[nr as usize][nc as usize - 1] != C::EMPTY
+                {
+                    c += 1;
+                }
+                // Bottom-right
+                if nr < Self::SIZE
+                    && nc < Self::SIZE
+                    && self.data[nr as usize][nc as usize] != C::EMPTY
+                {
+                    c += 1;
+                }
+
+                if c >= 2 {
+                    continue;
+                }
+
+                // Check if this possibility closes the loop. If so, hand it over to be checked.
+                // The current `placed_cnt` must have odd parity if adding this possibility would
+                // close the loop, because a closed loop must have even parity.
+                if nr == self.start.0 && nc == self.start.1 {
+                    assert_eq!(c, 1);
+
+                    self.place(ncellr, ncellc, n_cell, nr, nc);
+                    assert!(self.placed_cnt.is_multiple_of(2));
+
+                    if closed(self) {
+                        self.valid_layouts += 1;
+                    }
+
+                    // The loop can't carry on through its starting point, as it would cross
+                    // itself, so there is nothing more to search here.
+                    self.unplace();
+                    continue;
+                }
+
+                if self.placed_cnt + 1 > self.max_length {
+                    continue;
+                }
+
+                // Place the current possibility
+                self.place(ncellr, ncellc, n_cell, nr, nc);
+
+                if self.inner_cells <= self.max_inner_cells as usize {
+                    self.next_cell(closed);
+                }
+
+                self.unplace();
+            }
+        }
+    }
+
+    /// Place `cell` at row `r` and column `c` as the first cell of the loop. Returns `false`, and
+    /// places nothing, if the loop would start from a corner of the grid.
+    fn place_first(&mut self, r: u8, c: u8, cell: C) -> bool {
+        let (start, head) = if C::along(Slant::Forward).contains(&cell) {
+            ((r + 1, c), (r, c + 1))
+        } else {
+            ((r, c), (r + 1, c + 1))
+        };
+
+        if start == (0, 0)
+            || start == (0, Self::SIZE)
+            || start == (Self::SIZE, 0)
+            || start == (Self::SIZE, Self::SIZE)
+        {
+            return false;
+        }
+
+        self.head = start;
+        self.start = start;
+        self.place(r, c, cell, head.0, head.1);
+
+        true
+    }
+
+    fn place(&mut self, row: u8, col: u8, c: C, headr: u8, headc: u8) {
+        let cell = &mut self.data[row as usize][col as usize];
+        let placed = &mut self.placed[row as usize][col as usize];
+
+        assert!(!*placed);
+
+        *cell = c;
+        *placed = true;
+        self.placed_cnt += 1;
+        self.moves.push(((row, col), self.head));
+        self.twice_signed_area += shoelace_term(self.head, (headr, headc));
+        self.head = (headr, headc);
+
+        if row > 0 && row < Self::SIZE - 1 && col > 0 && col < Self::SIZE - 1 {
+            self.inner_cells += 1;
+        }
+    }
+
+    fn unplace(&mut self) {
+        let ((row, col), old_head) = self
+            .moves
+            .pop()
+            .expect("should never call `unplace` with nothing to unplace");
+        let cell = &mut self.data[row as usize][col as usize];
+        let placed = &mut self.placed[row as usize][col as usize];
+        assert!(*placed);
+
+        *cell = C::EMPTY;
+        *placed = false;
+        self.placed_cnt -= 1;
+        self.twice_signed_area -= shoelace_term(old_head, self.head);
+        self.head = old_head;
+
+        if row > 0 && row < Self::SIZE - 1 && col > 0 && col < Self::SIZE - 1 {
+            self.inner_cells -= 1;
+        }
+    }
+}
+
+/// The term of the shoelace formula for the polygon edge from grid point `a` to `b`, given as
+/// `(row, column)` pairs.
+fn shoelace_term(a: (u8, u8), b: (u8, u8)) -> i32 {
+    a.1 as i32 * b.0 as i32 - b.1 as i32 * a.0 as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fast, slow};
+
+    #[test]
+    fn both_models_find_the_same_loops() {
+        // Every layout of `n` diagonals found with the fast model can be drawn with arcs in 2^n
+        // ways, each of which the slow model finds separately.
+        let mut fast_loops = 0_usize;
+        LoopSearch::<fast::Cell, 4>::new(16, 16).run(&mut |search| {
+            fast_loops += 1 << search.length();
+            true
+        });
+
+        let mut slow_loops = 0;
+        LoopSearch::<slow::Cell, 4>::new(16, 16).run(&mut |_| {
+            slow_loops += 1;
+            true
+        });
+
+        assert!(fast_loops > 0);
+        assert_eq!(slow_loops, fast_loops);
+    }
+
+    #[test]
+    fn generators_agree_on_integer_areas() {
+        // The curves of integer area are those with as many arcs bulging outwards as inwards, and
+        // the fast generator counts these by multiplicity.
+        for units in [2, 4, 6] {
+            let (fast_cnt, _) =
+                fast::Generator::<4>::new(fast::Area { units, half: 0 }, 16, 16).generate();
+            let target = slow::Area {
+                units,
+                small: 0,
+                large: 0,
+            };
+            let slow_grids = slow::Generator::<4>::new(target, 16, 16).generate();
+
+            assert_eq!(slow_grids.len() as u128, fast_cnt);
+        }
+    }
+
+    #[test]
+    fn tracked_area_matches_loop_area() {
+        let mut closures = 0;
+        LoopSearch::<fast::Cell, 5>::new(25, 25).run(&mut |search| {
+            let doubled = search.polygon_area_doubled();
+            let area = fast::Grid::new(*search.cells()).loop_area().unwrap();
+            assert_eq!(doubled, 2 * area.units as u32 + area.half as u32);
+
+            closures += 1;
+            true
+        });
+
+        assert!(closures > 0);
+    }
+
+    #[test]
+    fn run_from_leaves_the_grid_empty() {
+        let mut from_first_cells = 0;
+        let mut search = LoopSearch::<fast::Cell, 4>::new(16, 16);
+        for (r, c, cell) in LoopSearch::<fast::Cell, 4>::first_cells() {
+            search.run_from(r, c, cell, &mut |_| {
+                from_first_cells += 1;
+                true
+            });
+            assert_eq!(search.placed, [[false; 4]; 4]);
+        }
+
+        // A full search on the same grid still finds every loop.
+        let mut from_run = 0;
+        search.run(&mut |_| {
+            from_run += 1;
+            true
+        });
+        assert_eq!(from_run, from_first_cells);
+    }
+}
@@ -13,11 +13,17 @@
 //! grid, and also curves above a threshold length. If we can prove constraints that curves of our
 //! desired area must obey, then we can use these to reduce the search space.
 
+use crate::fast;
+use crate::grid::{self, GridCell};
+pub use crate::grid::{AreaError, ParseError};
+use crate::search::{LoopSearch, Segment, Slant, MAX_GRID_SIZE};
+
 /// A cell in the grid.
 ///
 /// The non-empty cells have quarter-circle arcs drawn in them, and are denoted by the corner of
 /// the cell which contains the quarter-circle segment.
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Cell {
     Empty,
     TopLeft,
@@ -27,6 +33,7 @@ pub enum Cell {
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Area {
     /// The number of full units.
     pub units: u8,
@@ -49,22 +56,71 @@ impl Area {
         }
     }
 
+    /// Create the area of `units` plus `quarter_pis` lots of π/4, which may be negative, as
+    /// `Display` writes it. Each negative π/4 comes from a small piece, $1 - \pi/4$, and so
+    /// carries one of the units with it. Returns `None` if there aren't enough units for this,
+    /// i.e. if the area can't be made of non-negative numbers of units, small and large pieces.
+    pub fn from_units_and_fraction(units: u8, quarter_pis: i8) -> Option<Self> {
+        let quarters = quarter_pis.unsigned_abs();
+        if quarter_pis < 0 {
+            Some(Area {
+                units: units.checked_sub(quarters)?,
+                small: quarters,
+                large: 0,
+            })
+        } else {
+            Some(Area {
+                units,
+                small: 0,
+                large: quarters,
+            })
+        }
+    }
+
+    /// Split this area into an integer number of units plus a (possibly negative) number of
+    /// lots of π/4, as `Display` writes it. This is the inverse of
+    /// [from_units_and_fraction](Area::from_units_and_fraction).
+    pub fn to_units_and_fraction(&self) -> (u8, i8) {
+        let a = self.simplify();
+
+        // Each 'small' contributes 1-π/4, so a unit and a negative π/4, while each large
+        // contributes a positive π/4.
+        (a.units + a.small, a.large as i8 - a.small as i8)
+    }
+
+    /// Format this area as LaTeX, in the same form as `Display`: for example `32 + \frac{\pi}{4}`
+    /// or `32 - \frac{3\pi}{4}`.
+    pub fn to_latex(&self) -> String {
+        let (int, frac) = self.to_units_and_fraction();
+
+        let sign = if frac < 0 { "-" } else { "+" };
+        match frac.unsigned_abs() {
+            0 => format!("{}", int),
+            1 => format!("{} {} \\frac{{\\pi}}{{4}}", int, sign),
+            n => format!("{} {} \\frac{{{}\\pi}}{{4}}", int, sign, n),
+        }
+    }
+
     /// Whether this is an integer area of `n` units.
     #[allow(dead_code)]
     pub fn is_integer(&self, n: u8) -> bool {
         let simplified = self.simplify();
         simplified.units == n && simplified.small == 0 && simplified.large == 0
     }
+
+    /// The area as a number of units, where each small piece is $1 - \pi/4$ and each large
+    /// piece $\pi/4$. Being a float, this can't tell apart areas which are very nearly equal;
+    /// compare `Area`s directly for that.
+    pub fn to_f64(&self) -> f64 {
+        use std::f64::consts::FRAC_PI_4;
+
+        self.units as f64 + self.small as f64 * (1.0 - FRAC_PI_4) + self.large as f64 * FRAC_PI_4
+    }
 }
 
 impl std::fmt::Display for Area {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let a = self.simplify();
-
-        // Integer part. Each 'small' contributes 1-π/4.
-        let int = a.units + a.small;
-        // Fractional part. Each 'small' contributes a negative, each large a positive.
-        let frac = a.large as i8 - a.small as i8;
+        let (int, frac) = self.to_units_and_fraction();
 
         write!(f, "{}", int)?;
         if frac == 0 {
@@ -83,437 +139,843 @@ impl std::fmt::Display for Area {
     }
 }
 
-/// An error returned when attempting to calculate the area enclosed by a loop in a `Grid`.
-#[derive(Debug)]
-pub enum AreaError {
-    LoopNotClosed,
+/// Areas are ordered by magnitude. Equal magnitudes written differently, such as a small and a
+/// large piece versus one unit, are then ordered by their fields so that the ordering agrees with
+/// `Eq`.
+impl Ord for Area {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        // Each area is an integer plus a multiple of π/4, so the difference between them is too.
+        // As π is irrational, that difference is zero only if both parts are, and otherwise it is
+        // far enough from zero for its sign to be computed exactly in floating point.
+        let int =
+            (self.units as i16 + self.small as i16) - (other.units as i16 + other.small as i16);
+        let frac =
+            (self.large as i16 - self.small as i16) - (other.large as i16 - other.small as i16);
+        let diff = int as f64 + frac as f64 * std::f64::consts::FRAC_PI_4;
+
+        let by_magnitude = if frac == 0 {
+            int.cmp(&0)
+        } else {
+            diff.total_cmp(&0.0)
+        };
+        by_magnitude.then_with(|| {
+            (self.units, self.small, self.large).cmp(&(other.units, other.small, other.large))
+        })
+    }
+}
+
+impl PartialOrd for Area {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl std::ops::Add for Area {
+    type Output = Area;
+
+    fn add(self, rhs: Area) -> Area {
+        Area {
+            units: self.units + rhs.units,
+            small: self.small + rhs.small,
+            large: self.large + rhs.large,
+        }
+        .simplify()
+    }
+}
+
+/// Subtraction gives `None` if the result can't be written with non-negative numbers of units,
+/// small and large pieces. This includes every negative result.
+impl std::ops::Sub for Area {
+    type Output = Option<Area>;
+
+    fn sub(self, rhs: Area) -> Option<Area> {
+        // Write each area as an integer part plus a multiple of π/4, as `Display` does, since
+        // both are additive.
+        let parts = |a: Area| {
+            (
+                a.units as i16 + a.small as i16,
+                a.large as i16 - a.small as i16,
+            )
+        };
+        let (int, frac) = (parts(self).0 - parts(rhs).0, parts(self).1 - parts(rhs).1);
+
+        Area::from_units_and_fraction(int.try_into().ok()?, frac.try_into().ok()?)
+    }
+}
+
+impl std::ops::Mul<u8> for Area {
+    type Output = Area;
+
+    fn mul(self, rhs: u8) -> Area {
+        Area {
+            units: self.units * rhs,
+            small: self.small * rhs,
+            large: self.large * rhs,
+        }
+        .simplify()
+    }
 }
 
-#[derive(Clone, Debug)]
-pub struct Grid {
-    data: [[Cell; 7]; 7],
+/// An NxN grid, containing empty cells and curve segments. The puzzle itself uses the default
+/// 7x7 grid.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Grid<const N: usize = 7> {
+    data: [[Cell; N]; N],
 }
 
-impl Grid {
-    /// Create a new `Grid` from an array of arrays of `Cell`s.
-    pub fn new(data: [[Cell; 7]; 7]) -> Self {
+impl<const N: usize> Grid<N> {
+    /// Create a new `Grid` from an array of arrays of `Cell`s. `N` may be at most
+    /// [MAX_GRID_SIZE].
+    pub fn new(data: [[Cell; N]; N]) -> Self {
+        const {
+            assert!(
+                N <= MAX_GRID_SIZE,
+                "grids larger than 15x15 are not supported"
+            )
+        };
+
         Self { data }
     }
 
-    /// Calculate the enclosed area inside the loop drawn in this `Grid`. This function assumes
-    /// that the shape passed is a valid closed loop. It does not check this.
+    /// Iterate over the cells of the grid in row-major order, along with their `(row, col)`
+    /// coordinates.
+    pub fn cells(&self) -> impl Iterator<Item = ((usize, usize), Cell)> + '_ {
+        self.data
+            .iter()
+            .enumerate()
+            .flat_map(|(r, row)| row.iter().enumerate().map(move |(c, cell)| ((r, c), *cell)))
+    }
+
+    /// Every arc `Grid` drawing the same loop as the diagonal `grid`, and enclosing the same area.
+    ///
+    /// Each diagonal of a loop of length $2n$ can be replaced by an arc bulging either way across
+    /// its cell, enclosing $\pi/4$ or $1 - \pi/4$ rather than half a unit. The area is preserved
+    /// exactly when $n$ arcs bulge outwards and $n$ inwards, so there are $\binom{2n}{n}$ such
+    /// grids. This is the multiplicity the fast model counts each diagonal layout with.
+    ///
+    /// Returns an error if `grid` doesn't hold closed loops, as its area can't be measured.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the loop has 64 or more segments.
+    pub fn from_diagonal_grid(
+        grid: &fast::Grid<N>,
+    ) -> Result<impl Iterator<Item = Self>, fast::AreaError> {
+        grid.loop_area()?;
+
+        // For each segment of the loop, its position and the arcs which enclose pi/4 and 1-pi/4.
+        // These are found by scanning each row for the inside of the loop, as in `loop_area`.
+        let mut segments = Vec::new();
+        let mut outside = true;
+        for ((r, c), cell) in grid.cells() {
+            if c == 0 {
+                outside = true;
+            }
+            let slant = match cell {
+                fast::Cell::Empty => continue,
+                fast::Cell::Forward => Slant::Forward,
+                fast::Cell::Backward => Slant::Backward,
+            };
+
+            let &[a, b] = Cell::along(slant) else {
+                unreachable!("every diagonal has two arcs along it");
+            };
+            let large = |arc| match arc {
+                Cell::TopRight | Cell::BottomRight => outside,
+                _ => !outside,
+            };
+            segments.push(if large(a) { (r, c, a, b) } else { (r, c, b, a) });
+
+            outside = !outside;
+        }
+        assert!(segments.len() < 64, "loop should have under 64 segments");
+
+        // Each set of n segments to take the larger arc is a bitmask with n bits set. Step through
+        // them in increasing order, using Gosper's hack to find the next mask with as many bits.
+        let end = 1_u64 << segments.len();
+        let mut mask = Some((1_u64 << (segments.len() / 2)) - 1);
+
+        Ok(std::iter::from_fn(move || {
+            let m = mask?;
+            mask = (m != 0)
+                .then(|| {
+                    let lowest = m & m.wrapping_neg();
+                    let ripple = m + lowest;
+                    (((ripple ^ m) >> 2) / lowest) | ripple
+                })
+                .filter(|next| *next < end);
+
+            let mut data = [[Cell::Empty; N]; N];
+            for (i, (r, c, large, small)) in segments.iter().enumerate() {
+                data[*r][*c] = if m >> i & 1 == 1 { *large } else { *small };
+            }
+            Some(Grid::new(data))
+        }))
+    }
+
+    /// Parse a `Grid` from a drawing in the format produced by its `Display` implementation: one
+    /// line per row, with `·` for an empty cell and a quarter-circle glyph for an arc. The glyph
+    /// bulges away from the corner the arc is centred on, so `TopLeft` is drawn as `◞`,
+    /// `TopRight` as `◟`, `BottomLeft` as `◝` and `BottomRight` as `◜`.
+    pub fn from_str_art(s: &str) -> Result<Self, ParseError> {
+        grid::parse(s).map(Self::new)
+    }
+
+    /// Whether the curve segments in this `Grid` join up into a single closed loop, without any
+    /// loose ends, branches or self-intersections. [loop_area](Self::loop_area) assumes this.
+    pub fn is_valid_loop(&self) -> bool {
+        grid::is_valid_loop(&self.data)
+    }
+
+    /// Draw this grid as a standalone SVG document. The grid lines are drawn faintly, and each arc
+    /// as a quarter circle centred on the corner of its cell which names it.
+    pub fn to_svg(&self) -> String {
+        grid::to_svg(&self.data)
+    }
+
+    /// Calculate the enclosed area inside the loop drawn in this `Grid`. This function doesn't
+    /// check that the grid holds a single loop; use [is_valid_loop](Self::is_valid_loop) to do
+    /// so. Malformed grids, with loose ends or crossing segments, give an error rather than a
+    /// nonsensical area.
+    ///
+    /// Several loops which neither cross nor touch are supported, whether side by side or nested.
+    /// Each row is scanned from the left, toggling between outside and inside at every segment,
+    /// so a cell counts as inside when it is enclosed by an odd number of loops. The area of
+    /// separate loops is their total, while a loop nested inside another cuts a hole out of it,
+    /// leaving the area of the ring between them.
     pub fn loop_area(&self) -> Result<Area, AreaError> {
-        // These should sum to exactly 49 at the end of looping through the grid.
-        let mut n = 0; // The number of arc segments encountered.
-        let mut k = 0; // The number of outside full cells encountered.
+        grid::check_segment_ends(&self.data)?;
+
         let mut j = 0; // The number of inside full cells encountered.
 
-        // These should sum to exactly `n` at the end of looping through the grid.
+        // Every arc segment contributes one of these.
         let mut n_s = 0; // The number of arc segments which contribute a 'small' enclosed area (i.e.
                          // an area of 1-π/4).
         let mut n_b = 0; // The number of arc segments which contribute a 'large' enclosed area (i.e.
                          // an area of +π/4).
 
         for row in &self.data {
-            // Tracking whether we are inside or outside the loop before we inspect this cell.
-            let mut outside = true;
+            let (outside, ends_outside) = grid::scan_row(row);
+            if !ends_outside {
+                return Err(AreaError::Inconsistent);
+            }
 
-            for col in row {
+            for (col, outside) in row.iter().zip(outside) {
                 use Cell::*;
                 match col {
                     Empty => {
-                        if outside {
-                            k += 1;
-                        } else {
+                        if !outside {
                             j += 1;
                         }
                     }
                     TopLeft | BottomLeft => {
-                        n += 1;
-
                         if outside {
                             n_s += 1;
                         } else {
                             n_b += 1;
                         }
-
-                        outside = !outside;
                     }
                     TopRight | BottomRight => {
-                        n += 1;
-
                         if outside {
                             n_b += 1;
                         } else {
                             n_s += 1;
                         }
-
-                        outside = !outside;
                     }
                 }
             }
         }
 
-        assert_eq!(n_s + n_b, n);
-
-        if n + k + j != 49 {
-            Err(AreaError::LoopNotClosed)
-        } else {
-            Ok(Area {
-                units: j,
-                small: n_s,
-                large: n_b,
-            }
-            .simplify())
+        Ok(Area {
+            units: j,
+            small: n_s,
+            large: n_b,
         }
+        .simplify())
+    }
+
+    /// The [loop_area](Self::loop_area) as a number of units, for plotting and comparisons.
+    pub fn area_f64(&self) -> Result<f64, AreaError> {
+        self.loop_area().map(|area| area.to_f64())
     }
 }
 
-impl std::fmt::Display for Grid {
+impl<const N: usize> std::fmt::Display for Grid<N> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for row in &self.data {
-            for col in row {
-                use Cell::*;
-                match *col {
-                    Empty => write!(f, "·")?,
-                    TopLeft => write!(f, "╱")?,
-                    TopRight => write!(f, "╲")?,
-                    BottomLeft => write!(f, "╲")?,
-                    BottomRight => write!(f, "╱")?,
-                };
-            }
-            writeln!(f)?;
+        grid::draw(&self.data, f)
+    }
+}
+
+/// Grids are serialized compactly, as the text drawing produced by `Display`.
+#[cfg(feature = "serde")]
+impl<const N: usize> serde::Serialize for Grid<N> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, const N: usize> serde::Deserialize<'de> for Grid<N> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let art = String::deserialize(deserializer)?;
+        Grid::from_str_art(&art).map_err(|e| serde::de::Error::custom(format!("{:?}", e)))
+    }
+}
+
+impl GridCell for Cell {
+    const SVG_STYLE: &'static str = "stroke=\"black\" stroke-width=\"3\" fill=\"none\"";
+
+    /// The glyph bulges away from the corner the arc is centred on.
+    fn glyph(self) -> char {
+        use Cell::*;
+        match self {
+            Empty => '·',
+            TopLeft => '◞',
+            TopRight => '◟',
+            BottomLeft => '◝',
+            BottomRight => '◜',
         }
+    }
 
-        Ok(())
+    /// The arc is a quarter circle centred on the corner of its cell which names it.
+    fn svg_element(self, x: usize, y: usize, size: usize) -> Option<String> {
+        // The end points of the arc, and whether it sweeps clockwise between them.
+        use Cell::*;
+        let ((x1, y1), (x2, y2), clockwise) = match self {
+            Empty => return None,
+            TopLeft => ((x + size, y), (x, y + size), true),
+            TopRight => ((x, y), (x + size, y + size), false),
+            BottomLeft => ((x, y), (x + size, y + size), true),
+            BottomRight => ((x + size, y), (x, y + size), false),
+        };
+        Some(format!(
+            "<path d=\"M {x1} {y1} A {size} {size} 0 0 {} {x2} {y2}\"/>",
+            clockwise as u8
+        ))
+    }
+}
+
+impl Segment for Cell {
+    const EMPTY: Self = Cell::Empty;
+
+    fn along(slant: Slant) -> &'static [Self] {
+        match slant {
+            Slant::Forward => &[Cell::TopLeft, Cell::BottomRight],
+            Slant::Backward => &[Cell::TopRight, Cell::BottomLeft],
+        }
     }
 }
 
 /// A data structure for generating closed loops of a target area, using a back-tracking algorithm.
 #[derive(Debug)]
-pub struct Generator {
+pub struct Generator<const N: usize = 7> {
     /// The target area we are aiming for.
     target: Area,
-    /// The maximum number of inner cells (i.e. not part of the outer boundary of the grid) we can
-    /// have forming part of the curve. This constraint is useful to prune a very large number of
-    /// search paths, assuming we can prove it rigorously for our target area.
-    max_inner_cells: u8,
-    /// The maximum length of the loop (in quarter circle arcs). This constraint is useful to prune
-    /// some search paths, assuming we can prove it rigorously for our desired target area.
-    max_length: u8,
-    /// The current state of the grid.
-    grid: Grid,
-    /// Whether we have placed something in each cell of the grid so far during the backtracking
-    /// algorithm.
-    placed: [[bool; 7]; 7],
-    /// Tracks the number of placed cells; used to ensure backtracking doesn't recurse forever.
-    placed_cnt: u8,
-    /// The order of placements made in the grid. When we backtrack, we pop off elements and undo
-    /// those moves. The first tuple is the coordinate of the cell being placed. The second element
-    /// is the coordinates of the head before we placed this move (for undoing).
-    moves: Vec<((u8, u8), (u8, u8))>,
-    /// The coordinates of the loop's starting point, used to determine when we have closed the
-    /// loop. Coordinates are on the grid lines, zero-indexed from the top-left of the grid.
-    start: (u8, u8),
-    /// The location of the head of the loop we are generating. Coordinates are on the grid lines.
-    head: (u8, u8),
-    /// Storage for all the valid grids we find.
-    valid_grids: Vec<Grid>,
-    calls: usize,
-    /// The number of cells we have placed not on the outer rim of the grid. This constraint is
-    /// useful to prune a large number of search paths, assuming we can prove it rigorously for our
-    /// target area.
-    inner_cells: usize,
+    /// The state of the backtracking search.
+    search: LoopSearch<Cell, N>,
 }
 
-impl Generator {
+impl<const N: usize> Generator<N> {
     /// Create a new `Generator`.
     pub fn new(target: Area, max_inner_cells: u8, max_length: u8) -> Self {
         Self {
             target: target.simplify(),
-            max_inner_cells,
-            max_length,
-            grid: Grid::new([[Cell::Empty; 7]; 7]),
-            placed: [[false; 7]; 7],
-            placed_cnt: 0,
-            moves: Vec::with_capacity(49),
-            start: (0, 0),
-            head: (0, 0),
-            valid_grids: Vec::new(),
-            calls: 0,
-            inner_cells: 0,
+            search: LoopSearch::new(max_inner_cells, max_length),
         }
     }
 
-    pub fn generate(mut self) -> Vec<Grid> {
-        self.next_cell();
-        self.valid_grids
+    /// Report progress by calling `callback` every `interval` nodes of the search, with the number
+    /// of nodes visited and the number of valid grid layouts found so far. By default, progress
+    /// is not reported.
+    pub fn with_progress(
+        mut self,
+        interval: usize,
+        callback: impl FnMut(usize, usize) + Send + 'static,
+    ) -> Self {
+        self.search.set_progress(interval, callback);
+        self
     }
 
-    fn next_cell(&mut self) {
-        self.calls += 1;
-        if self.calls % 1_000_000 == 0 {
-            println!(
-                "{} nodes visited; {} valid grids found",
-                self.calls,
-                self.valid_grids.len(),
-            );
-        }
+    /// Generate a vec of all the valid grids.
+    pub fn generate(self) -> Vec<Grid<N>> {
+        let mut valid_grids = Vec::new();
+        self.generate_with(|grid| valid_grids.push(grid.clone()));
+        valid_grids
+    }
 
-        if self.moves.len() == 0 {
-            // Try every possibility for the first cell.
-            for r in 0..7 {
-                for c in 0..7 {
-                    use Cell::*;
-                    for cell in [TopLeft, TopRight, BottomLeft, BottomRight] {
-                        match cell {
-                            Empty => unreachable!(),
-                            TopLeft | BottomRight => {
-                                let start = (r + 1, c);
-                                if start == (0, 0)
-                                    || start == (0, 7)
-                                    || start == (7, 0)
-                                    || start == (7, 7)
-                                {
-                                    continue;
-                                }
-
-                                self.head = (r, c + 1);
-                                self.start = (r + 1, c);
-                                self.place(r, c, cell, r, c + 1);
-                            }
-                            TopRight | BottomLeft => {
-                                let start = (r, c);
-                                if start == (0, 0)
-                                    || start == (0, 7)
-                                    || start == (7, 0)
-                                    || start == (7, 7)
-                                {
-                                    continue;
-                                }
-
-                                self.head = (r + 1, c + 1);
-                                self.start = (r, c);
-                                self.place(r, c, cell, r + 1, c + 1);
-                            }
-                        }
+    /// Generate the count of valid grids, calling `f` with each one as it is found rather than
+    /// storing them all.
+    pub fn generate_with(mut self, mut f: impl FnMut(&Grid<N>)) -> usize {
+        let target = self.target;
+        let mut valid_cnt = 0;
+
+        // Each arc encloses half a unit more or less than the chord across its cell, depending on
+        // which way it bulges, and a small piece is one unit less π/4 while a large one is π/4. So
+        // the area of the polygon through the loop's grid points, which the search tracks, is the
+        // integer part of its area plus half its multiple of π/4. Loops whose polygon doesn't
+        // match the target's are rejected without building their grid.
+        let (units, quarter_pis) = target.to_units_and_fraction();
+        let polygon_area_doubled = 2 * units as i32 + quarter_pis as i32;
+
+        self.search.run(&mut |search| {
+            if search.polygon_area_doubled() as i32 != polygon_area_doubled {
+                return false;
+            }
 
-                        self.next_cell();
-                        self.unplace();
-                    }
+            let grid = Grid::new(*search.cells());
+            let area = grid.loop_area().expect("we formed a loop").simplify();
 
-                    // Unlike with non-first cells, we want to maintain the flag that marks
-                    // this as placed, because we don't want the loop to ever come back here.
-                    self.placed[r as usize][c as usize] = true;
-                    assert_eq!(self.grid.data, [[Empty; 7]; 7]);
-                }
+            if area != target {
+                // We formed a loop, but it was the wrong size.
+                return false;
             }
-        } else {
-            // Get the last cell that we placed.
-            let ((pr, pc), _) = self.moves.last().expect("should be non-empty").clone();
-            let p_cell = self.grid.data[pr as usize][pc as usize];
-            assert_ne!(p_cell, Cell::Empty);
 
-            let mut moves = Vec::with_capacity(6);
+            valid_cnt += 1;
+            f(&grid);
+            true
+        });
 
-            // Consider the current head. There are four cells surrounding it. Establish from the
-            // `placed` grid which of these we can move to next.
-            let (hr, hc) = self.head;
+        valid_cnt
+    }
+}
 
-            for dr in [-1, 1] {
-                let nr = hr as i32 + dr;
-                if nr < 0 || nr > 7 {
-                    continue;
-                }
-                let nr = nr as u8;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-                for dc in [-1, 1] {
-                    let nc = hc as i32 + dc;
-                    if nc < 0 || nc > 7 {
-                        continue;
-                    }
-                    let nc = nc as u8;
+    fn example_grids() -> [Grid; 3] {
+        use Cell::*;
 
-                    let ncellr = if dr == 1 { nr - 1 } else { nr };
-                    let ncellc = if dc == 1 { nc - 1 } else { nc };
+        [
+            Grid::new([
+                [Empty, Empty, Empty, Empty, Empty, Empty, Empty],
+                [Empty, TopLeft, TopRight, Empty, Empty, Empty, Empty],
+                [Empty, BottomLeft, BottomRight, Empty, Empty, Empty, Empty],
+                [Empty, Empty, Empty, Empty, Empty, Empty, Empty],
+                [Empty, Empty, Empty, Empty, Empty, Empty, Empty],
+                [Empty, Empty, Empty, Empty, Empty, Empty, Empty],
+                [Empty, Empty, Empty, Empty, Empty, Empty, Empty],
+            ]),
+            Grid::new([
+                [Empty, Empty, Empty, Empty, Empty, Empty, Empty],
+                [Empty, Empty, Empty, Empty, Empty, Empty, Empty],
+                [Empty, Empty, Empty, Empty, Empty, Empty, Empty],
+                [Empty, Empty, Empty, Empty, Empty, Empty, Empty],
+                [Empty, Empty, TopLeft, BottomLeft, TopLeft, TopRight, Empty],
+                [Empty, Empty, BottomLeft, Empty, Empty, TopLeft, Empty],
+                [Empty, Empty, Empty, TopRight, TopLeft, Empty, Empty],
+            ]),
+            Grid::new([
+                [
+                    Empty, Empty, TopLeft, BottomLeft, TopLeft, BottomLeft, Empty,
+                ],
+                [Empty, BottomRight, Empty, Empty, Empty, Empty, TopRight],
+                [TopLeft, Empty, Empty, Empty, Empty, Empty, TopLeft],
+                [TopRight, Empty, Empty, Empty, Empty, Empty, TopRight],
+                [TopLeft, Empty, Empty, Empty, Empty, Empty, TopLeft],
+                [TopRight, Empty, Empty, Empty, Empty, BottomRight, Empty],
+                [
+                    Empty, BottomLeft, TopLeft, BottomLeft, TopLeft, Empty, Empty,
+                ],
+            ]),
+        ]
+    }
 
-                    // Check that the proposed new cell location isn't already populated.
-                    if self.placed[ncellr as usize][ncellc as usize] {
-                        continue;
-                    }
+    #[test]
+    fn example_shapes_have_correct_area() {
+        let [grid1, grid2, grid3] = example_grids();
 
-                    // Push the relevant moves into the list.
-                    use Cell::*;
-                    match (dr, dc) {
-                        (-1, -1) | (1, 1) => {
-                            moves.push((ncellr, ncellc, TopRight, nr, nc));
-                            moves.push((ncellr, ncellc, BottomLeft, nr, nc));
-                        }
-                        (-1, 1) | (1, -1) => {
-                            moves.push((ncellr, ncellc, TopLeft, nr, nc));
-                            moves.push((ncellr, ncellc, BottomRight, nr, nc));
-                        }
-                        _ => unreachable!(),
-                    }
-                }
+        assert_eq!(
+            grid1.loop_area().unwrap(),
+            Area {
+                units: 0,
+                small: 4,
+                large: 0
             }
+        );
 
-            // Iterate the moves
-            for (ncellr, ncellc, n_cell, nr, nc) in moves {
-                // Check if the current possibility causes a self-intersection. If so, continue.
-                let mut c = 0_u8;
-
-                // Top-left
-                if nr > 0
-                    && nc > 0
-                    && self.grid.data[nr as usize - 1][nc as usize - 1] != Cell::Empty
-                {
-                    c += 1;
-                }
-                // Top-right
-                if nr > 0 && nc < 7 && self.grid.data[nr as usize - 1][nc as usize] != Cell::Empty {
-                    c += 1;
-                }
-                // Bottom-left
-                if nr < 7 && nc > 0 && self.grid.data[nr as usize][nc as usize - 1] != Cell::Empty {
-                    c += 1;
-                }
-                // Bottom-right
-                if nr < 7 && nc < 7 && self.grid.data[nr as usize][nc as usize] != Cell::Empty {
-                    c += 1;
-                }
+        assert!(grid2.loop_area().unwrap().is_integer(6));
 
-                if c >= 2 {
-                    continue;
-                }
+        assert!(grid3.loop_area().unwrap().is_integer(32));
+    }
 
-                // Check if this possibility closes the loop. If so, add it to the valid grids.
-                // The current `placed_cnt` must have odd parity if adding this possibility would
-                // close the loop, because a closed loop must have even parity.
-                if nr == self.start.0 && nc == self.start.1 {
-                    assert_eq!(c, 1);
+    #[test]
+    fn grids_round_trip_through_str_art() {
+        for grid in example_grids() {
+            assert_eq!(Grid::from_str_art(&grid.to_string()), Ok(grid));
+        }
+    }
 
-                    self.place(ncellr, ncellc, n_cell, nr, nc);
-                    assert!(self.placed_cnt % 2 == 0);
+    #[test]
+    fn malformed_str_art_is_rejected() {
+        let art = example_grids()[0].to_string();
 
-                    let area = self.grid.loop_area().expect("we formed a loop").simplify();
+        let short: String = art.lines().take(6).map(|l| format!("{l}\n")).collect();
+        assert_eq!(
+            Grid::<7>::from_str_art(&short),
+            Err(ParseError::WrongRowCount(6))
+        );
 
-                    if area == self.target {
-                        self.valid_grids.push(self.grid.clone());
-                        self.unplace();
-                    } else {
-                        // We formed a loop, but it was the wrong size.
-                        self.unplace();
-                        continue;
-                    }
-                }
+        let narrow = art.replacen("·\n", "\n", 1);
+        assert_eq!(
+            Grid::<7>::from_str_art(&narrow),
+            Err(ParseError::WrongRowLength(0, 6))
+        );
 
-                if self.placed_cnt + 1 > self.max_length {
-                    continue;
-                }
+        // The slanted glyphs are ambiguous for arcs, so are not accepted.
+        let slanted = art.replacen('◜', "╱", 1);
+        assert_eq!(
+            Grid::<7>::from_str_art(&slanted),
+            Err(ParseError::UnknownGlyph('╱'))
+        );
+    }
 
-                // Place the current possibility
-                self.place(ncellr, ncellc, n_cell, nr, nc);
+    #[test]
+    fn generator_on_small_grid() {
+        // On a 3x3 grid, the only curves of area 2 go around one of the four inner grid points,
+        // with two of their four arcs bulging outwards.
+        let target = Area {
+            units: 2,
+            small: 0,
+            large: 0,
+        };
+        let valid_grids = Generator::<3>::new(target, 9, 9).generate();
+
+        assert_eq!(valid_grids.len(), 24);
+    }
 
-                if self.inner_cells <= self.max_inner_cells as usize {
-                    self.next_cell();
-                }
+    #[test]
+    fn generate_with_visits_every_valid_grid() {
+        let target = Area {
+            units: 4,
+            small: 0,
+            large: 0,
+        };
+        let valid_grids = Generator::<4>::new(target, 16, 16).generate();
+
+        let mut calls = 0;
+        let cnt = Generator::<4>::new(target, 16, 16).generate_with(|grid| {
+            assert!(grid.loop_area().unwrap().is_integer(4));
+            calls += 1;
+        });
+
+        assert!(!valid_grids.is_empty());
+        assert_eq!(calls, valid_grids.len());
+        assert_eq!(cnt, valid_grids.len());
+    }
 
-                self.unplace();
-            }
+    #[test]
+    fn generator_finds_every_loop_of_each_area() {
+        let mut counts = std::collections::BTreeMap::new();
+        LoopSearch::<Cell, 4>::new(16, 16).run(&mut |search| {
+            let area = Grid::new(*search.cells()).loop_area().unwrap().simplify();
+            *counts.entry(area).or_insert(0) += 1;
+            false
+        });
+
+        assert!(counts.len() > 1);
+        for (area, cnt) in counts {
+            assert_eq!(
+                Generator::<4>::new(area, 16, 16).generate_with(|_| {}),
+                cnt,
+                "{area:?}"
+            );
         }
     }
 
-    fn place(&mut self, row: u8, col: u8, c: Cell, headr: u8, headc: u8) {
-        let cell = &mut self.grid.data[row as usize][col as usize];
-        let placed = &mut self.placed[row as usize][col as usize];
+    #[test]
+    fn valid_loops_are_recognised() {
+        use Cell::*;
 
-        assert_eq!(*placed, false);
+        for grid in example_grids() {
+            assert!(grid.is_valid_loop());
+        }
 
-        *cell = c;
-        *placed = true;
-        self.placed_cnt += 1;
-        self.moves.push(((row, col), self.head));
-        self.head = (headr, headc);
+        // A diamond with one side missing is an open path.
+        let open = Grid::new({
+            let mut data = [[Empty; 7]; 7];
+            data[1][1] = TopLeft;
+            data[1][2] = TopRight;
+            data[2][1] = BottomLeft;
+            data
+        });
+        assert!(!open.is_valid_loop());
+
+        // Two separate diamonds are two loops, not one.
+        let disjoint = Grid::new({
+            let mut data = [[Empty; 7]; 7];
+            data[1][1] = TopLeft;
+            data[1][2] = TopRight;
+            data[2][1] = BottomLeft;
+            data[2][2] = BottomRight;
+            data[4][4] = TopLeft;
+            data[4][5] = TopRight;
+            data[5][4] = BottomLeft;
+            data[5][5] = BottomRight;
+            data
+        });
+        assert!(!disjoint.is_valid_loop());
+
+        assert!(!Grid::new([[Empty; 7]; 7]).is_valid_loop());
+    }
 
-        if row > 0 && row < 6 && col > 0 && col < 6 {
-            self.inner_cells += 1;
-        }
+    #[test]
+    fn area_arithmetic() {
+        let area = |units, small, large| Area {
+            units,
+            small,
+            large,
+        };
+
+        // A small piece and a large piece carry into a full unit.
+        assert_eq!(area(1, 1, 0) + area(0, 0, 1), area(2, 0, 0));
+        assert_eq!(area(1, 2, 0) + area(0, 0, 3), area(3, 0, 1));
+        assert_eq!(area(1, 0, 1) * 3, area(3, 0, 3));
+
+        assert_eq!(area(2, 0, 0) - area(0, 0, 1), Some(area(1, 1, 0)));
+        assert_eq!(area(3, 0, 1) - area(1, 0, 2), Some(area(1, 1, 0)));
+        assert_eq!(area(1, 0, 0) - area(0, 0, 1), Some(area(0, 1, 0)));
+        // 1 - π/2 is negative.
+        assert_eq!(area(1, 0, 0) - area(0, 0, 2), None);
+        assert_eq!(area(0, 0, 1) - area(1, 0, 0), None);
     }
 
-    fn unplace(&mut self) {
-        let ((row, col), old_head) = self
-            .moves
-            .pop()
-            .expect("should never call `unplace` with nothing to unplace");
-        let cell = &mut self.grid.data[row as usize][col as usize];
-        let placed = &mut self.placed[row as usize][col as usize];
-        assert_eq!(*placed, true);
+    #[test]
+    fn areas_sort_by_magnitude() {
+        let area = |units, small, large| Area {
+            units,
+            small,
+            large,
+        };
+        let mut areas = vec![
+            area(1, 0, 0), // 1
+            area(0, 0, 1), // π/4 ≈ 0.785
+            area(0, 1, 0), // 1-π/4 ≈ 0.215
+            area(0, 0, 2), // π/2 ≈ 1.571
+            area(1, 1, 0), // 2-π/4 ≈ 1.215
+            area(0, 1, 1), // 1
+            area(3, 4, 0), // 7-π ≈ 3.858
+            area(0, 0, 5), // 5π/4 ≈ 3.927
+        ];
+        areas.sort();
 
-        *cell = Cell::Empty;
-        *placed = false;
-        self.placed_cnt -= 1;
-        self.head = old_head;
+        assert_eq!(
+            areas,
+            vec![
+                area(0, 1, 0),
+                area(0, 0, 1),
+                area(0, 1, 1),
+                area(1, 0, 0),
+                area(1, 1, 0),
+                area(0, 0, 2),
+                area(3, 4, 0),
+                area(0, 0, 5),
+            ]
+        );
+        assert_eq!(area(0, 1, 1).cmp(&area(1, 0, 0)), std::cmp::Ordering::Less);
+    }
+
+    #[test]
+    fn area_as_latex() {
+        let area = |units, small, large| Area {
+            units,
+            small,
+            large,
+        };
+
+        assert_eq!(area(32, 0, 0).to_latex(), "32");
+        assert_eq!(area(31, 1, 1).to_latex(), "32");
+        assert_eq!(area(32, 0, 1).to_latex(), r"32 + \frac{\pi}{4}");
+        assert_eq!(area(31, 1, 0).to_latex(), r"32 - \frac{\pi}{4}");
+        assert_eq!(area(29, 3, 0).to_latex(), r"32 - \frac{3\pi}{4}");
+        assert_eq!(area(0, 0, 5).to_latex(), r"0 + \frac{5\pi}{4}");
+    }
 
-        if row > 0 && row < 6 && col > 0 && col < 6 {
-            self.inner_cells -= 1;
+    #[cfg(feature = "serde")]
+    #[test]
+    fn grids_round_trip_through_serde() {
+        for grid in example_grids() {
+            let json = serde_json::to_string(&grid).unwrap();
+            assert_eq!(json, serde_json::to_string(&grid.to_string()).unwrap());
+            assert_eq!(serde_json::from_str::<Grid>(&json).unwrap(), grid);
         }
+
+        let area = example_grids()[2].loop_area().unwrap();
+        let json = serde_json::to_string(&area).unwrap();
+        assert_eq!(serde_json::from_str::<Area>(&json).unwrap(), area);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn grid_as_svg() {
+        for grid in example_grids() {
+            let arcs = grid
+                .data
+                .iter()
+                .flatten()
+                .filter(|c| **c != Cell::Empty)
+                .count();
+
+            let svg = grid.to_svg();
+            assert!(svg.starts_with("<svg "));
+            assert!(svg.ends_with("</svg>\n"));
+            assert_eq!(svg.matches("<line ").count(), 16);
+            assert_eq!(svg.matches("<path d=\"M ").count(), arcs);
+            assert_eq!(svg.matches(" A 40 40 0 0 ").count(), arcs);
+        }
+
+        // The arcs of the first example are centred on the outer corners of the 2x2 block, so
+        // they bulge inwards. The top-left one runs clockwise from the top-right of its cell.
+        let svg = example_grids()[0].to_svg();
+        assert!(svg.contains(r#"<path d="M 90 50 A 40 40 0 0 1 50 90"/>"#));
+    }
 
     #[test]
-    fn example_shapes_have_correct_area() {
-        use Cell::*;
+    fn cells_in_row_major_order() {
+        let grid = example_grids()[2].clone();
+        let cells: Vec<_> = grid.cells().collect();
+
+        assert_eq!(cells.len(), 49);
+        for (i, ((r, c), cell)) in cells.into_iter().enumerate() {
+            assert_eq!((r, c), (i / 7, i % 7));
+            assert_eq!(cell, grid.data[r][c]);
+        }
+    }
+
+    #[test]
+    fn malformed_grids_give_area_errors() {
+        // Two arcs crossing at a grid point, with loose ends.
+        let crossing = Grid::<4>::from_str_art("····\n·◟◞·\n·◞◟·\n····").unwrap();
+        assert_eq!(crossing.loop_area(), Err(AreaError::LoopNotClosed));
 
-        let grid1 = Grid::new([
-            [Empty, Empty, Empty, Empty, Empty, Empty, Empty],
-            [Empty, TopLeft, TopRight, Empty, Empty, Empty, Empty],
-            [Empty, BottomLeft, BottomRight, Empty, Empty, Empty, Empty],
-            [Empty, Empty, Empty, Empty, Empty, Empty, Empty],
-            [Empty, Empty, Empty, Empty, Empty, Empty, Empty],
-            [Empty, Empty, Empty, Empty, Empty, Empty, Empty],
-            [Empty, Empty, Empty, Empty, Empty, Empty, Empty],
-        ]);
+        // Two circles touching at a grid point, so the curve crosses itself there.
+        let eight = Grid::<4>::from_str_art("◜◝◜◝\n◟◞◟◞\n····\n····").unwrap();
+        assert_eq!(eight.loop_area(), Err(AreaError::Inconsistent));
 
+        let open = Grid::<4>::from_str_art("····\n·◜··\n····\n····").unwrap();
+        assert_eq!(open.loop_area(), Err(AreaError::LoopNotClosed));
+    }
+
+    #[test]
+    fn nested_loops_enclose_ring() {
+        // Rounded diamonds of radius 3 and 1, both centred on the same grid point, the smaller
+        // being a circle. Nested, they enclose the ring between them.
+        let outer = Grid::<7>::from_str_art(
+            "··◜◝···\n·◜··◝··\n◜····◝·\n◟····◞·\n·◟··◞··\n··◟◞···\n·······",
+        )
+        .unwrap();
+        let inner = Grid::<7>::from_str_art(
+            "·······\n·······\n··◜◝···\n··◟◞···\n·······\n·······\n·······",
+        )
+        .unwrap();
+        let nested = Grid::<7>::from_str_art(
+            "··◜◝···\n·◜··◝··\n◜·◜◝·◝·\n◟·◟◞·◞·\n·◟··◞··\n··◟◞···\n·······",
+        )
+        .unwrap();
+
+        let (outer, inner) = (outer.loop_area().unwrap(), inner.loop_area().unwrap());
         assert_eq!(
-            grid1.loop_area().unwrap(),
+            inner,
             Area {
                 units: 0,
-                small: 4,
-                large: 0
+                small: 0,
+                large: 4,
             }
         );
+        assert_eq!(nested.loop_area().unwrap(), (outer - inner).unwrap());
+    }
 
-        let grid2 = Grid::new([
-            [Empty, Empty, Empty, Empty, Empty, Empty, Empty],
-            [Empty, Empty, Empty, Empty, Empty, Empty, Empty],
-            [Empty, Empty, Empty, Empty, Empty, Empty, Empty],
-            [Empty, Empty, Empty, Empty, Empty, Empty, Empty],
-            [Empty, Empty, TopLeft, BottomLeft, TopLeft, TopRight, Empty],
-            [Empty, Empty, BottomLeft, Empty, Empty, TopLeft, Empty],
-            [Empty, Empty, Empty, TopRight, TopLeft, Empty, Empty],
-        ]);
+    #[test]
+    fn diagonal_grids_expand_to_arc_grids() {
+        use std::collections::HashSet;
+
+        let diamond = fast::Grid::<4>::from_str_art("····\n·╱╲·\n·╲╱·\n····").unwrap();
+        let grids: Vec<Grid<4>> = Grid::from_diagonal_grid(&diamond).unwrap().collect();
+        assert_eq!(grids.len(), 6);
+
+        // The example loop of area 32 from the fast model, which has 18 segments.
+        let fast_grid = fast::Grid::<7>::from_str_art(
+            "··╱╲╱╲·\n·╱····╲\n╱·····╱\n╲·····╲\n╱·····╱\n╲····╱·\n·╲╱╲╱··",
+        )
+        .unwrap();
+        assert_eq!(fast_grid.loop_length(), 18);
+
+        let mut distinct = HashSet::new();
+        for grid in Grid::from_diagonal_grid(&fast_grid).unwrap() {
+            assert_eq!(
+                grid.loop_area().unwrap().simplify(),
+                Area {
+                    units: 32,
+                    small: 0,
+                    large: 0,
+                }
+            );
+            distinct.insert(grid.to_string());
+        }
+        assert_eq!(distinct.len(), 48_620);
 
-        assert!(grid2.loop_area().unwrap().is_integer(6));
+        let open = fast::Grid::<4>::from_str_art("····\n·╱··\n····\n····").unwrap();
+        assert!(Grid::from_diagonal_grid(&open).is_err());
+    }
 
-        let grid3 = Grid::new([
-            [
-                Empty, Empty, TopLeft, BottomLeft, TopLeft, BottomLeft, Empty,
-            ],
-            [Empty, BottomRight, Empty, Empty, Empty, Empty, TopRight],
-            [TopLeft, Empty, Empty, Empty, Empty, Empty, TopLeft],
-            [TopRight, Empty, Empty, Empty, Empty, Empty, TopRight],
-            [TopLeft, Empty, Empty, Empty, Empty, Empty, TopLeft],
-            [TopRight, Empty, Empty, Empty, Empty, BottomRight, Empty],
-            [
-                Empty, BottomLeft, TopLeft, BottomLeft, TopLeft, Empty, Empty,
-            ],
-        ]);
+    #[test]
+    fn areas_as_floats() {
+        use std::f64::consts::PI;
 
-        assert!(grid3.loop_area().unwrap().is_integer(32));
+        let circle = Grid::<2>::from_str_art("◜◝\n◟◞").unwrap();
+        assert!((circle.area_f64().unwrap() - PI).abs() < 1e-12);
+
+        // Bulging the other way, the arcs cut the circle out of a 2x2 square.
+        let astroid = Grid::<2>::from_str_art("◞◟\n◝◜").unwrap();
+        assert!((astroid.area_f64().unwrap() - (4.0 - PI)).abs() < 1e-12);
+
+        let area = Area {
+            units: 32,
+            small: 1,
+            large: 2,
+        };
+        assert!((area.to_f64() - (33.0 + PI / 4.0)).abs() < 1e-12);
+
+        let open = Grid::<2>::from_str_art("◜·\n··").unwrap();
+        assert_eq!(open.area_f64(), Err(AreaError::LoopNotClosed));
+    }
+
+    #[test]
+    fn areas_from_units_and_fraction() {
+        let cases = [
+            ((32, 0), "32"),
+            ((32, 1), "32+π/4"),
+            ((32, -1), "32-π/4"),
+            ((32, 3), "32+3π/4"),
+            ((32, -3), "32-3π/4"),
+            ((0, 4), "0+4π/4"),
+        ];
+        for ((units, quarter_pis), display) in cases {
+            let area = Area::from_units_and_fraction(units, quarter_pis).unwrap();
+            assert_eq!(area.to_string(), display);
+            assert_eq!(area.to_units_and_fraction(), (units, quarter_pis));
+        }
+
+        assert_eq!(
+            Area::from_units_and_fraction(32, -1),
+            Some(Area {
+                units: 31,
+                small: 1,
+                large: 0
+            })
+        );
+        // Three small pieces need three units to come from.
+        assert_eq!(Area::from_units_and_fraction(2, -3), None);
+
+        // Every area comes back in its simplest form.
+        let area = Area {
+            units: 5,
+            small: 2,
+            large: 3,
+        };
+        let (units, quarter_pis) = area.to_units_and_fraction();
+        assert_eq!(
+            Area::from_units_and_fraction(units, quarter_pis),
+            Some(area.simplify())
+        );
     }
 }